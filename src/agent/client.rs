@@ -0,0 +1,239 @@
+use std::env;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use keys::{PrivateKey, PublicKey};
+
+use agent::messages::{self, Constraint, Message};
+
+use sshio::SSHWrite;
+
+/// Why the agent refused a request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+  /// SSH_AGENT_FAILURE, the agent can't or won't do it
+  Failure,
+  /// SSH_AGENT_EXTENSION_FAILURE, the extension failed
+  ExtensionFailure
+}
+
+/// Talks to an agent holding private keys, like OpenSSH's `ssh-agent`.
+pub struct Client {
+  stream: UnixStream
+}
+
+impl Client {
+  pub fn new(stream: UnixStream) -> Client {
+    return Client { stream: stream };
+  }
+
+  /// Connects to the agent listening on the Unix socket at `path`.
+  pub fn connect(path: &Path) -> io::Result<Client> {
+    return Ok(Client::new(try!(UnixStream::connect(path))));
+  }
+
+  /// Connects to the agent in `SSH_AUTH_SOCK`, if there is one.
+  pub fn from_env() -> Option<Client> {
+    return match env::var_os("SSH_AUTH_SOCK") {
+      Some(path) => Client::connect(Path::new(&path)).ok(),
+      None => None
+    };
+  }
+
+  fn request(&mut self, message: &Message) -> Message {
+    message.write(&mut self.stream);
+
+    return Message::read(&mut self.stream);
+  }
+
+  fn request_success(&mut self, message: &Message) -> Result<(), Error> {
+    return match self.request(message) {
+      Message::Success(_) => Ok(()),
+      Message::Failure => Err(Error::Failure),
+      m => panic!("Unexpected agent message {:?}", m)
+    };
+  }
+
+  /// The keys the agent holds, with their comments. Keys of types we don't
+  /// support are left out.
+  pub fn identities(&mut self) -> Result<Vec<PublicKey>, Error> {
+    return match self.request(&Message::RequestIdentities) {
+      Message::IdentitiesAnswer(identities) => Ok(identities.into_iter().filter_map(|(blob, comment)| {
+        PublicKey::from_blob(&blob[..]).ok().map(|mut key| {
+          key.comment = comment;
+          key
+        })
+      }).collect()),
+      Message::Failure => Err(Error::Failure),
+      m => panic!("Unexpected agent message {:?}", m)
+    };
+  }
+
+  /// Has the agent sign `data` with the private half of `key`, returning the
+  /// encoded signature. For RSA keys, `algorithm` picks the hash.
+  pub fn sign(&mut self, key: &PublicKey, algorithm: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let request = Message::SignRequest {
+      key_blob: key.blob(),
+      data: data.to_vec(),
+      flags: messages::signature_flags(algorithm)
+    };
+
+    return match self.request(&request) {
+      Message::SignResponse(signature) => Ok(signature),
+      Message::Failure => Err(Error::Failure),
+      m => panic!("Unexpected agent message {:?}", m)
+    };
+  }
+
+  /// Gives `key` to the agent, to use as restricted by `constraints`.
+  pub fn add(&mut self, key: &PrivateKey, comment: &str, constraints: Vec<Constraint>) -> Result<(), Error> {
+    return self.request_success(&Message::AddIdentity {
+      key: key.clone(),
+      comment: comment.to_string(),
+      constraints: constraints
+    });
+  }
+
+  /// Makes the agent forget `key`.
+  pub fn remove(&mut self, key: &PublicKey) -> Result<(), Error> {
+    return self.request_success(&Message::RemoveIdentity(key.blob()));
+  }
+
+  /// Makes the agent forget all keys.
+  pub fn remove_all(&mut self) -> Result<(), Error> {
+    return self.request_success(&Message::RemoveAllIdentities);
+  }
+
+  /// Locks the agent with `passphrase`, after which it holds no keys until
+  /// unlocked.
+  pub fn lock(&mut self, passphrase: &str) -> Result<(), Error> {
+    return self.request_success(&Message::Lock(passphrase.to_string()));
+  }
+
+  pub fn unlock(&mut self, passphrase: &str) -> Result<(), Error> {
+    return self.request_success(&Message::Unlock(passphrase.to_string()));
+  }
+
+  /// Tells the agent that requests on this connection are for the session
+  /// with `session_identifier`, as proven by the server's `signature` of
+  /// it with `host_key`, and whether they are forwarded through it.
+  ///
+  /// Keys restricted to some destinations can only be used on bound
+  /// connections.
+  pub fn bind_session(&mut self, host_key: &PublicKey, session_identifier: &[u8], signature: &[u8], forwarding: bool) -> Result<(), Error> {
+    let mut contents = io::Cursor::new(Vec::new());
+
+    contents.write_binary_string(&host_key.blob()[..]);
+    contents.write_binary_string(session_identifier);
+    contents.write_binary_string(signature);
+    contents.write_bool(forwarding);
+
+    return self.extension(messages::SESSION_BIND, &contents.into_inner()[..]).map(|_| { () });
+  }
+
+  /// Sends the extension request `name`, with its encoded `contents`,
+  /// returning the contents of the reply.
+  pub fn extension(&mut self, name: &str, contents: &[u8]) -> Result<Vec<u8>, Error> {
+    return match self.request(&Message::Extension(name.to_string(), contents.to_vec())) {
+      Message::Success(contents) => Ok(contents),
+      Message::Failure => Err(Error::Failure),
+      Message::ExtensionFailure => Err(Error::ExtensionFailure),
+      m => panic!("Unexpected agent message {:?}", m)
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::net::UnixStream;
+  use std::thread;
+
+  use keys::{ed25519, rsa, PrivateKey, PublicKey};
+
+  use agent::messages::{self, Constraint, Message};
+
+  use super::{Client, Error};
+
+  /// Answers each message the client sends with the next of `replies`,
+  /// returning the messages it got.
+  fn agent(replies: Vec<Message>) -> (Client, thread::JoinHandle<Vec<Message>>) {
+    let (client, mut server) = UnixStream::pair().unwrap();
+
+    let handle = thread::spawn(move || {
+      let mut received = Vec::new();
+
+      for reply in replies {
+        received.push(Message::read(&mut server));
+        reply.write(&mut server);
+      }
+
+      return received;
+    });
+
+    return (Client::new(client), handle);
+  }
+
+  #[test]
+  fn lists_identities() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[1; 32])).public_key();
+
+    let (mut client, handle) = agent(vec![Message::IdentitiesAnswer(vec![
+      (key.blob(), "key".to_string()),
+      (vec![0, 0, 0, 7, 115, 115, 104, 45, 100, 115, 115], "dsa".to_string())
+    ])]);
+
+    let identities = client.identities().unwrap();
+
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0].blob(), key.blob());
+    assert_eq!(identities[0].comment, "key");
+
+    assert_eq!(handle.join().unwrap(), vec![Message::RequestIdentities]);
+  }
+
+  #[test]
+  fn signs_with_flags_for_rsa_hashes() {
+    let key = PrivateKey::Rsa(rsa::PrivateKey::generate(1024));
+    let public_key = key.public_key();
+    let signature = key.sign("rsa-sha2-512", b"data");
+
+    let (mut client, handle) = agent(vec![Message::SignResponse(signature.clone()), Message::Failure]);
+
+    assert_eq!(client.sign(&public_key, "rsa-sha2-512", b"data"), Ok(signature));
+    assert_eq!(client.sign(&public_key, "ssh-rsa", b"data"), Err(Error::Failure));
+
+    assert_eq!(handle.join().unwrap(), vec![
+      Message::SignRequest { key_blob: public_key.blob(), data: b"data".to_vec(), flags: messages::RSA_SHA2_512 },
+      Message::SignRequest { key_blob: public_key.blob(), data: b"data".to_vec(), flags: 0 }
+    ]);
+  }
+
+  #[test]
+  fn manages_identities() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[2; 32]));
+    let public_key: PublicKey = key.public_key();
+
+    let (mut client, handle) = agent(vec![
+      Message::Success(Vec::new()),
+      Message::Success(Vec::new()),
+      Message::Failure,
+      Message::Success(Vec::new()),
+      Message::ExtensionFailure
+    ]);
+
+    assert_eq!(client.add(&key, "test", vec![Constraint::Lifetime(10)]), Ok(()));
+    assert_eq!(client.remove(&public_key), Ok(()));
+    assert_eq!(client.lock("secret"), Err(Error::Failure));
+    assert_eq!(client.remove_all(), Ok(()));
+    assert_eq!(client.extension("query", &[]), Err(Error::ExtensionFailure));
+
+    assert_eq!(handle.join().unwrap(), vec![
+      Message::AddIdentity { key: key.clone(), comment: "test".to_string(), constraints: vec![Constraint::Lifetime(10)] },
+      Message::RemoveIdentity(public_key.blob()),
+      Message::Lock("secret".to_string()),
+      Message::RemoveAllIdentities,
+      Message::Extension("query".to_string(), Vec::new())
+    ]);
+  }
+}
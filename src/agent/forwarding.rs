@@ -0,0 +1,453 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use rand::{self, Rng};
+
+use packets::connection::{CONNECT_FAILED, UNKNOWN_CHANNEL_TYPE};
+
+use connection::multiplexer::{Error, Event, Multiplexer};
+
+use agent::messages::MAX_MESSAGE_LENGTH;
+
+/// The session request asking the server to forward its agent connections.
+pub static REQUEST: &'static str = "auth-agent-req@openssh.com";
+/// The type of the channels the server opens for each agent connection.
+pub static CHANNEL_TYPE: &'static str = "auth-agent@openssh.com";
+
+fn too_long() -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, "agent message too long");
+}
+
+/// Takes the first whole message, with its length, out of `buffer` if it
+/// has arrived.
+fn take_message(buffer: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+  if buffer.len() < 4 {
+    return Ok(None);
+  }
+
+  let length = BigEndian::read_u32(&buffer[.. 4]) as usize;
+
+  if length > MAX_MESSAGE_LENGTH {
+    return Err(too_long());
+  }
+
+  if buffer.len() < 4 + length {
+    return Ok(None);
+  }
+
+  return Ok(Some(buffer.drain(.. 4 + length).collect()));
+}
+
+/// Reads a whole message, with its length.
+fn read_message(reader: &mut Read) -> io::Result<Vec<u8>> {
+  let mut message = vec![0; 4];
+  try!(reader.read_exact(&mut message[..]));
+
+  let length = BigEndian::read_u32(&message[..]) as usize;
+
+  if length > MAX_MESSAGE_LENGTH {
+    return Err(too_long());
+  }
+
+  message.resize(4 + length, 0);
+  try!(reader.read_exact(&mut message[4 ..]));
+
+  return Ok(message);
+}
+
+/// Relays the data of an `auth-agent@openssh.com` channel to the local
+/// agent, on the client.
+///
+/// Channel data can split and join messages as it likes, so it is buffered
+/// until whole requests arrive.
+pub struct Forwarder {
+  agent: UnixStream,
+  buffer: Vec<u8>
+}
+
+impl Forwarder {
+  /// Relays to the agent connected to with `agent`.
+  pub fn new(agent: UnixStream) -> Forwarder {
+    return Forwarder { agent: agent, buffer: Vec::new() };
+  }
+
+  /// Relays to the agent in `SSH_AUTH_SOCK`, if there is one.
+  pub fn from_env() -> Option<Forwarder> {
+    return match env::var_os("SSH_AUTH_SOCK") {
+      Some(path) => UnixStream::connect(Path::new(&path)).ok().map(Forwarder::new),
+      None => None
+    };
+  }
+
+  /// Takes `data` from the channel, returning the agent's replies to the
+  /// requests it completes, to send back.
+  ///
+  /// Fails if the agent hung up, or a message is longer than
+  /// `MAX_MESSAGE_LENGTH`, after which the channel should be closed.
+  pub fn data(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+    self.buffer.extend(data.iter().cloned());
+
+    let mut replies = Vec::new();
+
+    while let Some(request) = try!(take_message(&mut self.buffer)) {
+      try!(self.agent.write_all(&request[..]));
+
+      replies.extend(try!(read_message(&mut self.agent)));
+    }
+
+    return Ok(replies);
+  }
+}
+
+/// Serves the `auth-agent@openssh.com` channels the server opens once the
+/// client asked for agent forwarding, on the client, relaying each through
+/// a connection of its own to the local agent.
+pub struct AgentChannels {
+  agent_path: PathBuf,
+  forwarders: HashMap<u32, Forwarder>
+}
+
+impl AgentChannels {
+  /// Relays to the agent listening at `agent_path`.
+  pub fn new(agent_path: &Path) -> AgentChannels {
+    return AgentChannels { agent_path: agent_path.to_path_buf(), forwarders: HashMap::new() };
+  }
+
+  /// Relays to the agent in `SSH_AUTH_SOCK`, if there is one.
+  pub fn from_env() -> Option<AgentChannels> {
+    return env::var_os("SSH_AUTH_SOCK").map(|path| { AgentChannels::new(Path::new(&path)) });
+  }
+
+  /// Accepts the agent channels the server wants to open, rejecting other
+  /// types, and relays the data that arrived on them. Call it whenever the
+  /// multiplexer has processed packets.
+  pub fn process(&mut self, multiplexer: &mut Multiplexer) -> Result<(), Error> {
+    while let Some(open) = multiplexer.take_open() {
+      if open.channel_type != CHANNEL_TYPE {
+        multiplexer.reject(&open, UNKNOWN_CHANNEL_TYPE, "unsupported channel type");
+        continue;
+      }
+
+      match UnixStream::connect(&self.agent_path) {
+        Ok(agent) => {
+          let id = multiplexer.accept(&open, &[]);
+          self.forwarders.insert(id, Forwarder::new(agent));
+        }
+        Err(_) => multiplexer.reject(&open, CONNECT_FAILED, "could not connect to the agent")
+      }
+    }
+
+    let ids: Vec<u32> = self.forwarders.keys().cloned().collect();
+
+    for id in ids {
+      loop {
+        match try!(multiplexer.take_event(id)) {
+          Some(Event::Data(data)) => match self.forwarders.get_mut(&id).unwrap().data(&data[..]) {
+            Ok(ref replies) if replies.is_empty() => {}
+            Ok(replies) => match multiplexer.send_data(id, &replies[..]) {
+              Ok(()) | Err(Error::Closed) => {}
+              Err(e) => return Err(e)
+            },
+            Err(_) => multiplexer.close(id)
+          },
+          Some(Event::Eof) => multiplexer.close(id),
+          Some(Event::Request(request)) => if request.want_reply {
+            multiplexer.reply(id, false);
+          },
+          Some(Event::ExtendedData(_, _)) => {}
+          Some(Event::Close) => {
+            self.forwarders.remove(&id);
+            break;
+          }
+          None => break
+        }
+      }
+    }
+
+    return Ok(());
+  }
+}
+
+/// Relays `connection`, a program that connected to a `ForwardedSocket` on
+/// the server, to the client's agent through an `auth-agent@openssh.com`
+/// channel of its own, until either end hangs up.
+///
+/// Requests are relayed one at a time, and the other channels wait while
+/// the program writes its next one.
+pub fn relay_connection(multiplexer: &mut Multiplexer, connection: UnixStream) -> Result<(), Error> {
+  let mut connection = connection;
+
+  let id = try!(multiplexer.open(CHANNEL_TYPE, &[]));
+  let mut buffer = Vec::new();
+
+  'relay: loop {
+    let request = match read_message(&mut connection) {
+      Ok(request) => request,
+      Err(_) => break
+    };
+
+    match multiplexer.send_data(id, &request[..]) {
+      Ok(()) => {}
+      Err(Error::Closed) => break,
+      Err(e) => return Err(e)
+    }
+
+    loop {
+      match take_message(&mut buffer) {
+        Ok(Some(reply)) => {
+          if connection.write_all(&reply[..]).is_err() {
+            break 'relay;
+          }
+
+          break;
+        }
+        Ok(None) => {}
+        Err(_) => break 'relay
+      }
+
+      match try!(multiplexer.next_event(id)) {
+        Event::Data(data) => buffer.extend(data),
+        Event::Request(request) => if request.want_reply {
+          multiplexer.reply(id, false);
+        },
+        Event::ExtendedData(_, _) => {}
+        Event::Eof => break 'relay,
+        Event::Close => return Ok(())
+      }
+    }
+  }
+
+  multiplexer.close(id);
+
+  return Ok(());
+}
+
+/// The socket a server makes for a session that forwards the client's
+/// agent, like `/tmp/ssh-XXXXXXXXXX/agent.1234`, in a directory only the
+/// user can enter.
+///
+/// Each connection to it gets an `auth-agent@openssh.com` channel of its
+/// own, with `relay_connection`. The socket and its directory are removed
+/// when dropped.
+pub struct ForwardedSocket {
+  directory: PathBuf,
+  path: PathBuf,
+  listener: UnixListener
+}
+
+impl ForwardedSocket {
+  /// Makes a socket in the temporary directory.
+  pub fn create() -> io::Result<ForwardedSocket> {
+    let name: String = rand::thread_rng().gen_ascii_chars().take(10).collect();
+
+    return ForwardedSocket::create_in(&env::temp_dir().join(format!("ssh-{}", name)));
+  }
+
+  /// Makes a socket in `directory`, which must not exist yet.
+  pub fn create_in(directory: &Path) -> io::Result<ForwardedSocket> {
+    try!(DirBuilder::new().mode(0o700).create(directory));
+
+    let path = directory.join(format!("agent.{}", process::id()));
+
+    let listener = match UnixListener::bind(&path) {
+      Ok(listener) => listener,
+      Err(e) => {
+        let _ = fs::remove_dir(directory);
+
+        return Err(e);
+      }
+    };
+
+    return Ok(ForwardedSocket { directory: directory.to_path_buf(), path: path, listener: listener });
+  }
+
+  /// The path of the socket, for `SSH_AUTH_SOCK`.
+  pub fn path(&self) -> &Path {
+    return &self.path;
+  }
+
+  /// Waits for the next program to connect.
+  pub fn accept(&self) -> io::Result<UnixStream> {
+    return self.listener.accept().map(|(stream, _)| { stream });
+  }
+}
+
+impl Drop for ForwardedSocket {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+    let _ = fs::remove_dir(&self.directory);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use std::io::{Cursor, Read, Write};
+  use std::net::Shutdown;
+  use std::os::unix::fs::PermissionsExt;
+  use std::os::unix::net::UnixStream;
+  use std::sync::Mutex;
+  use std::thread;
+
+  use keys::{ed25519, PrivateKey};
+
+  use packets::SSHPacket;
+  use packets::connection::{ChannelOpen, ChannelOpenConfirmation, ChannelData, ChannelClose};
+
+  use authentication::client::tests::Script;
+
+  use connection::multiplexer::Multiplexer;
+
+  use agent::client::Client;
+  use agent::messages::Message;
+  use agent::server::{serve_connection, Agent};
+
+  use super::{AgentChannels, ForwardedSocket, Forwarder};
+
+  fn encode(message: Message) -> Vec<u8> {
+    let mut writer = Cursor::new(Vec::new());
+    message.write(&mut writer);
+
+    return writer.into_inner();
+  }
+
+  #[test]
+  fn relays_split_and_joined_requests() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[1; 32]));
+
+    let mut agent = Agent::new();
+    agent.add(key.clone(), "forwarded", vec![]);
+
+    let (stream, agent_stream) = UnixStream::pair().unwrap();
+    thread::spawn(move || { serve_connection(&Mutex::new(agent), agent_stream) });
+
+    let mut forwarder = Forwarder::new(stream);
+
+    let mut requests = Cursor::new(Vec::new());
+    Message::RequestIdentities.write(&mut requests);
+    Message::SignRequest { key_blob: key.public_key_blob(), data: vec![1, 2], flags: 0 }.write(&mut requests);
+
+    let requests = requests.into_inner();
+
+    assert_eq!(forwarder.data(&requests[.. 3]).unwrap(), vec![]);
+
+    let mut replies = Cursor::new(forwarder.data(&requests[3 ..]).unwrap());
+
+    assert_eq!(Message::read(&mut replies), Message::IdentitiesAnswer(vec![(key.public_key_blob(), "forwarded".to_string())]));
+    assert_eq!(Message::read(&mut replies), Message::SignResponse(key.sign("ssh-ed25519", &[1, 2])));
+    assert_eq!(Message::read_next(&mut replies).unwrap(), None);
+  }
+
+  #[test]
+  fn refuses_overlong_messages() {
+    let (stream, _agent_stream) = UnixStream::pair().unwrap();
+
+    assert!(Forwarder::new(stream).data(&[0, 4, 0, 1]).is_err());
+  }
+
+  #[test]
+  fn serves_agent_channels_on_the_client() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[1; 32]));
+
+    let mut agent = Agent::new();
+    agent.add(key.clone(), "forwarded", vec![]);
+
+    let socket = ForwardedSocket::create().unwrap();
+    let path = socket.path().to_path_buf();
+
+    let served = thread::spawn(move || { serve_connection(&Mutex::new(agent), socket.accept().unwrap()) });
+
+    let open = ChannelOpen { channel_type: "auth-agent@openssh.com".to_string(), sender_channel: 3, initial_window_size: 1024, maximum_packet_size: 1024, data: vec![] };
+
+    let mut script = Script::new(vec![
+      SSHPacket::ChannelOpen(open.clone()),
+      SSHPacket::ChannelOpen(ChannelOpen { channel_type: "x11".to_string(), sender_channel: 4, ..open }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: encode(Message::RequestIdentities) }),
+      SSHPacket::ChannelClose(ChannelClose { recipient_channel: 0 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let mut channels = AgentChannels::new(&path);
+
+      for _ in 0 .. 4 {
+        multiplexer.process().unwrap();
+        channels.process(&mut multiplexer).unwrap();
+      }
+    }
+
+    served.join().unwrap();
+
+    let outgoing: Vec<String> = script.outgoing.iter().map(|p| { format!("{:?}", p) }).collect();
+    let answer = encode(Message::IdentitiesAnswer(vec![(key.public_key_blob(), "forwarded".to_string())]));
+
+    assert!(outgoing[0].starts_with("ChannelOpenConfirmation(ChannelOpenConfirmation { recipient_channel: 3, sender_channel: 0"));
+    assert!(outgoing[1].starts_with("ChannelOpenFailure(ChannelOpenFailure { recipient_channel: 4, reason_code: 3"));
+    assert_eq!(outgoing[2], format!("{:?}", SSHPacket::ChannelData(ChannelData { recipient_channel: 3, data: answer })));
+    assert_eq!(outgoing[3], format!("{:?}", SSHPacket::ChannelClose(ChannelClose { recipient_channel: 3 })));
+  }
+
+  #[test]
+  fn relays_connections_on_the_server() {
+    let request = encode(Message::RequestIdentities);
+    let answer = encode(Message::IdentitiesAnswer(vec![]));
+
+    let mut script = Script::new(vec![
+      SSHPacket::ChannelOpenConfirmation(ChannelOpenConfirmation { recipient_channel: 0, sender_channel: 5, initial_window_size: 1024, maximum_packet_size: 1024, data: vec![] }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: answer[.. 3].to_vec() }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: answer[3 ..].to_vec() })
+    ]);
+
+    let (mut program, connection) = UnixStream::pair().unwrap();
+
+    program.write_all(&request[..]).unwrap();
+    program.shutdown(Shutdown::Write).unwrap();
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+
+      assert_eq!(super::relay_connection(&mut multiplexer, connection), Ok(()));
+    }
+
+    let mut reply = Vec::new();
+    program.read_to_end(&mut reply).unwrap();
+
+    assert_eq!(reply, answer);
+
+    match script.outgoing[0] {
+      SSHPacket::ChannelOpen(ref open) => assert_eq!(open.channel_type, "auth-agent@openssh.com"),
+      ref p => panic!("Unexpected packet {:?}", p)
+    }
+
+    assert_eq!(format!("{:?}", script.outgoing[1]), format!("{:?}", SSHPacket::ChannelData(ChannelData { recipient_channel: 5, data: request })));
+    assert_eq!(format!("{:?}", script.outgoing[2]), format!("{:?}", SSHPacket::ChannelClose(ChannelClose { recipient_channel: 5 })));
+  }
+
+  #[test]
+  fn makes_private_sockets() {
+    let socket = ForwardedSocket::create().unwrap();
+    let path = socket.path().to_path_buf();
+    let directory = path.parent().unwrap().to_path_buf();
+
+    assert_eq!(fs::metadata(&directory).unwrap().permissions().mode() & 0o777, 0o700);
+
+    let agent = thread::spawn(move || {
+      let connection = socket.accept().unwrap();
+      serve_connection(&Mutex::new(Agent::new()), connection);
+    });
+
+    assert_eq!(Client::new(UnixStream::connect(&path).unwrap()).identities(), Ok(vec![]));
+
+    agent.join().unwrap();
+
+    assert!(fs::metadata(&directory).is_err());
+  }
+}
@@ -0,0 +1,455 @@
+use std::io::{self, Read, Write};
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+
+use keys::{PrivateKey, PublicKey};
+
+use sshio::{Malformed, SSHRead, SSHWrite};
+
+/// The longest message, with its type, that is read, as OpenSSH's agent
+/// allows.
+pub const MAX_MESSAGE_LENGTH: usize = 256 * 1024;
+
+/// Sign with `rsa-sha2-256` instead of `ssh-rsa`.
+pub const RSA_SHA2_256: u32 = 2;
+/// Sign with `rsa-sha2-512` instead of `ssh-rsa`.
+pub const RSA_SHA2_512: u32 = 4;
+
+/// The flags of a sign request that ask for signatures with `algorithm`.
+pub fn signature_flags(algorithm: &str) -> u32 {
+  return match algorithm {
+    "rsa-sha2-256" => RSA_SHA2_256,
+    "rsa-sha2-512" => RSA_SHA2_512,
+    _ => 0
+  };
+}
+
+/// The extension constraint restricting where a key may be used.
+pub static RESTRICT_DESTINATION: &'static str = "restrict-destination-v00@openssh.com";
+/// The extension binding an agent connection to an SSH session.
+pub static SESSION_BIND: &'static str = "session-bind@openssh.com";
+
+/// A host in a destination constraint, identified by its host keys or the
+/// CAs that sign them. Without a host name, the origin, the agent's host.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hop {
+  /// A pattern for the users the key may log in as, empty for any
+  pub user_name: String,
+  pub host_name: String,
+  /// Host keys, each with whether it is a CA
+  pub keys: Vec<(PublicKey, bool)>
+}
+
+impl Hop {
+  fn read(data: &[u8]) -> Result<Hop, Malformed> {
+    let mut reader = io::Cursor::new(data);
+
+    let user_name = try!(reader.try_read_string());
+    let host_name = try!(reader.try_read_string());
+    try!(reader.try_read_binary_string());
+
+    let mut keys = Vec::new();
+
+    while (reader.position() as usize) < data.len() {
+      let key = match PublicKey::from_blob(&try!(reader.try_read_binary_string())[..]) {
+        Ok(key) => key,
+        Err(_) => return Err(Malformed)
+      };
+
+      keys.push((key, try!(reader.try_read_bool())));
+    }
+
+    return Ok(Hop { user_name: user_name, host_name: host_name, keys: keys });
+  }
+
+  fn write(&self, writer: &mut Write) {
+    let mut hop = io::Cursor::new(Vec::new());
+
+    hop.write_string(self.user_name.as_str());
+    hop.write_string(self.host_name.as_str());
+    hop.write_binary_string(&[]);
+
+    for &(ref key, certificate_authority) in self.keys.iter() {
+      hop.write_binary_string(&key.blob()[..]);
+      hop.write_bool(certificate_authority);
+    }
+
+    let mut writer = writer;
+
+    writer.write_binary_string(&hop.into_inner()[..]);
+  }
+}
+
+/// A hop a key may be used for, from one host to another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Destination {
+  pub from: Hop,
+  pub to: Hop
+}
+
+/// A restriction on the use of a key added to an agent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+  /// Forget the key after this many seconds
+  Lifetime(u32),
+  /// Ask the user before each use of the key
+  Confirm,
+  /// Only use the key along these hops, the `RESTRICT_DESTINATION`
+  /// extension
+  Destinations(Vec<Destination>),
+  /// A constraint defined by an extension, with its still encoded contents
+  Extension(String, Vec<u8>)
+}
+
+impl Constraint {
+  /// Reads a constraint, failing for ones of unknown types too.
+  fn read(reader: &mut io::Cursor<&[u8]>) -> Result<Constraint, Malformed> {
+    return match try!(reader.try_read_n(1))[0] {
+      1 => Ok(Constraint::Lifetime(try!(reader.try_read_uint32()))),
+      2 => Ok(Constraint::Confirm),
+      255 => {
+        let name = try!(reader.try_read_string());
+
+        if name == RESTRICT_DESTINATION {
+          return Constraint::read_destinations(&try!(reader.try_read_binary_string())[..]);
+        }
+
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+
+        Ok(Constraint::Extension(name, contents))
+      }
+      _ => Err(Malformed)
+    };
+  }
+
+  fn read_destinations(data: &[u8]) -> Result<Constraint, Malformed> {
+    let mut reader = io::Cursor::new(data);
+    let mut destinations = Vec::new();
+
+    while (reader.position() as usize) < data.len() {
+      let mut destination = io::Cursor::new(try!(reader.try_read_binary_string()));
+
+      let from = try!(Hop::read(&try!(destination.try_read_binary_string())[..]));
+      let to = try!(Hop::read(&try!(destination.try_read_binary_string())[..]));
+
+      destinations.push(Destination { from: from, to: to });
+    }
+
+    return Ok(Constraint::Destinations(destinations));
+  }
+
+  fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    match self {
+      &Constraint::Lifetime(seconds) => {
+        writer.write_u8(1).unwrap();
+        writer.write_uint32(seconds);
+      }
+      &Constraint::Confirm => writer.write_u8(2).unwrap(),
+      &Constraint::Destinations(ref destinations) => {
+        let mut contents = io::Cursor::new(Vec::new());
+
+        for destination in destinations.iter() {
+          let mut hops = io::Cursor::new(Vec::new());
+
+          destination.from.write(&mut hops);
+          destination.to.write(&mut hops);
+          hops.write_binary_string(&[]);
+
+          contents.write_binary_string(&hops.into_inner()[..]);
+        }
+
+        writer.write_u8(255).unwrap();
+        writer.write_string(RESTRICT_DESTINATION);
+        writer.write_binary_string(&contents.into_inner()[..]);
+      }
+      &Constraint::Extension(ref name, ref contents) => {
+        writer.write_u8(255).unwrap();
+        writer.write_string(name.as_str());
+        writer.write_all(&contents[..]).unwrap();
+      }
+    }
+  }
+}
+
+/// A message of the agent protocol.
+///
+/// See [draft-miller-ssh-agent](https://tools.ietf.org/html/draft-miller-ssh-agent).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+  /// SSH_AGENT_FAILURE, 5
+  Failure,
+  /// SSH_AGENT_SUCCESS, 6, with the contents of an extension's reply
+  Success(Vec<u8>),
+  /// SSH_AGENTC_REQUEST_IDENTITIES, 11
+  RequestIdentities,
+  /// SSH_AGENT_IDENTITIES_ANSWER, 12, public key blobs with their comments
+  IdentitiesAnswer(Vec<(Vec<u8>, String)>),
+  /// SSH_AGENTC_SIGN_REQUEST, 13
+  SignRequest {
+    key_blob: Vec<u8>,
+    data: Vec<u8>,
+    flags: u32
+  },
+  /// SSH_AGENT_SIGN_RESPONSE, 14, an encoded signature
+  SignResponse(Vec<u8>),
+  /// SSH_AGENTC_ADD_IDENTITY, 17, or SSH_AGENTC_ADD_ID_CONSTRAINED, 25
+  AddIdentity {
+    key: PrivateKey,
+    comment: String,
+    constraints: Vec<Constraint>
+  },
+  /// SSH_AGENTC_REMOVE_IDENTITY, 18, a public key blob
+  RemoveIdentity(Vec<u8>),
+  /// SSH_AGENTC_REMOVE_ALL_IDENTITIES, 19
+  RemoveAllIdentities,
+  /// SSH_AGENTC_LOCK, 22, with a passphrase
+  Lock(String),
+  /// SSH_AGENTC_UNLOCK, 23, with a passphrase
+  Unlock(String),
+  /// SSH_AGENTC_EXTENSION, 27, with the extension's still encoded contents
+  Extension(String, Vec<u8>),
+  /// SSH_AGENT_EXTENSION_FAILURE, 28
+  ExtensionFailure,
+  /// Any other message, or one we could not make sense of, like a key of a
+  /// type we don't support
+  Other(u8, Vec<u8>)
+}
+
+impl Message {
+  /// Reads a message, with its length, from an agent.
+  pub fn read(reader: &mut Read) -> Message {
+    return match Message::read_next(reader) {
+      Ok(Some(message)) => message,
+      Ok(None) => panic!("Agent connection closed"),
+      Err(e) => panic!("Can't read from agent connection: {}", e)
+    };
+  }
+
+  /// Reads a message, or returns `None` if the connection was closed
+  /// before the next one.
+  ///
+  /// Fails for messages that are empty, longer than `MAX_MESSAGE_LENGTH`
+  /// or malformed, after which the connection should be closed.
+  pub fn read_next(reader: &mut Read) -> io::Result<Option<Message>> {
+    let mut length = [0; 4];
+
+    if try!(reader.read(&mut length[.. 1])) == 0 {
+      return Ok(None);
+    }
+
+    try!(reader.read_exact(&mut length[1 ..]));
+
+    let length = BigEndian::read_u32(&length) as usize;
+
+    if length == 0 || length > MAX_MESSAGE_LENGTH {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid agent message length"));
+    }
+
+    let mut payload = vec![0; length];
+    try!(reader.read_exact(&mut payload[..]));
+
+    return match Message::decode(payload[0], payload[1 ..].to_vec()) {
+      Ok(message) => Ok(Some(message)),
+      Err(Malformed) => Err(io::Error::new(io::ErrorKind::InvalidData, "malformed agent message"))
+    };
+  }
+
+  fn decode(message_type: u8, payload: Vec<u8>) -> Result<Message, Malformed> {
+    let mut reader = io::Cursor::new(&payload[..]);
+
+    return Ok(match message_type {
+      5 => Message::Failure,
+      6 => Message::Success(payload.clone()),
+      11 => Message::RequestIdentities,
+      12 => {
+        let count = try!(reader.try_read_uint32());
+        let mut identities = Vec::new();
+
+        for _ in 0 .. count {
+          let key_blob = try!(reader.try_read_binary_string());
+
+          identities.push((key_blob, try!(reader.try_read_string())));
+        }
+
+        Message::IdentitiesAnswer(identities)
+      }
+      13 => Message::SignRequest {
+        key_blob: try!(reader.try_read_binary_string()),
+        data: try!(reader.try_read_binary_string()),
+        flags: try!(reader.try_read_uint32())
+      },
+      14 => Message::SignResponse(try!(reader.try_read_binary_string())),
+      17 | 25 => {
+        let key = match PrivateKey::read(&mut reader) {
+          Ok(key) => key,
+          Err(_) => return Ok(Message::Other(message_type, payload.clone()))
+        };
+
+        let comment = try!(reader.try_read_string());
+        let mut constraints = Vec::new();
+
+        while (reader.position() as usize) < payload.len() {
+          match Constraint::read(&mut reader) {
+            Ok(constraint) => constraints.push(constraint),
+            Err(_) => return Ok(Message::Other(message_type, payload.clone()))
+          }
+        }
+
+        Message::AddIdentity { key: key, comment: comment, constraints: constraints }
+      }
+      18 => Message::RemoveIdentity(try!(reader.try_read_binary_string())),
+      19 => Message::RemoveAllIdentities,
+      22 => Message::Lock(try!(reader.try_read_string())),
+      23 => Message::Unlock(try!(reader.try_read_string())),
+      27 => {
+        let name = try!(reader.try_read_string());
+
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+
+        Message::Extension(name, contents)
+      }
+      28 => Message::ExtensionFailure,
+      _ => Message::Other(message_type, payload.clone())
+    });
+  }
+
+  /// Writes the message, with its length.
+  pub fn write(&self, writer: &mut Write) {
+    let mut payload = io::Cursor::new(Vec::new());
+
+    payload.write_u8(self.message_type()).unwrap();
+
+    match self {
+      &Message::Failure | &Message::RequestIdentities | &Message::RemoveAllIdentities | &Message::ExtensionFailure => {}
+      &Message::Success(ref contents) => payload.write_all(&contents[..]).unwrap(),
+      &Message::IdentitiesAnswer(ref identities) => {
+        payload.write_uint32(identities.len() as u32);
+
+        for &(ref key_blob, ref comment) in identities.iter() {
+          payload.write_binary_string(&key_blob[..]);
+          payload.write_string(comment.as_str());
+        }
+      }
+      &Message::SignRequest { ref key_blob, ref data, flags } => {
+        payload.write_binary_string(&key_blob[..]);
+        payload.write_binary_string(&data[..]);
+        payload.write_uint32(flags);
+      }
+      &Message::SignResponse(ref signature) => payload.write_binary_string(&signature[..]),
+      &Message::AddIdentity { ref key, ref comment, ref constraints } => {
+        key.write(&mut payload);
+        payload.write_string(comment.as_str());
+
+        for constraint in constraints.iter() {
+          constraint.write(&mut payload);
+        }
+      }
+      &Message::RemoveIdentity(ref key_blob) => payload.write_binary_string(&key_blob[..]),
+      &Message::Lock(ref passphrase) | &Message::Unlock(ref passphrase) => payload.write_string(passphrase.as_str()),
+      &Message::Extension(ref name, ref contents) => {
+        payload.write_string(name.as_str());
+        payload.write_all(&contents[..]).unwrap();
+      }
+      &Message::Other(_, ref contents) => payload.write_all(&contents[..]).unwrap()
+    }
+
+    let mut writer = writer;
+
+    writer.write_binary_string(&payload.into_inner()[..]);
+  }
+
+  fn message_type(&self) -> u8 {
+    return match self {
+      &Message::Failure => 5,
+      &Message::Success(_) => 6,
+      &Message::RequestIdentities => 11,
+      &Message::IdentitiesAnswer(_) => 12,
+      &Message::SignRequest { .. } => 13,
+      &Message::SignResponse(_) => 14,
+      &Message::AddIdentity { ref constraints, .. } => if constraints.is_empty() { 17 } else { 25 },
+      &Message::RemoveIdentity(_) => 18,
+      &Message::RemoveAllIdentities => 19,
+      &Message::Lock(_) => 22,
+      &Message::Unlock(_) => 23,
+      &Message::Extension(..) => 27,
+      &Message::ExtensionFailure => 28,
+      &Message::Other(t, _) => t
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use keys::{ed25519, PrivateKey};
+
+  use super::{Constraint, Destination, Hop, Message};
+
+  fn roundtrip(message: Message) {
+    let mut writer = Cursor::new(Vec::new());
+
+    message.write(&mut writer);
+
+    assert_eq!(Message::read(&mut Cursor::new(writer.into_inner())), message);
+  }
+
+  #[test]
+  fn roundtrips() {
+    roundtrip(Message::RequestIdentities);
+    roundtrip(Message::IdentitiesAnswer(vec![(vec![1, 2, 3], "key".to_string()), (vec![4], "".to_string())]));
+    roundtrip(Message::SignRequest { key_blob: vec![1, 2], data: vec![3, 4], flags: 4 });
+    roundtrip(Message::SignResponse(vec![5, 6]));
+    roundtrip(Message::Success(Vec::new()));
+    roundtrip(Message::Lock("hunter2".to_string()));
+    roundtrip(Message::Extension("query".to_string(), vec![]));
+  }
+
+  #[test]
+  fn roundtrips_identities_with_constraints() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[7; 32]));
+
+    roundtrip(Message::AddIdentity { key: key.clone(), comment: "test".to_string(), constraints: vec![] });
+    roundtrip(Message::AddIdentity {
+      key: key,
+      comment: "test".to_string(),
+      constraints: vec![Constraint::Lifetime(60), Constraint::Confirm]
+    });
+  }
+
+  #[test]
+  fn roundtrips_destination_constraints() {
+    let host_key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[8; 32])).public_key();
+
+    roundtrip(Message::AddIdentity {
+      key: PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[7; 32])),
+      comment: "test".to_string(),
+      constraints: vec![Constraint::Destinations(vec![Destination {
+        from: Hop { user_name: String::new(), host_name: String::new(), keys: vec![] },
+        to: Hop { user_name: "alice".to_string(), host_name: "server".to_string(), keys: vec![(host_key, false)] }
+      }]), Constraint::Confirm]
+    });
+  }
+
+  #[test]
+  fn keeps_messages_it_does_not_understand() {
+    let data = [0, 0, 0, 13, 17, 0, 0, 0, 7, 115, 115, 104, 45, 100, 115, 115, 0];
+
+    assert_eq!(Message::read(&mut Cursor::new(&data[..])), Message::Other(17, data[5 ..].to_vec()));
+    assert_eq!(Message::read(&mut Cursor::new(&[0, 0, 0, 2, 99, 1][..])), Message::Other(99, vec![1]));
+  }
+
+  #[test]
+  fn rejects_invalid_lengths_and_malformed_messages() {
+    assert!(Message::read_next(&mut Cursor::new(&[0, 0, 0, 0][..])).is_err());
+    assert!(Message::read_next(&mut Cursor::new(&[0, 4, 0, 1, 11][..])).is_err());
+    assert!(Message::read_next(&mut Cursor::new(&[0, 0, 0, 2, 11][..])).is_err());
+    assert!(Message::read_next(&mut Cursor::new(&[0, 0, 0, 3, 22, 0, 0][..])).is_err());
+    assert!(Message::read_next(&mut Cursor::new(&[0, 0, 0, 5, 12, 0, 0, 0, 1][..])).is_err());
+    assert_eq!(Message::read_next(&mut Cursor::new(&[][..])).unwrap(), None);
+  }
+}
@@ -0,0 +1,629 @@
+use std::io;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{self, Rng};
+
+use hash::constant_time_eq;
+
+use keys::{Key, PrivateKey, PublicKey};
+use keys::bcrypt_pbkdf::bcrypt_pbkdf;
+use keys::certificate::CertificateType;
+
+use agent::messages::{self, Constraint, Destination, Hop, Message};
+
+use pattern;
+
+use sshio::SSHRead;
+
+/// The most sessions a connection can be bound to, as in OpenSSH.
+const MAXIMUM_BINDINGS: usize = 16;
+
+/// Asks the user whether a key added with `Constraint::Confirm` may be
+/// used, usually with a dialog.
+pub trait Confirmer {
+  fn confirm(&mut self, key: &PublicKey) -> bool;
+}
+
+/// A key the agent holds, with its comment in `public_key`.
+struct Identity {
+  key: PrivateKey,
+  public_key: PublicKey,
+  expires: Option<Instant>,
+  confirm: bool,
+  destinations: Vec<Destination>
+}
+
+/// A session a connection was bound to with `session-bind@openssh.com`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+  pub host_key: PublicKey,
+  pub session_identifier: Vec<u8>,
+  /// Whether the connection is forwarded through the session, rather than
+  /// used to authenticate it
+  pub forwarding: bool
+}
+
+/// What the agent knows about one connection to it.
+pub struct Connection {
+  bindings: Vec<Binding>
+}
+
+impl Connection {
+  pub fn new() -> Connection {
+    return Connection { bindings: Vec::new() };
+  }
+
+  /// The sessions the connection was bound to, from the origin onwards.
+  pub fn bindings(&self) -> &Vec<Binding> {
+    return &self.bindings;
+  }
+}
+
+/// Holds private keys in memory and signs with them for clients, like
+/// OpenSSH's `ssh-agent`.
+///
+/// Keys can be restricted to a lifetime, to uses the `Confirmer` allows and
+/// to hosts reached through sessions the connection is bound to.
+pub struct Agent {
+  identities: Vec<Identity>,
+  /// The salt and hash of the passphrase, while locked
+  lock: Option<(Vec<u8>, Vec<u8>)>,
+  confirmer: Option<Box<Confirmer + Send>>
+}
+
+impl Agent {
+  pub fn new() -> Agent {
+    return Agent { identities: Vec::new(), lock: None, confirmer: None };
+  }
+
+  /// Sets what asks the user about keys added with `Constraint::Confirm`.
+  /// Without one, those keys can't be used.
+  pub fn set_confirmer(&mut self, confirmer: Box<Confirmer + Send>) {
+    self.confirmer = Some(confirmer);
+  }
+
+  /// Adds `key`, or replaces the constraints of a key already held. Returns
+  /// `false` for constraints defined by extensions we don't know.
+  pub fn add(&mut self, key: PrivateKey, comment: &str, constraints: Vec<Constraint>) -> bool {
+    let mut public_key = key.public_key();
+    public_key.comment = comment.to_string();
+
+    let mut identity = Identity { key: key, public_key: public_key, expires: None, confirm: false, destinations: Vec::new() };
+
+    for constraint in constraints {
+      match constraint {
+        Constraint::Lifetime(seconds) => identity.expires = Some(Instant::now() + Duration::from_secs(seconds as u64)),
+        Constraint::Confirm => identity.confirm = true,
+        Constraint::Destinations(destinations) => identity.destinations.extend(destinations),
+        Constraint::Extension(..) => return false
+      }
+    }
+
+    let blob = identity.public_key.blob();
+
+    self.identities.retain(|i| { i.public_key.blob() != blob });
+    self.identities.push(identity);
+
+    return true;
+  }
+
+  /// Answers a request on `connection`.
+  pub fn handle(&mut self, connection: &mut Connection, message: Message) -> Message {
+    let now = Instant::now();
+
+    self.identities.retain(|i| { i.expires.map_or(true, |e| { now < e }) });
+
+    if self.lock.is_some() {
+      return match message {
+        Message::RequestIdentities => Message::IdentitiesAnswer(Vec::new()),
+        Message::Unlock(passphrase) => self.unlock(&passphrase),
+        _ => Message::Failure
+      };
+    }
+
+    return match message {
+      Message::RequestIdentities => Message::IdentitiesAnswer(self.identities.iter().filter(|i| {
+        permitted(i, connection, None)
+      }).map(|i| {
+        (i.public_key.blob(), i.public_key.comment.clone())
+      }).collect()),
+      Message::SignRequest { key_blob, data, flags } => self.sign(connection, &key_blob[..], &data[..], flags),
+      Message::AddIdentity { key, comment, constraints } => success(self.add(key, &comment, constraints)),
+      Message::RemoveIdentity(key_blob) => {
+        let count = self.identities.len();
+
+        self.identities.retain(|i| { i.public_key.blob() != key_blob });
+
+        success(self.identities.len() < count)
+      }
+      Message::RemoveAllIdentities => {
+        self.identities.clear();
+
+        Message::Success(Vec::new())
+      }
+      Message::Lock(passphrase) => {
+        let mut salt = vec![0; 16];
+        rand::thread_rng().fill_bytes(&mut salt[..]);
+
+        let hash = bcrypt_pbkdf(passphrase.as_bytes(), &salt[..], 1, 32);
+
+        self.lock = Some((salt, hash));
+
+        Message::Success(Vec::new())
+      }
+      Message::Extension(ref name, ref contents) if name == messages::SESSION_BIND => success(bind(connection, &contents[..])),
+      _ => Message::Failure
+    };
+  }
+
+  fn unlock(&mut self, passphrase: &str) -> Message {
+    let unlocked = match self.lock {
+      Some((ref salt, ref hash)) => constant_time_eq(&bcrypt_pbkdf(passphrase.as_bytes(), &salt[..], 1, 32)[..], &hash[..]),
+      None => false
+    };
+
+    if unlocked {
+      self.lock = None;
+    }
+
+    return success(unlocked);
+  }
+
+  /// Signs `data`, if the key may be used for it.
+  ///
+  /// Keys restricted to destinations only sign user authentication requests
+  /// for the session the connection was bound to last.
+  fn sign(&mut self, connection: &Connection, key_blob: &[u8], data: &[u8], flags: u32) -> Message {
+    let identity = match self.identities.iter().find(|i| { i.public_key.blob() == key_blob }) {
+      Some(identity) => identity,
+      None => return Message::Failure
+    };
+
+    if !identity.destinations.is_empty() {
+      let binding = match connection.bindings.last() {
+        Some(binding) => binding,
+        None => return Message::Failure
+      };
+
+      let (session_identifier, user_name, host_key) = match parse_user_authentication(data, key_blob) {
+        Some(request) => request,
+        None => return Message::Failure
+      };
+
+      if !permitted(identity, connection, Some(&user_name)) || session_identifier != binding.session_identifier {
+        return Message::Failure;
+      }
+
+      let host_key_matches = match host_key {
+        Some(ref host_key) => host_key.blob() == binding.host_key.blob(),
+        None => connection.bindings.len() == 1
+      };
+
+      if !host_key_matches {
+        return Message::Failure;
+      }
+    }
+
+    if identity.confirm {
+      let confirmed = match self.confirmer {
+        Some(ref mut confirmer) => confirmer.confirm(&identity.public_key),
+        None => false
+      };
+
+      if !confirmed {
+        return Message::Failure;
+      }
+    }
+
+    let algorithm = match identity.key {
+      PrivateKey::Rsa(_) if flags & messages::RSA_SHA2_512 != 0 => "rsa-sha2-512",
+      PrivateKey::Rsa(_) if flags & messages::RSA_SHA2_256 != 0 => "rsa-sha2-256",
+      PrivateKey::Rsa(_) => "ssh-rsa",
+      _ => identity.key.algorithm()
+    };
+
+    return Message::SignResponse(identity.key.sign(algorithm, data));
+  }
+}
+
+fn success(ok: bool) -> Message {
+  return if ok { Message::Success(Vec::new()) } else { Message::Failure };
+}
+
+/// Binds `connection` to the session in a `session-bind@openssh.com`
+/// request, if the host key's signature of the session identifier is good.
+///
+/// A connection used to authenticate can't be bound again, except to the
+/// same session.
+fn bind(connection: &mut Connection, contents: &[u8]) -> bool {
+  let mut reader = io::Cursor::new(contents);
+
+  let host_key = match PublicKey::from_blob(&reader.read_binary_string()[..]) {
+    Ok(key) => key,
+    Err(_) => return false
+  };
+
+  let session_identifier = reader.read_binary_string();
+  let signature = reader.read_binary_string();
+  let forwarding = reader.read_bool();
+
+  if !host_key.verify(&session_identifier[..], &signature[..]) {
+    return false;
+  }
+
+  let binding = Binding { host_key: host_key, session_identifier: session_identifier, forwarding: forwarding };
+
+  if let Some(bound) = connection.bindings.iter().find(|b| { b.session_identifier == binding.session_identifier }) {
+    return bound.host_key.blob() == binding.host_key.blob() && bound.forwarding == binding.forwarding;
+  }
+
+  if connection.bindings.iter().any(|b| { !b.forwarding }) || connection.bindings.len() >= MAXIMUM_BINDINGS {
+    return false;
+  }
+
+  connection.bindings.push(binding);
+
+  return true;
+}
+
+/// Parses a `publickey` user authentication request signed with the key in
+/// `key_blob`, returning its session identifier, user name and, for
+/// `publickey-hostbound-v00@openssh.com`, the server's host key.
+fn parse_user_authentication(data: &[u8], key_blob: &[u8]) -> Option<(Vec<u8>, String, Option<PublicKey>)> {
+  let mut reader = io::Cursor::new(data);
+
+  let session_identifier = reader.read_binary_string();
+
+  if reader.read_n(1) != [50] {
+    return None;
+  }
+
+  let user_name = reader.read_string();
+  reader.read_string();
+
+  let method = reader.read_string();
+
+  if (method != "publickey" && method != "publickey-hostbound-v00@openssh.com") || !reader.read_bool() {
+    return None;
+  }
+
+  reader.read_string();
+
+  if reader.read_binary_string() != key_blob {
+    return None;
+  }
+
+  let mut host_key = None;
+
+  if method != "publickey" {
+    host_key = match PublicKey::from_blob(&reader.read_binary_string()[..]) {
+      Ok(key) => Some(key),
+      Err(_) => return None
+    };
+  }
+
+  if reader.position() != data.len() as u64 {
+    return None;
+  }
+
+  return Some((session_identifier, user_name, host_key));
+}
+
+/// Whether the hosts `connection` was bound to are a path the destination
+/// constraints of `identity` allow, ending in a login as `user_name` if
+/// given.
+///
+/// Keys without destination constraints may be used anywhere, and keys with
+/// them may be listed on unbound connections, where only local use is
+/// possible.
+fn permitted(identity: &Identity, connection: &Connection, user_name: Option<&str>) -> bool {
+  if identity.destinations.is_empty() || connection.bindings.is_empty() {
+    return true;
+  }
+
+  let mut from = None;
+
+  for (i, binding) in connection.bindings.iter().enumerate() {
+    let last = i == connection.bindings.len() - 1;
+
+    if !binding.forwarding && !last {
+      return false;
+    }
+
+    let user_name = if last && !binding.forwarding { user_name } else { None };
+
+    if !identity.destinations.iter().any(|d| { permits(d, from, Some(&binding.host_key), user_name) }) {
+      return false;
+    }
+
+    from = Some(&binding.host_key);
+  }
+
+  if connection.bindings.last().unwrap().forwarding {
+    return user_name.is_none() && identity.destinations.iter().any(|d| { permits(d, from, None, None) });
+  }
+
+  return true;
+}
+
+/// Whether `destination` allows a hop from the host with the key `from`, or
+/// the origin, to the one with the key `to`, or anywhere, logging in as
+/// `user_name`.
+fn permits(destination: &Destination, from: Option<&PublicKey>, to: Option<&PublicKey>, user_name: Option<&str>) -> bool {
+  let from_matches = match from {
+    Some(key) => hop_matches(&destination.from, key),
+    None => destination.from.host_name.is_empty() && destination.from.keys.is_empty()
+  };
+
+  if !from_matches || !to.map_or(true, |key| { hop_matches(&destination.to, key) }) {
+    return false;
+  }
+
+  return match user_name {
+    Some(user_name) if !destination.to.user_name.is_empty() => pattern::matches(user_name, &destination.to.user_name),
+    _ => true
+  };
+}
+
+/// Whether `key` is one of the keys of `hop`, or a host certificate signed
+/// by one of its CAs.
+fn hop_matches(hop: &Hop, key: &PublicKey) -> bool {
+  return hop.keys.iter().any(|&(ref hop_key, certificate_authority)| {
+    if !certificate_authority {
+      return hop_key.blob() == key.blob();
+    }
+
+    return match key.key {
+      Key::Certificate(ref c) => c.certificate_type == CertificateType::Host && c.signature_key.blob() == hop_key.blob(),
+      _ => false
+    };
+  });
+}
+
+/// Answers requests on `stream` until the client closes it.
+pub fn serve_connection(agent: &Mutex<Agent>, stream: UnixStream) {
+  let mut stream = stream;
+  let mut connection = Connection::new();
+
+  // Malformed requests end the connection, like closing it does.
+  while let Ok(Some(message)) = Message::read_next(&mut stream) {
+    // A connection that panicked on a malformed request leaves the agent
+    // poisoned, but not broken.
+    let reply = match agent.lock() {
+      Ok(mut agent) => agent.handle(&mut connection, message),
+      Err(poisoned) => poisoned.into_inner().handle(&mut connection, message)
+    };
+
+    reply.write(&mut stream);
+  }
+}
+
+/// Serves each connection to `listener` on a thread of its own.
+pub fn serve(agent: Arc<Mutex<Agent>>, listener: UnixListener) {
+  for stream in listener.incoming() {
+    let stream = match stream {
+      Ok(stream) => stream,
+      Err(_) => continue
+    };
+
+    let agent = agent.clone();
+
+    thread::spawn(move || { serve_connection(&agent, stream) });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{Cursor, Write};
+  use std::os::unix::net::UnixStream;
+  use std::sync::{Arc, Mutex};
+  use std::thread;
+
+  use keys::{ed25519, rsa, PrivateKey, PublicKey};
+
+  use sshio::SSHWrite;
+
+  use agent::client::{self, Client};
+  use agent::messages::{Constraint, Destination, Hop, Message};
+
+  use super::{serve_connection, Agent, Confirmer, Connection};
+
+  fn key(seed: u8) -> PrivateKey {
+    return PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[seed; 32]));
+  }
+
+  fn connect(agent: Agent) -> Client {
+    let (client, server) = UnixStream::pair().unwrap();
+    let agent = Arc::new(Mutex::new(agent));
+
+    thread::spawn(move || { serve_connection(&agent, server) });
+
+    return Client::new(client);
+  }
+
+  #[test]
+  fn serves_clients() {
+    let rsa = PrivateKey::Rsa(rsa::PrivateKey::generate(1024));
+
+    let mut client = connect(Agent::new());
+
+    assert_eq!(client.add(&key(1), "ed25519", vec![]), Ok(()));
+    assert_eq!(client.add(&rsa, "rsa", vec![]), Ok(()));
+
+    let identities = client.identities().unwrap();
+
+    assert_eq!(identities.iter().map(|i| { i.comment.clone() }).collect::<Vec<_>>(), vec!["ed25519", "rsa"]);
+
+    for identity in identities.iter() {
+      for algorithm in identity.signature_algorithms() {
+        let signature = client.sign(identity, algorithm, b"data").unwrap();
+
+        assert!(signature.starts_with(&[0, 0, 0, algorithm.len() as u8]));
+        assert!(identity.verify(b"data", &signature[..]));
+      }
+    }
+
+    assert_eq!(client.remove(&identities[0]), Ok(()));
+    assert_eq!(client.remove(&identities[0]), Err(client::Error::Failure));
+    assert_eq!(client.sign(&identities[0], "ssh-ed25519", b"data"), Err(client::Error::Failure));
+    assert_eq!(client.remove_all(), Ok(()));
+    assert_eq!(client.identities(), Ok(vec![]));
+    assert_eq!(client.extension("unknown@example.com", &[]), Err(client::Error::Failure));
+  }
+
+  #[test]
+  fn locks() {
+    let mut client = connect(Agent::new());
+
+    client.add(&key(1), "", vec![]).unwrap();
+
+    assert_eq!(client.lock("secret"), Ok(()));
+    assert_eq!(client.identities(), Ok(vec![]));
+    assert_eq!(client.sign(&key(1).public_key(), "ssh-ed25519", b"data"), Err(client::Error::Failure));
+    assert_eq!(client.add(&key(2), "", vec![]), Err(client::Error::Failure));
+    assert_eq!(client.unlock("wrong"), Err(client::Error::Failure));
+    assert_eq!(client.unlock("secret"), Ok(()));
+    assert_eq!(client.identities().unwrap().len(), 1);
+  }
+
+  #[test]
+  fn forgets_expired_keys() {
+    let mut agent = Agent::new();
+
+    assert!(agent.add(key(1), "", vec![Constraint::Lifetime(0)]));
+    assert!(agent.add(key(2), "", vec![Constraint::Lifetime(60)]));
+    assert!(!agent.add(key(3), "", vec![Constraint::Extension("unknown@example.com".to_string(), vec![])]));
+
+    match agent.handle(&mut Connection::new(), Message::RequestIdentities) {
+      Message::IdentitiesAnswer(identities) => assert_eq!(identities, vec![(key(2).public_key().blob(), String::new())]),
+      m => panic!("Unexpected message {:?}", m)
+    }
+  }
+
+  struct Answer(bool);
+
+  impl Confirmer for Answer {
+    fn confirm(&mut self, _: &PublicKey) -> bool {
+      return self.0;
+    }
+  }
+
+  #[test]
+  fn asks_for_confirmation() {
+    let request = Message::SignRequest { key_blob: key(1).public_key().blob(), data: vec![1], flags: 0 };
+
+    let mut agent = Agent::new();
+    agent.add(key(1), "", vec![Constraint::Confirm]);
+
+    assert_eq!(agent.handle(&mut Connection::new(), request.clone()), Message::Failure);
+
+    agent.set_confirmer(Box::new(Answer(false)));
+    assert_eq!(agent.handle(&mut Connection::new(), request.clone()), Message::Failure);
+
+    agent.set_confirmer(Box::new(Answer(true)));
+    assert_eq!(agent.handle(&mut Connection::new(), request), Message::SignResponse(key(1).sign("ssh-ed25519", &[1])));
+  }
+
+  /// Signed data of a `publickey` request, or of a
+  /// `publickey-hostbound-v00@openssh.com` request naming `host_key`.
+  fn user_authentication(user_name: &str, session_identifier: &[u8], key: &PublicKey, host_key: Option<&PublicKey>) -> Vec<u8> {
+    let mut writer = Cursor::new(Vec::new());
+
+    writer.write_binary_string(session_identifier);
+    writer.write_all(&[50]).unwrap();
+    writer.write_string(user_name);
+    writer.write_string("ssh-connection");
+    writer.write_string(if host_key.is_some() { "publickey-hostbound-v00@openssh.com" } else { "publickey" });
+    writer.write_bool(true);
+    writer.write_string("ssh-ed25519");
+    writer.write_binary_string(&key.blob()[..]);
+
+    if let Some(host_key) = host_key {
+      writer.write_binary_string(&host_key.blob()[..]);
+    }
+
+    return writer.into_inner();
+  }
+
+  fn hop(user_name: &str, host_name: &str, keys: Vec<PublicKey>) -> Hop {
+    return Hop {
+      user_name: user_name.to_string(),
+      host_name: host_name.to_string(),
+      keys: keys.into_iter().map(|k| { (k, false) }).collect()
+    };
+  }
+
+  #[test]
+  fn restricts_keys_to_destinations() {
+    let (user, jump, server, other) = (key(1), key(2), key(3), key(4));
+    let user_key = user.public_key();
+
+    let destinations = vec![
+      Destination { from: hop("", "", vec![]), to: hop("", "jump", vec![jump.public_key()]) },
+      Destination { from: hop("", "jump", vec![jump.public_key()]), to: hop("alice", "server", vec![server.public_key()]) }
+    ];
+
+    let mut agent = Agent::new();
+    agent.add(user, "", vec![Constraint::Destinations(destinations)]);
+
+    let agent = Arc::new(Mutex::new(agent));
+
+    let connect = |bindings: &[(&PrivateKey, &[u8], bool)]| -> Client {
+      let (client, stream) = UnixStream::pair().unwrap();
+      let agent = agent.clone();
+
+      thread::spawn(move || { serve_connection(&agent, stream) });
+
+      let mut client = Client::new(client);
+
+      for &(host, session_identifier, forwarding) in bindings.iter() {
+        let signature = host.sign("ssh-ed25519", session_identifier);
+
+        assert_eq!(client.bind_session(&host.public_key(), session_identifier, &signature[..], forwarding), Ok(()));
+      }
+
+      return client;
+    };
+
+    let sign = |client: &mut Client, user_name: &str, session_identifier: &[u8], host_key: Option<&PrivateKey>| -> bool {
+      let host_key = host_key.map(|k| { k.public_key() });
+      let data = user_authentication(user_name, session_identifier, &user_key, host_key.as_ref());
+
+      return client.sign(&user_key, "ssh-ed25519", &data[..]).is_ok();
+    };
+
+    assert!(!sign(&mut connect(&[]), "alice", b"jump", None));
+    assert!(sign(&mut connect(&[(&jump, b"jump", false)]), "anyone", b"jump", None));
+    assert!(sign(&mut connect(&[(&jump, b"jump", false)]), "anyone", b"jump", Some(&jump)));
+    assert!(!sign(&mut connect(&[(&jump, b"jump", false)]), "anyone", b"other", None));
+    assert!(!sign(&mut connect(&[(&jump, b"jump", false)]), "anyone", b"jump", Some(&other)));
+    assert!(!sign(&mut connect(&[(&server, b"server", false)]), "alice", b"server", None));
+    assert!(!sign(&mut connect(&[(&other, b"other", false)]), "alice", b"other", None));
+    assert!(!sign(&mut connect(&[(&jump, b"jump", true)]), "alice", b"jump", None));
+
+    let mut forwarded = connect(&[(&jump, b"jump", true), (&server, b"server", false)]);
+
+    assert_eq!(forwarded.identities().unwrap().len(), 1);
+    assert!(!sign(&mut forwarded, "bob", b"server", Some(&server)));
+    assert!(!sign(&mut forwarded, "alice", b"server", None));
+    assert!(sign(&mut forwarded, "alice", b"server", Some(&server)));
+
+    let mut bound_elsewhere = connect(&[(&jump, b"jump", true), (&other, b"other", false)]);
+
+    assert_eq!(bound_elsewhere.identities(), Ok(vec![]));
+  }
+
+  #[test]
+  fn refuses_bad_bindings() {
+    let host = key(2);
+    let mut client = connect(Agent::new());
+
+    assert_eq!(client.bind_session(&host.public_key(), b"session", &key(3).sign("ssh-ed25519", b"session")[..], false), Err(client::Error::Failure));
+    assert_eq!(client.bind_session(&host.public_key(), b"session", &host.sign("ssh-ed25519", b"session")[..], false), Ok(()));
+    assert_eq!(client.bind_session(&host.public_key(), b"session", &host.sign("ssh-ed25519", b"session")[..], false), Ok(()));
+    assert_eq!(client.bind_session(&host.public_key(), b"again", &host.sign("ssh-ed25519", b"again")[..], false), Err(client::Error::Failure));
+  }
+}
@@ -0,0 +1,368 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use keys::PublicKey;
+use keys::certificate::{parse_time, Certificate};
+
+use pattern;
+
+/// The options in front of a key in an `authorized_keys` file, which
+/// restrict what a session authenticated with the key may do.
+///
+/// See the AUTHORIZED_KEYS FILE FORMAT section of OpenSSH's `sshd(8)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+  /// A command to run instead of whatever the client asks for
+  pub command: Option<String>,
+  /// Patterns the client's address or host name has to match
+  pub from: Option<String>,
+  pub no_pty: bool,
+  pub no_port_forwarding: bool,
+  pub no_agent_forwarding: bool,
+  pub no_x11_forwarding: bool,
+  pub no_user_rc: bool,
+  /// Destinations, `host:port`, local port forwarding is limited to
+  pub permit_open: Vec<String>,
+  /// Variables to set in the environment, `NAME=value`
+  pub environment: Vec<(String, String)>,
+  /// With `cert_authority`, the principals a certificate must have one of,
+  /// instead of the user name
+  pub principals: Option<Vec<String>>,
+  /// The key is a CA, trusted to sign user certificates
+  pub cert_authority: bool,
+  /// When the key stops being accepted, in seconds since the epoch
+  pub expiry_time: Option<u64>
+}
+
+/// A key with its options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+  pub options: Options,
+  pub key: PublicKey
+}
+
+impl Entry {
+  /// Parses a line, `[options] type base64 [comment]`.
+  pub fn parse(line: &str) -> Option<Entry> {
+    let line = line.trim();
+
+    if let Ok(key) = PublicKey::parse(line) {
+      return Some(Entry { options: Options::default(), key: key });
+    }
+
+    let (options, rest) = split_options(line);
+
+    let options = match parse_options(options) {
+      Some(options) => options,
+      None => return None
+    };
+
+    return match PublicKey::parse(rest.trim()) {
+      Ok(key) => Some(Entry { options: options, key: key }),
+      Err(_) => None
+    };
+  }
+
+  /// Whether the options allow a client from `address`, and with the host
+  /// name `host_name` if it resolves to one.
+  pub fn allows_from(&self, address: &str, host_name: Option<&str>) -> bool {
+    let patterns = match self.options.from {
+      Some(ref patterns) => patterns,
+      None => return true
+    };
+
+    let by_address = pattern::matches_list(address, patterns);
+    let by_name = host_name.and_then(|h| { pattern::matches_list(&h.to_lowercase(), patterns) });
+
+    return by_address != Some(false) && by_name != Some(false) && (by_address == Some(true) || by_name == Some(true));
+  }
+
+  /// Whether the key has expired at `now`, in seconds since the epoch.
+  pub fn has_expired(&self, now: u64) -> bool {
+    return self.options.expiry_time.map_or(false, |t| { now >= t });
+  }
+}
+
+/// The keys in an `authorized_keys` file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthorizedKeys {
+  pub entries: Vec<Entry>
+}
+
+impl AuthorizedKeys {
+  /// Parses the lines of an `authorized_keys` file, skipping comments and
+  /// lines that can't be parsed.
+  pub fn parse(text: &str) -> AuthorizedKeys {
+    let entries = text.lines().filter(|l| {
+      let l = l.trim();
+
+      !l.is_empty() && !l.starts_with('#')
+    }).filter_map(Entry::parse).collect();
+
+    return AuthorizedKeys { entries: entries };
+  }
+
+  pub fn read_file(path: &Path) -> io::Result<AuthorizedKeys> {
+    let mut text = String::new();
+
+    try!(try!(File::open(path)).read_to_string(&mut text));
+
+    return Ok(AuthorizedKeys::parse(&text));
+  }
+
+  /// Finds the entry for `key`, if it is listed, not a CA, not expired, and
+  /// allowed for a client from `address`.
+  pub fn find(&self, key: &PublicKey, address: &str, host_name: Option<&str>) -> Option<&Entry> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    let blob = key.blob();
+
+    return self.entries.iter().find(|e| {
+      !e.options.cert_authority && e.key.blob() == blob && !e.has_expired(now) && e.allows_from(address, host_name)
+    });
+  }
+
+  /// Finds a `cert-authority` entry for the CA that signed `certificate`,
+  /// if it allows a client from `address` to log in as `user_name`.
+  ///
+  /// The certificate has to list `user_name` as a principal, or one of the
+  /// entry's `principals` if it has any. Whether the certificate itself is
+  /// valid is not checked here.
+  pub fn find_certificate_authority(&self, certificate: &Certificate, user_name: &str, address: &str, host_name: Option<&str>) -> Option<&Entry> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    let blob = certificate.signature_key.blob();
+
+    return self.entries.iter().find(|e| {
+      let principals = match e.options.principals {
+        Some(ref principals) => principals.clone(),
+        None => vec![user_name.to_string()]
+      };
+
+      e.options.cert_authority && e.key.blob() == blob && !e.has_expired(now) && e.allows_from(address, host_name) &&
+        certificate.valid_principals.iter().any(|p| { principals.contains(p) })
+    });
+  }
+}
+
+/// Splits a line into its options and the rest, at the first space outside
+/// quotes.
+fn split_options(line: &str) -> (&str, &str) {
+  let mut quoted = false;
+  let mut escaped = false;
+
+  for (i, c) in line.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      '"' => quoted = !quoted,
+      ' ' | '\t' if !quoted => return (&line[.. i], &line[i ..]),
+      _ => {}
+    }
+  }
+
+  return (line, "");
+}
+
+/// Parses comma separated options, some with quoted values, like
+/// `no-pty,command="uptime"`.
+fn parse_options(text: &str) -> Option<Options> {
+  let mut options = Options::default();
+
+  let mut rest = text;
+
+  while !rest.is_empty() {
+    let name_end = rest.find(|c| { c == '=' || c == ',' }).unwrap_or(rest.len());
+    let name = rest[.. name_end].to_lowercase();
+
+    rest = &rest[name_end ..];
+
+    let value = if rest.starts_with("=\"") {
+      let (value, length) = match unquote(&rest[1 ..]) {
+        Some(v) => v,
+        None => return None
+      };
+
+      rest = &rest[1 + length ..];
+
+      Some(value)
+    } else {
+      None
+    };
+
+    if rest.starts_with(',') {
+      rest = &rest[1 ..];
+    } else if !rest.is_empty() {
+      return None;
+    }
+
+    match (name.as_str(), value) {
+      ("restrict", None) => {
+        options.no_pty = true;
+        options.no_port_forwarding = true;
+        options.no_agent_forwarding = true;
+        options.no_x11_forwarding = true;
+        options.no_user_rc = true;
+      }
+      ("no-pty", None) => options.no_pty = true,
+      ("pty", None) => options.no_pty = false,
+      ("no-port-forwarding", None) => options.no_port_forwarding = true,
+      ("port-forwarding", None) => options.no_port_forwarding = false,
+      ("no-agent-forwarding", None) => options.no_agent_forwarding = true,
+      ("agent-forwarding", None) => options.no_agent_forwarding = false,
+      ("no-x11-forwarding", None) => options.no_x11_forwarding = true,
+      ("x11-forwarding", None) => options.no_x11_forwarding = false,
+      ("no-user-rc", None) => options.no_user_rc = true,
+      ("user-rc", None) => options.no_user_rc = false,
+      ("cert-authority", None) => options.cert_authority = true,
+      ("command", Some(command)) => options.command = Some(command),
+      ("from", Some(from)) => options.from = Some(from),
+      ("permitopen", Some(destination)) => options.permit_open.push(destination),
+      ("environment", Some(variable)) => {
+        let i = match variable.find('=') {
+          Some(i) if i > 0 => i,
+          _ => return None
+        };
+
+        options.environment.push((variable[.. i].to_string(), variable[i + 1 ..].to_string()));
+      }
+      ("principals", Some(principals)) => {
+        options.principals = Some(principals.split(',').map(|p| { p.to_string() }).collect());
+      }
+      ("expiry-time", Some(time)) => options.expiry_time = Some(match parse_time(&time) {
+        Some(time) => time,
+        None => return None
+      }),
+      _ => return None
+    }
+  }
+
+  return Some(options);
+}
+
+/// Reads a quoted string, returning its contents and how many bytes it took
+/// up, quotes included. Only `\"` is an escape, as in OpenSSH.
+fn unquote(text: &str) -> Option<(String, usize)> {
+  let mut value = String::new();
+  let mut chars = text.char_indices().skip(1);
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '"' => return Some((value, i + 1)),
+      '\\' if text[i + 1 ..].starts_with('"') => {
+        chars.next();
+        value.push('"');
+      }
+      c => value.push(c)
+    }
+  }
+
+  return None;
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io;
+  use std::path::Path;
+
+  use keys::{Key, PublicKey};
+  use keys::certificate;
+
+  use super::{AuthorizedKeys, Entry};
+
+  static KEY: &'static str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIC/if5K7Qs4g31VdM9NitvIotVGzDfkbYpEPflZBWP1l user@host";
+
+  #[test]
+  fn parses_plain_keys() {
+    let entry = Entry::parse(KEY).unwrap();
+
+    assert_eq!(entry.key, PublicKey::parse(KEY).unwrap());
+    assert!(!entry.options.no_pty);
+  }
+
+  #[test]
+  fn parses_options() {
+    let line = format!("command=\"echo \\\"hi, there\\\"\",from=\"10.0.0.*,!10.0.0.1\",no-pty,permitopen=\"db:5432\",environment=\"LANG=C\",expiry-time=\"20300101\" {}", KEY);
+
+    let options = Entry::parse(&line).unwrap().options;
+
+    assert_eq!(options.command, Some("echo \"hi, there\"".to_string()));
+    assert_eq!(options.from, Some("10.0.0.*,!10.0.0.1".to_string()));
+    assert!(options.no_pty);
+    assert!(!options.no_port_forwarding);
+    assert_eq!(options.permit_open, vec!["db:5432".to_string()]);
+    assert_eq!(options.environment, vec![("LANG".to_string(), "C".to_string())]);
+    assert_eq!(options.expiry_time, Some(1893456000));
+  }
+
+  #[test]
+  fn restrict_can_be_relaxed() {
+    let options = Entry::parse(&format!("restrict,pty {}", KEY)).unwrap().options;
+
+    assert!(!options.no_pty);
+    assert!(options.no_port_forwarding);
+    assert!(options.no_agent_forwarding);
+  }
+
+  #[test]
+  fn parses_cert_authorities() {
+    let options = Entry::parse(&format!("cert-authority,principals=\"alice,bob\" {}", KEY)).unwrap().options;
+
+    assert!(options.cert_authority);
+    assert_eq!(options.principals, Some(vec!["alice".to_string(), "bob".to_string()]));
+  }
+
+  #[test]
+  fn skips_bad_lines() {
+    let keys = AuthorizedKeys::parse(&format!("# comment\n\nbogus-option {}\nnot a key\n{}\n", KEY, KEY));
+
+    assert_eq!(keys.entries.len(), 1);
+  }
+
+  #[test]
+  fn finds_keys_for_allowed_addresses() {
+    let keys = AuthorizedKeys::parse(&format!("from=\"10.0.0.*,!10.0.0.1\" {}\n", KEY));
+    let key = PublicKey::parse(KEY).unwrap();
+
+    assert!(keys.find(&key, "10.0.0.2", None).is_some());
+    assert!(keys.find(&key, "10.0.0.1", None).is_none());
+    assert!(keys.find(&key, "10.1.0.2", None).is_none());
+  }
+
+  #[test]
+  fn skips_expired_keys() {
+    let keys = AuthorizedKeys::parse(&format!("expiry-time=\"20000101\" {}\n", KEY));
+
+    assert!(keys.find(&PublicKey::parse(KEY).unwrap(), "10.0.0.2", None).is_none());
+  }
+
+  #[test]
+  fn finds_certificate_authorities() {
+    let key = PublicKey::parse(certificate::tests::USER_CERTIFICATE).unwrap();
+
+    let certificate = match key.key {
+      Key::Certificate(c) => c,
+      _ => panic!("Not a certificate")
+    };
+
+    let keys = AuthorizedKeys::parse(&format!("cert-authority {}\n", certificate::tests::CA));
+
+    assert!(keys.find_certificate_authority(&certificate, "alice", "10.0.0.2", None).is_some());
+    assert!(keys.find_certificate_authority(&certificate, "bob", "10.0.0.2", None).is_none());
+    assert!(keys.find(&certificate.signature_key, "10.0.0.2", None).is_none());
+
+    let keys = AuthorizedKeys::parse(&format!("cert-authority,principals=\"admin\" {}\n", certificate::tests::CA));
+
+    assert!(keys.find_certificate_authority(&certificate, "bob", "10.0.0.2", None).is_some());
+  }
+
+  #[test]
+  fn reports_missing_files() {
+    match AuthorizedKeys::read_file(Path::new("/nonexistent/authorized_keys")) {
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+      _ => panic!("Expected a missing file")
+    }
+  }
+}
@@ -0,0 +1,712 @@
+use std::io;
+
+use keys::{PrivateKey, PublicKey};
+use keys::certificate;
+
+use agent;
+
+use packets::SSHPacket;
+use packets::disconnect::Disconnect;
+use packets::service::ServiceRequest;
+use packets::authentication_request::{AuthenticationRequest, AuthenticationMethod};
+use packets::authentication_response::{PasswordChangeRequest, PublicKeyOk};
+use packets::keyboard_interactive::{InfoRequest, InfoResponse, Prompt};
+
+use transport::ssh_transport::PacketStream;
+
+/// The service user authentication runs as.
+pub static SERVICE_NAME: &'static str = "ssh-userauth";
+
+/// A way of proving who we are to the server.
+#[derive(Clone, Debug)]
+pub enum Method {
+  /// The `none` method, which only succeeds for users without credentials,
+  /// but makes the server tell us which methods it accepts
+  None,
+  /// The `password` method
+  Password(String),
+  /// The `publickey` method, signing with a private key
+  PublicKey(PrivateKey),
+  /// The `publickey` method, presenting a certificate for the private key
+  Certificate(PrivateKey, PublicKey),
+  /// The `publickey` method, with a key or certificate held by the client's
+  /// agent
+  Agent(PublicKey),
+  /// The `keyboard-interactive` method, answering the server's prompts with
+  /// the client's `Prompter`
+  KeyboardInteractive
+}
+
+impl Method {
+  pub fn name(&self) -> &'static str {
+    return match self {
+      &Method::None => "none",
+      &Method::Password(_) => "password",
+      &Method::PublicKey(_) | &Method::Certificate(..) | &Method::Agent(_) => "publickey",
+      &Method::KeyboardInteractive => "keyboard-interactive"
+    };
+  }
+}
+
+/// Asks the user the questions a server has for them, usually by showing
+/// them on a terminal.
+pub trait Prompter {
+  /// Answers `prompts`, one response for each. The `name` and `instruction`
+  /// should be shown to the user, even if there are no prompts.
+  fn respond(&mut self, name: &str, instruction: &str, prompts: &[Prompt]) -> Vec<String>;
+
+  /// Asks for a new password, because the server says the old one has
+  /// expired. Returning `None` gives up on the `password` method.
+  fn change_password(&mut self, prompt: &str, language: &str) -> Option<String> {
+    let _ = (prompt, language);
+
+    return None;
+  }
+}
+
+/// Why authentication failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+  /// The server disconnected
+  Disconnected(Disconnect),
+  /// None of our methods were accepted, the server would still accept these
+  Denied(Vec<String>)
+}
+
+/// How a method ended.
+enum Outcome {
+  Success,
+  Failure(Vec<String>, bool),
+  /// We gave up before the server decided
+  Abandoned
+}
+
+/// A message answering a request.
+enum Response {
+  Outcome(Outcome),
+  /// A message from 60 to 79, which the method has to make sense of
+  MethodSpecific(u8, Vec<u8>)
+}
+
+impl Response {
+  /// The outcome, for methods without method specific messages.
+  fn outcome(self) -> Outcome {
+    return match self {
+      Response::Outcome(outcome) => outcome,
+      Response::MethodSpecific(t, _) => panic!("Unexpected message {} during authentication", t)
+    };
+  }
+}
+
+/// Authenticates as a user, trying the configured methods in order.
+///
+/// See [RFC 4252](https://tools.ietf.org/html/rfc4252).
+pub struct Client<'a> {
+  user_name: String,
+  service_name: String,
+  methods: Vec<Method>,
+  banners: Vec<String>,
+  /// Methods that succeeded, but where the server required more
+  partial_successes: Vec<String>,
+  prompter: Option<Box<Prompter + 'a>>,
+  agent: Option<agent::client::Client>
+}
+
+impl<'a> Client<'a> {
+  /// Authenticates `user_name` for the `ssh-connection` service.
+  pub fn new(user_name: &str) -> Client<'a> {
+    return Client {
+      user_name: user_name.to_string(),
+      service_name: "ssh-connection".to_string(),
+      methods: Vec::new(),
+      banners: Vec::new(),
+      partial_successes: Vec::new(),
+      prompter: None,
+      agent: None
+    };
+  }
+
+  /// Sets what answers the questions of `keyboard-interactive`.
+  pub fn set_prompter(&mut self, prompter: Box<Prompter + 'a>) {
+    self.prompter = Some(prompter);
+  }
+
+  /// Sets the agent that signs for the `Agent` method.
+  pub fn set_agent(&mut self, agent: agent::client::Client) {
+    self.agent = Some(agent);
+  }
+
+  /// Adds a method to try after the ones already added.
+  pub fn add_method(&mut self, method: Method) {
+    self.methods.push(method);
+  }
+
+  /// Banners the server sent during authentication, to show the user.
+  pub fn banners(&self) -> &Vec<String> {
+    return &self.banners;
+  }
+
+  /// Methods that succeeded, but after which the server required more.
+  pub fn partial_successes(&self) -> &Vec<String> {
+    return &self.partial_successes;
+  }
+
+  /// Requests the `ssh-userauth` service, then tries each method the server
+  /// still accepts, until one succeeds.
+  ///
+  /// Methods the server did not list in its last failure are skipped, except
+  /// `none`, as are `keyboard-interactive` without a `Prompter` and `Agent`
+  /// without an agent. A `Prompter` is also asked for a new password if the
+  /// server says ours has expired.
+  pub fn authenticate(&mut self, stream: &mut PacketStream) -> Result<(), Error> {
+    stream.write(&SSHPacket::ServiceRequest(ServiceRequest { service_name: SERVICE_NAME.to_string() }));
+
+    match stream.read() {
+      SSHPacket::ServiceAccept(ref a) if a.service_name == SERVICE_NAME => {},
+      SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+      p => panic!("Unexpected packet during service request: {:?}", p)
+    }
+
+    let mut allowed: Option<Vec<String>> = None;
+
+    for method in self.methods.clone() {
+      let name = method.name();
+
+      if name != "none" && allowed.as_ref().map_or(false, |a| { !a.iter().any(|m| { m == name }) }) {
+        continue;
+      }
+
+      match method {
+        Method::KeyboardInteractive if self.prompter.is_none() => continue,
+        Method::Agent(_) if self.agent.is_none() => continue,
+        _ => {}
+      }
+
+      match try!(self.attempt(stream, &method)) {
+        Outcome::Success => return Ok(()),
+        Outcome::Failure(methods, partial_success) => {
+          if partial_success {
+            self.partial_successes.push(name.to_string());
+          }
+
+          allowed = Some(methods);
+        }
+        Outcome::Abandoned => {}
+      }
+    }
+
+    return Err(Error::Denied(allowed.unwrap_or(Vec::new())));
+  }
+
+  fn attempt(&mut self, stream: &mut PacketStream, method: &Method) -> Result<Outcome, Error> {
+    let method = match method {
+      &Method::None => AuthenticationMethod::NoneMethod,
+      &Method::Password(ref password) => return self.attempt_password(stream, password),
+      &Method::PublicKey(ref key) => return self.attempt_public_key(stream, Some(key), &key.public_key()),
+      &Method::Certificate(ref key, ref certificate) => return self.attempt_public_key(stream, Some(key), certificate),
+      &Method::Agent(ref public_key) => return self.attempt_public_key(stream, None, public_key),
+      &Method::KeyboardInteractive => return self.attempt_keyboard_interactive(stream)
+    };
+
+    self.request(stream, method);
+
+    return Ok(try!(self.read_response(stream)).outcome());
+  }
+
+  /// Sends `password`, and a new one whenever the server asks for a change.
+  ///
+  /// See [RFC 4252, section 8](https://tools.ietf.org/html/rfc4252#section-8).
+  fn attempt_password(&mut self, stream: &mut PacketStream, password: &str) -> Result<Outcome, Error> {
+    self.request(stream, AuthenticationMethod::Password { password: password.to_string(), new_password: None });
+
+    loop {
+      match try!(self.read_response(stream)) {
+        Response::MethodSpecific(60, payload) => {
+          let request = PasswordChangeRequest::read(&mut io::Cursor::new(payload));
+
+          let new_password = match self.prompter {
+            Some(ref mut prompter) => prompter.change_password(&request.prompt, &request.language),
+            None => None
+          };
+
+          match new_password {
+            Some(new_password) => self.request(stream, AuthenticationMethod::Password {
+              password: password.to_string(),
+              new_password: Some(new_password)
+            }),
+            None => return Ok(Outcome::Abandoned)
+          }
+        }
+        response => return Ok(response.outcome())
+      }
+    }
+  }
+
+  /// Asks whether the server would accept `public_key`, either the public
+  /// half of `key` or a certificate for it, with each of its signature
+  /// algorithms in turn, and signs a request with `key` once it would, or
+  /// has the agent sign it without one.
+  fn attempt_public_key(&mut self, stream: &mut PacketStream, key: Option<&PrivateKey>, public_key: &PublicKey) -> Result<Outcome, Error> {
+    let algorithms = public_key.signature_algorithms();
+    let blob = public_key.blob();
+
+    let mut outcome = Outcome::Failure(Vec::new(), false);
+
+    for algorithm in algorithms {
+      self.request(stream, AuthenticationMethod::PublicKey {
+        algorithm: algorithm.to_string(),
+        public_key: blob.clone(),
+        signature: None
+      });
+
+      match try!(self.read_response(stream)) {
+        Response::MethodSpecific(60, payload) => {
+          let ok = PublicKeyOk::read(&mut io::Cursor::new(payload));
+
+          if ok.algorithm != algorithm || ok.public_key != blob {
+            panic!("Server accepted a key we did not offer");
+          }
+
+          let mut request = self.request_for(AuthenticationMethod::PublicKey {
+            algorithm: algorithm.to_string(),
+            public_key: blob.clone(),
+            signature: None
+          });
+
+          let session_identifier = match stream.session_identifier() {
+            Some(session_identifier) => session_identifier.to_vec(),
+            None => panic!("Can't sign for authentication before key exchange")
+          };
+
+          let data = request.signed_data(&session_identifier[..]).unwrap();
+
+          let signature = match (key, self.agent.as_mut()) {
+            (Some(key), _) => key.sign(certificate::base_algorithm(algorithm), &data[..]),
+            (None, Some(agent)) => match agent.sign(public_key, certificate::base_algorithm(algorithm), &data[..]) {
+              Ok(signature) => signature,
+              Err(_) => return Ok(Outcome::Abandoned)
+            },
+            (None, None) => panic!("No agent to sign with")
+          };
+
+          if let AuthenticationMethod::PublicKey { signature: ref mut s, .. } = request.method {
+            *s = Some(signature);
+          }
+
+          stream.write(&SSHPacket::AuthenticationRequest(request));
+
+          return Ok(try!(self.read_response(stream)).outcome());
+        }
+        Response::Outcome(Outcome::Failure(methods, partial_success)) => {
+          let again = methods.iter().any(|m| { m == "publickey" });
+
+          outcome = Outcome::Failure(methods, partial_success);
+
+          if !again {
+            break;
+          }
+        }
+        response => return Ok(response.outcome())
+      }
+    }
+
+    return Ok(outcome);
+  }
+
+  /// Answers the server's questions until it decides.
+  ///
+  /// See [RFC 4256, section 3](https://tools.ietf.org/html/rfc4256#section-3).
+  fn attempt_keyboard_interactive(&mut self, stream: &mut PacketStream) -> Result<Outcome, Error> {
+    self.request(stream, AuthenticationMethod::KeyboardInteractive { language: String::new(), submethods: String::new() });
+
+    loop {
+      match try!(self.read_response(stream)) {
+        Response::MethodSpecific(60, payload) => {
+          let request = InfoRequest::read(&mut io::Cursor::new(payload));
+
+          let mut responses = match self.prompter {
+            Some(ref mut prompter) => prompter.respond(&request.name, &request.instruction, &request.prompts[..]),
+            None => Vec::new()
+          };
+
+          responses.truncate(request.prompts.len());
+
+          while responses.len() < request.prompts.len() {
+            responses.push(String::new());
+          }
+
+          let mut writer = io::Cursor::new(Vec::new());
+          InfoResponse { responses: responses }.write(&mut writer);
+
+          stream.write(&SSHPacket::AuthenticationMethodSpecific(61, writer.into_inner()));
+        }
+        response => return Ok(response.outcome())
+      }
+    }
+  }
+
+  fn request_for(&self, method: AuthenticationMethod) -> AuthenticationRequest {
+    return AuthenticationRequest {
+      user_name: self.user_name.clone(),
+      service_name: self.service_name.clone(),
+      method: method
+    };
+  }
+
+  fn request(&self, stream: &mut PacketStream, method: AuthenticationMethod) {
+    stream.write(&SSHPacket::AuthenticationRequest(self.request_for(method)));
+  }
+
+  /// Reads the answer to a request, collecting banners on the way.
+  fn read_response(&mut self, stream: &mut PacketStream) -> Result<Response, Error> {
+    loop {
+      match stream.read() {
+        SSHPacket::AuthenticationBanner(b) => self.banners.push(b.message),
+        SSHPacket::AuthenticationSuccess(_) => return Ok(Response::Outcome(Outcome::Success)),
+        SSHPacket::AuthenticationFailure(f) => return Ok(Response::Outcome(Outcome::Failure(f.methods, f.partial_success))),
+        SSHPacket::AuthenticationMethodSpecific(t, payload) => return Ok(Response::MethodSpecific(t, payload)),
+        SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+        p => panic!("Unexpected packet during authentication: {:?}", p)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use std::collections::VecDeque;
+  use std::io;
+  use std::os::unix::net::UnixStream;
+  use std::thread;
+
+  use agent;
+  use agent::messages::Message;
+
+  use keys;
+  use keys::{certificate, ed25519, PrivateKey, PublicKey};
+
+  use packets::SSHPacket;
+  use packets::service::ServiceAccept;
+  use packets::authentication_request::AuthenticationMethod;
+  use packets::authentication_response::{AuthenticationBanner, AuthenticationFailure, AuthenticationSuccess, PasswordChangeRequest, PublicKeyOk};
+  use packets::keyboard_interactive::{InfoRequest, InfoResponse, Prompt};
+
+  use transport::ssh_transport::PacketStream;
+
+  use super::{Client, Error, Method, Prompter};
+
+  /// Plays back packets from a server, and records what we send.
+  pub struct Script {
+    pub incoming: VecDeque<SSHPacket>,
+    pub outgoing: Vec<SSHPacket>,
+    pub session_identifier: Vec<u8>
+  }
+
+  impl Script {
+    pub fn new(incoming: Vec<SSHPacket>) -> Script {
+      return Script {
+        incoming: incoming.into_iter().collect(),
+        outgoing: Vec::new(),
+        session_identifier: vec![1, 2, 3, 4]
+      };
+    }
+  }
+
+  impl PacketStream for Script {
+    fn read(&mut self) -> SSHPacket {
+      return self.incoming.pop_front().expect("Script ran out of packets");
+    }
+
+    fn write(&mut self, packet: &SSHPacket) {
+      let mut writer = ::std::io::Cursor::new(Vec::new());
+      packet.write(&mut writer);
+
+      let mut reader = ::std::io::Cursor::new(writer.into_inner());
+      self.outgoing.push(SSHPacket::read(&mut reader));
+    }
+
+    fn session_identifier(&self) -> Option<&[u8]> {
+      return Some(&self.session_identifier[..]);
+    }
+  }
+
+  fn accept() -> SSHPacket {
+    return SSHPacket::ServiceAccept(ServiceAccept { service_name: "ssh-userauth".to_string() });
+  }
+
+  fn failure(methods: &[&str], partial_success: bool) -> SSHPacket {
+    return SSHPacket::AuthenticationFailure(AuthenticationFailure {
+      methods: methods.iter().map(|m| { m.to_string() }).collect(),
+      partial_success: partial_success
+    });
+  }
+
+  fn method_names(script: &Script) -> Vec<String> {
+    return script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::AuthenticationRequest(ref r) => Some(r.method.name().to_string()),
+        _ => None
+      }
+    }).collect();
+  }
+
+  #[test]
+  fn tries_methods_in_order() {
+    let mut script = Script::new(vec![
+      accept(),
+      failure(&["publickey", "password"], false),
+      SSHPacket::AuthenticationBanner(AuthenticationBanner { message: "Hello".to_string(), language: "".to_string() }),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::None);
+    client.add_method(Method::Password("hunter2".to_string()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+    assert_eq!(client.banners(), &vec!["Hello".to_string()]);
+    assert_eq!(method_names(&script), vec!["none", "password"]);
+  }
+
+  #[test]
+  fn skips_methods_the_server_does_not_accept() {
+    let mut script = Script::new(vec![accept(), failure(&["publickey"], false)]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::None);
+    client.add_method(Method::Password("hunter2".to_string()));
+
+    assert_eq!(client.authenticate(&mut script), Err(Error::Denied(vec!["publickey".to_string()])));
+    assert_eq!(method_names(&script), vec!["none"]);
+  }
+
+  #[test]
+  fn continues_after_partial_success() {
+    let mut script = Script::new(vec![
+      accept(),
+      failure(&["password"], true),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::Password("first".to_string()));
+    client.add_method(Method::Password("second".to_string()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+    assert_eq!(client.partial_successes(), &vec!["password".to_string()]);
+  }
+
+  #[test]
+  fn signs_public_key_requests_after_pk_ok() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[5; 32]));
+
+    let mut ok = io::Cursor::new(Vec::new());
+    PublicKeyOk { algorithm: "ssh-ed25519".to_string(), public_key: key.public_key_blob() }.write(&mut ok);
+
+    let mut script = Script::new(vec![
+      accept(),
+      SSHPacket::AuthenticationMethodSpecific(60, ok.into_inner()),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::PublicKey(key.clone()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+
+    let requests: Vec<_> = script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::AuthenticationRequest(ref r) => Some(r.clone()),
+        _ => None
+      }
+    }).collect();
+
+    assert_eq!(requests.len(), 2);
+
+    match (&requests[0].method, &requests[1].method) {
+      (&AuthenticationMethod::PublicKey { signature: None, .. }, &AuthenticationMethod::PublicKey { signature: Some(ref signature), .. }) => {
+        let data = requests[1].signed_data(&[1, 2, 3, 4]).unwrap();
+
+        assert!(key.public_key().verify(&data[..], &signature[..]));
+      }
+      methods => panic!("Unexpected methods {:?}", methods)
+    }
+  }
+
+  #[test]
+  fn presents_certificates() {
+    let certificate = PublicKey::parse(certificate::tests::USER_CERTIFICATE).unwrap();
+    let (key, _) = keys::decode_private_key(certificate::tests::USER_KEY, None).unwrap().remove(0);
+
+    let mut ok = io::Cursor::new(Vec::new());
+    PublicKeyOk { algorithm: certificate.algorithm().to_string(), public_key: certificate.blob() }.write(&mut ok);
+
+    let mut script = Script::new(vec![
+      accept(),
+      SSHPacket::AuthenticationMethodSpecific(60, ok.into_inner()),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("alice");
+    client.add_method(Method::Certificate(key, certificate.clone()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::AuthenticationRequest(ref request)) => match request.method {
+        AuthenticationMethod::PublicKey { ref algorithm, ref public_key, signature: Some(ref signature) } => {
+          assert_eq!(algorithm, "ssh-ed25519-cert-v01@openssh.com");
+          assert_eq!(public_key, &certificate.blob());
+          assert!(certificate.verify(&request.signed_data(&[1, 2, 3, 4]).unwrap()[..], &signature[..]));
+        }
+        ref method => panic!("Unexpected method {:?}", method)
+      },
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn signs_with_an_agent() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[6; 32]));
+    let public_key = key.public_key();
+
+    let (stream, mut agent_stream) = UnixStream::pair().unwrap();
+
+    let agent = thread::spawn(move || {
+      match Message::read(&mut agent_stream) {
+        Message::SignRequest { data, .. } => Message::SignResponse(key.sign("ssh-ed25519", &data[..])).write(&mut agent_stream),
+        m => panic!("Unexpected agent message {:?}", m)
+      }
+    });
+
+    let mut ok = io::Cursor::new(Vec::new());
+    PublicKeyOk { algorithm: "ssh-ed25519".to_string(), public_key: public_key.blob() }.write(&mut ok);
+
+    let mut script = Script::new(vec![
+      accept(),
+      SSHPacket::AuthenticationMethodSpecific(60, ok.into_inner()),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.set_agent(agent::client::Client::new(stream));
+    client.add_method(Method::Agent(public_key.clone()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+
+    agent.join().unwrap();
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::AuthenticationRequest(ref request)) => match request.method {
+        AuthenticationMethod::PublicKey { signature: Some(ref signature), .. } => {
+          assert!(public_key.verify(&request.signed_data(&[1, 2, 3, 4]).unwrap()[..], &signature[..]));
+        }
+        ref method => panic!("Unexpected method {:?}", method)
+      },
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn skips_agent_keys_without_an_agent() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[6; 32]));
+
+    let mut script = Script::new(vec![accept()]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::Agent(key.public_key()));
+
+    assert_eq!(client.authenticate(&mut script), Err(Error::Denied(Vec::new())));
+  }
+
+  #[test]
+  fn gives_up_on_keys_the_server_does_not_want() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[5; 32]));
+
+    let mut script = Script::new(vec![accept(), failure(&["publickey", "password"], false)]);
+
+    let mut client = Client::new("user");
+    client.add_method(Method::PublicKey(key));
+
+    assert_eq!(client.authenticate(&mut script), Err(Error::Denied(vec!["publickey".to_string(), "password".to_string()])));
+  }
+
+  struct Answers(Vec<String>);
+
+  impl Prompter for Answers {
+    fn respond(&mut self, _: &str, _: &str, prompts: &[Prompt]) -> Vec<String> {
+      assert_eq!(prompts.len(), self.0.len());
+
+      return self.0.clone();
+    }
+
+    fn change_password(&mut self, prompt: &str, _: &str) -> Option<String> {
+      assert_eq!(prompt, "Password expired");
+
+      return self.0.pop();
+    }
+  }
+
+  #[test]
+  fn answers_keyboard_interactive_prompts() {
+    let mut info = io::Cursor::new(Vec::new());
+
+    InfoRequest {
+      name: "".to_string(),
+      instruction: "Two factors".to_string(),
+      language: "".to_string(),
+      prompts: vec![
+        Prompt { prompt: "Password: ".to_string(), echo: false },
+        Prompt { prompt: "OTP: ".to_string(), echo: true }
+      ]
+    }.write(&mut info);
+
+    let mut script = Script::new(vec![
+      accept(),
+      failure(&["keyboard-interactive"], false),
+      SSHPacket::AuthenticationMethodSpecific(60, info.into_inner()),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.set_prompter(Box::new(Answers(vec!["hunter2".to_string(), "123456".to_string()])));
+    client.add_method(Method::None);
+    client.add_method(Method::KeyboardInteractive);
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::AuthenticationMethodSpecific(61, ref payload)) => {
+        let response = InfoResponse::read(&mut io::Cursor::new(payload.clone()));
+
+        assert_eq!(response.responses, vec!["hunter2".to_string(), "123456".to_string()]);
+      }
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn changes_expired_passwords() {
+    let mut change = io::Cursor::new(Vec::new());
+    PasswordChangeRequest { prompt: "Password expired".to_string(), language: "".to_string() }.write(&mut change);
+
+    let mut script = Script::new(vec![
+      accept(),
+      SSHPacket::AuthenticationMethodSpecific(60, change.into_inner()),
+      SSHPacket::AuthenticationSuccess(AuthenticationSuccess)
+    ]);
+
+    let mut client = Client::new("user");
+    client.set_prompter(Box::new(Answers(vec!["new".to_string()])));
+    client.add_method(Method::Password("old".to_string()));
+
+    assert_eq!(client.authenticate(&mut script), Ok(()));
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::AuthenticationRequest(ref r)) => {
+        assert_eq!(r.method, AuthenticationMethod::Password { password: "old".to_string(), new_password: Some("new".to_string()) });
+      }
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+}
@@ -0,0 +1,809 @@
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use keys::{Key, PublicKey};
+use keys::certificate;
+use keys::certificate::{Certificate, CertificateType};
+use keys::krl::RevocationList;
+
+use packets::SSHPacket;
+use packets::disconnect;
+use packets::disconnect::Disconnect;
+use packets::service::ServiceAccept;
+use packets::authentication_request::{AuthenticationRequest, AuthenticationMethod};
+use packets::authentication_response::{AuthenticationBanner, AuthenticationFailure, AuthenticationSuccess, PublicKeyOk};
+use packets::keyboard_interactive::{InfoRequest, InfoResponse};
+
+use sshio::SSHRead;
+
+use transport::ssh_transport::PacketStream;
+
+use authentication::client::SERVICE_NAME;
+use authentication::authorized_keys::Options;
+
+/// How a `keyboard-interactive` challenge continues after the user answers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Challenge {
+  Accept,
+  Reject,
+  /// Ask the user more questions
+  More(InfoRequest)
+}
+
+/// Decides who may log in, implemented by the application.
+///
+/// Every method rejects by default. The library checks the signatures of
+/// public key requests, so `check_public_key` only decides whether the user
+/// may log in with the key, and how the session is restricted.
+///
+/// Certificates reach `check_public_key` only if they are valid and not
+/// signed by one of the server's trusted user CAs, so the application can
+/// look for their CA in an `authorized_keys` file. Certificates with critical
+/// options, like `force-command` or `source-address`, are always rejected
+/// since nothing enforces them.
+pub trait Authenticator {
+  /// Whether `user_name` may log in without credentials.
+  fn check_none(&mut self, user_name: &str) -> bool {
+    let _ = user_name;
+
+    return false;
+  }
+
+  fn check_password(&mut self, user_name: &str, password: &str) -> bool {
+    let _ = (user_name, password);
+
+    return false;
+  }
+
+  /// Whether `user_name` may log in with `key` from `address`, returning
+  /// the options of its `authorized_keys` entry if so, which the session
+  /// is restricted by.
+  fn check_public_key(&mut self, user_name: &str, key: &PublicKey, address: &str) -> Option<Options> {
+    let _ = (user_name, key, address);
+
+    return None;
+  }
+
+  /// Starts a `keyboard-interactive` challenge for `user_name`, or `None` if
+  /// there is none for them.
+  fn start_challenge(&mut self, user_name: &str, submethods: &str) -> Option<InfoRequest> {
+    let _ = (user_name, submethods);
+
+    return None;
+  }
+
+  /// Checks the answers to the questions of the challenge.
+  fn check_responses(&mut self, user_name: &str, responses: &[String]) -> Challenge {
+    let _ = (user_name, responses);
+
+    return Challenge::Reject;
+  }
+}
+
+/// Why a client was not authenticated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+  /// The client disconnected
+  Disconnected(Disconnect),
+  /// The client asked for a service other than `ssh-userauth`
+  ServiceNotAvailable(String),
+  /// The client failed more than `max_tries` times
+  TooManyTries,
+  /// The client did not authenticate within the login grace time
+  Timeout
+}
+
+/// Authenticates clients, asking an `Authenticator` about their credentials.
+///
+/// See [RFC 4252](https://tools.ietf.org/html/rfc4252).
+pub struct Server<'a> {
+  authenticator: Box<Authenticator + 'a>,
+  service_name: String,
+  /// Lists of methods, one of which the client has to complete in order
+  method_lists: Vec<Vec<String>>,
+  max_tries: u32,
+  grace_time: Duration,
+  banner: Option<String>,
+  /// CAs whose user certificates are accepted for their principals
+  trusted_user_cas: Vec<PublicKey>,
+  revocation_list: Option<RevocationList>,
+  /// The client's address, for `from=` options
+  address: String,
+  /// The options of the key the user logged in with
+  options: Options
+}
+
+impl<'a> Server<'a> {
+  /// Authenticates clients for the `ssh-connection` service, with any one
+  /// of `publickey`, `password` or `keyboard-interactive`, allowing 6 tries
+  /// within 2 minutes.
+  pub fn new(authenticator: Box<Authenticator + 'a>) -> Server<'a> {
+    return Server {
+      authenticator: authenticator,
+      service_name: "ssh-connection".to_string(),
+      method_lists: vec![
+        vec!["publickey".to_string()],
+        vec!["password".to_string()],
+        vec!["keyboard-interactive".to_string()]
+      ],
+      max_tries: 6,
+      grace_time: Duration::from_secs(120),
+      banner: None,
+      trusted_user_cas: Vec::new(),
+      revocation_list: None,
+      address: String::new(),
+      options: Options::default()
+    };
+  }
+
+  /// Sets the methods clients have to complete, like OpenSSH's
+  /// `AuthenticationMethods`: space separated lists of comma separated
+  /// methods, like `publickey,password publickey,keyboard-interactive`.
+  pub fn set_authentication_methods(&mut self, methods: &str) {
+    self.method_lists = methods.split_whitespace().map(|list| {
+      list.split(',').map(|m| { m.to_string() }).collect()
+    }).collect();
+  }
+
+  /// Sets how many failed requests a client gets before it is disconnected.
+  pub fn set_max_tries(&mut self, max_tries: u32) {
+    self.max_tries = max_tries;
+  }
+
+  /// Sets how long a client gets to authenticate.
+  ///
+  /// This is checked whenever a packet arrives, so the application should
+  /// also set a read timeout on its socket.
+  pub fn set_grace_time(&mut self, grace_time: Duration) {
+    self.grace_time = grace_time;
+  }
+
+  /// Sets a banner to show clients before they authenticate.
+  pub fn set_banner(&mut self, banner: &str) {
+    self.banner = Some(banner.to_string());
+  }
+
+  /// Trusts `key` to sign user certificates, like OpenSSH's
+  /// `TrustedUserCAKeys`: a valid certificate it signed lets the user log in
+  /// as any of the certificate's principals.
+  pub fn add_trusted_user_ca(&mut self, key: PublicKey) {
+    self.trusted_user_cas.push(key);
+  }
+
+  /// Rejects the keys and certificates in `revocation_list`, like OpenSSH's
+  /// `RevokedKeys`.
+  pub fn set_revocation_list(&mut self, revocation_list: RevocationList) {
+    self.revocation_list = Some(revocation_list);
+  }
+
+  /// Accepts the `ssh-userauth` service, then answers requests from the
+  /// client at `address` until it has completed one of the lists of
+  /// methods, returning the user name it authenticated as.
+  pub fn authenticate(&mut self, stream: &mut PacketStream, address: &str) -> Result<String, Error> {
+    let start = Instant::now();
+
+    self.address = address.to_string();
+    self.options = Options::default();
+
+    match stream.read() {
+      SSHPacket::ServiceRequest(ref r) if r.service_name == SERVICE_NAME => {},
+      SSHPacket::ServiceRequest(r) => {
+        disconnect(stream, disconnect::SERVICE_NOT_AVAILABLE, "Service not available");
+        return Err(Error::ServiceNotAvailable(r.service_name));
+      }
+      SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+      p => panic!("Unexpected packet during service request: {:?}", p)
+    }
+
+    stream.write(&SSHPacket::ServiceAccept(ServiceAccept { service_name: SERVICE_NAME.to_string() }));
+
+    let mut banner = self.banner.clone();
+
+    let mut user_name = String::new();
+    let mut completed: Vec<String> = Vec::new();
+    let mut failures = 0;
+
+    loop {
+      let request = match stream.read() {
+        SSHPacket::AuthenticationRequest(r) => r,
+        SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+        p => panic!("Unexpected packet during authentication: {:?}", p)
+      };
+
+      if start.elapsed() > self.grace_time {
+        disconnect(stream, disconnect::NO_MORE_AUTH_METHODS_AVAILABLE, "Login grace time exceeded");
+        return Err(Error::Timeout);
+      }
+
+      if let Some(banner) = banner.take() {
+        stream.write(&SSHPacket::AuthenticationBanner(AuthenticationBanner { message: banner, language: String::new() }));
+      }
+
+      if request.service_name != self.service_name {
+        disconnect(stream, disconnect::SERVICE_NOT_AVAILABLE, "Service not available");
+        return Err(Error::ServiceNotAvailable(request.service_name));
+      }
+
+      // Methods completed for one user don't count for another.
+      if request.user_name != user_name {
+        user_name = request.user_name.clone();
+        completed.clear();
+        self.options = Options::default();
+      }
+
+      let method = request.method.name().to_string();
+      let allowed = self.next_methods(&completed);
+
+      let accepted = if method == "none" {
+        self.authenticator.check_none(&user_name)
+      } else if allowed.contains(&method) {
+        match try!(self.check(stream, &request)) {
+          Some(accepted) => accepted,
+          // Only a query, which does not count as a try
+          None => continue
+        }
+      } else {
+        false
+      };
+
+      if accepted {
+        completed.push(method.clone());
+
+        if method == "none" || self.method_lists.iter().any(|list| { *list == completed }) {
+          stream.write(&SSHPacket::AuthenticationSuccess(AuthenticationSuccess));
+          return Ok(user_name);
+        }
+      } else if method != "none" {
+        failures += 1;
+
+        if failures >= self.max_tries {
+          disconnect(stream, disconnect::NO_MORE_AUTH_METHODS_AVAILABLE, "Too many authentication failures");
+          return Err(Error::TooManyTries);
+        }
+      }
+
+      stream.write(&SSHPacket::AuthenticationFailure(AuthenticationFailure {
+        methods: self.next_methods(&completed),
+        partial_success: accepted
+      }));
+    }
+  }
+
+  /// The options of the key the user authenticated with, the defaults if
+  /// none was used. The application enforces them with `session::restrict`
+  /// and `session::allows_open`.
+  pub fn options(&self) -> &Options {
+    return &self.options;
+  }
+
+  /// The methods that can continue one of the lists, after `completed`.
+  fn next_methods(&self, completed: &Vec<String>) -> Vec<String> {
+    let mut methods: Vec<String> = Vec::new();
+
+    for list in self.method_lists.iter() {
+      if list.len() > completed.len() && list[.. completed.len()] == completed[..] {
+        let method = &list[completed.len()];
+
+        if !methods.contains(method) {
+          methods.push(method.clone());
+        }
+      }
+    }
+
+    return methods;
+  }
+
+  /// Checks a request, returning `None` if it was only a query that has
+  /// been answered.
+  fn check(&mut self, stream: &mut PacketStream, request: &AuthenticationRequest) -> Result<Option<bool>, Error> {
+    let user_name = &request.user_name;
+
+    return match request.method {
+      AuthenticationMethod::Password { ref password, new_password: None } => {
+        Ok(Some(self.authenticator.check_password(user_name, password)))
+      }
+      AuthenticationMethod::PublicKey { ref algorithm, ref public_key, ref signature } => {
+        let key = match PublicKey::from_blob(&public_key[..]) {
+          Ok(key) => key,
+          Err(_) => return Ok(Some(false))
+        };
+
+        if !key.signature_algorithms().contains(&algorithm.as_str()) {
+          return Ok(Some(false));
+        }
+
+        if self.revocation_list.as_ref().map_or(false, |l| { l.is_revoked(&key) }) {
+          return Ok(Some(false));
+        }
+
+        let options = match key.key {
+          Key::Certificate(ref c) => self.check_certificate(user_name, c, &key),
+          _ => self.authenticator.check_public_key(user_name, &key, &self.address)
+        };
+
+        let options = match options {
+          Some(options) => options,
+          None => return Ok(Some(false))
+        };
+
+        match signature {
+          &None => {
+            let mut writer = io::Cursor::new(Vec::new());
+            PublicKeyOk { algorithm: algorithm.clone(), public_key: public_key.clone() }.write(&mut writer);
+
+            stream.write(&SSHPacket::AuthenticationMethodSpecific(60, writer.into_inner()));
+
+            Ok(None)
+          }
+          &Some(ref signature) => {
+            let session_identifier = match stream.session_identifier() {
+              Some(session_identifier) => session_identifier.to_vec(),
+              None => panic!("Can't verify signatures before key exchange")
+            };
+
+            let data = request.signed_data(&session_identifier[..]).unwrap();
+
+            let expected = certificate::base_algorithm(algorithm).to_string();
+
+            if signature_algorithm(signature) != Some(expected) || !key.verify(&data[..], &signature[..]) {
+              return Ok(Some(false));
+            }
+
+            self.options = options;
+
+            Ok(Some(true))
+          }
+        }
+      }
+      AuthenticationMethod::KeyboardInteractive { ref submethods, .. } => {
+        let mut info = match self.authenticator.start_challenge(user_name, submethods) {
+          Some(info) => info,
+          None => return Ok(Some(false))
+        };
+
+        loop {
+          let mut writer = io::Cursor::new(Vec::new());
+          info.write(&mut writer);
+
+          stream.write(&SSHPacket::AuthenticationMethodSpecific(60, writer.into_inner()));
+
+          let response = match stream.read() {
+            SSHPacket::AuthenticationMethodSpecific(61, payload) => InfoResponse::read(&mut io::Cursor::new(payload)),
+            SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+            p => panic!("Unexpected packet during keyboard-interactive: {:?}", p)
+          };
+
+          if response.responses.len() != info.prompts.len() {
+            return Ok(Some(false));
+          }
+
+          match self.authenticator.check_responses(user_name, &response.responses[..]) {
+            Challenge::Accept => return Ok(Some(true)),
+            Challenge::Reject => return Ok(Some(false)),
+            Challenge::More(next) => info = next
+          }
+        }
+      }
+      _ => Ok(Some(false))
+    };
+  }
+
+  /// Whether `user_name` may log in with a user certificate, either because
+  /// a trusted CA signed it for them or because the authenticator accepts
+  /// it, returning the options for the session.
+  ///
+  /// Like OpenSSH, the session only gets what the certificate's extensions
+  /// permit, like `permit-pty`.
+  fn check_certificate(&mut self, user_name: &str, certificate: &Certificate, key: &PublicKey) -> Option<Options> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    if certificate.validate(CertificateType::User, None, now).is_err() {
+      return None;
+    }
+
+    if !certificate.critical_options.is_empty() {
+      return None;
+    }
+
+    let ca = certificate.signature_key.blob();
+
+    let options = if self.trusted_user_cas.iter().any(|k| { k.blob() == ca }) {
+      if certificate.validate(CertificateType::User, Some(user_name), now).is_err() {
+        return None;
+      }
+
+      Options::default()
+    } else {
+      match self.authenticator.check_public_key(user_name, key, &self.address) {
+        Some(options) => options,
+        None => return None
+      }
+    };
+
+    return Some(Options {
+      no_pty: options.no_pty || !certificate.has_extension("permit-pty"),
+      no_port_forwarding: options.no_port_forwarding || !certificate.has_extension("permit-port-forwarding"),
+      no_agent_forwarding: options.no_agent_forwarding || !certificate.has_extension("permit-agent-forwarding"),
+      no_x11_forwarding: options.no_x11_forwarding || !certificate.has_extension("permit-X11-forwarding"),
+      no_user_rc: options.no_user_rc || !certificate.has_extension("permit-user-rc"),
+      ..options
+    });
+  }
+}
+
+/// The algorithm name of an encoded signature.
+fn signature_algorithm(signature: &[u8]) -> Option<String> {
+  return io::Cursor::new(signature).try_read_string().ok();
+}
+
+fn disconnect(stream: &mut PacketStream, reason: u32, message: &str) {
+  stream.write(&SSHPacket::Disconnect(Disconnect {
+    reason: reason,
+    message: message.to_string(),
+    language: String::new()
+  }));
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io;
+  use std::time::Duration;
+
+  use keys;
+  use keys::{certificate, ed25519, Key, PrivateKey, PublicKey};
+  use keys::certificate::{Certificate, CertificateType};
+  use keys::krl::{RevocationList, RevokedCertificates};
+
+  use packets::SSHPacket;
+  use packets::service::ServiceRequest;
+  use packets::authentication_request::{AuthenticationRequest, AuthenticationMethod};
+  use packets::keyboard_interactive::{InfoRequest, InfoResponse, Prompt};
+
+  use authentication::client::tests::Script;
+  use authentication::authorized_keys::{AuthorizedKeys, Options};
+
+  use super::{Authenticator, Challenge, Error, Server};
+
+  struct Users {
+    key: PublicKey
+  }
+
+  impl Authenticator for Users {
+    fn check_password(&mut self, user_name: &str, password: &str) -> bool {
+      return user_name == "user" && password == "hunter2";
+    }
+
+    fn check_public_key(&mut self, user_name: &str, key: &PublicKey, _: &str) -> Option<Options> {
+      if user_name == "user" && key.blob() == self.key.blob() {
+        return Some(Options::default());
+      }
+
+      return None;
+    }
+
+    fn start_challenge(&mut self, _: &str, _: &str) -> Option<InfoRequest> {
+      return Some(InfoRequest {
+        name: "".to_string(),
+        instruction: "".to_string(),
+        language: "".to_string(),
+        prompts: vec![Prompt { prompt: "OTP: ".to_string(), echo: true }]
+      });
+    }
+
+    fn check_responses(&mut self, _: &str, responses: &[String]) -> Challenge {
+      return if responses[0] == "123456" { Challenge::Accept } else { Challenge::Reject };
+    }
+  }
+
+  /// Accepts the keys in an `authorized_keys` file for any user.
+  struct KeyFile {
+    authorized_keys: AuthorizedKeys
+  }
+
+  impl Authenticator for KeyFile {
+    fn check_public_key(&mut self, _: &str, key: &PublicKey, address: &str) -> Option<Options> {
+      return self.authorized_keys.find(key, address, None).map(|e| { e.options.clone() });
+    }
+  }
+
+  fn key() -> PrivateKey {
+    return PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[9; 32]));
+  }
+
+  fn server<'a>() -> Server<'a> {
+    return Server::new(Box::new(Users { key: key().public_key() }));
+  }
+
+  fn service() -> SSHPacket {
+    return SSHPacket::ServiceRequest(ServiceRequest { service_name: "ssh-userauth".to_string() });
+  }
+
+  fn request(method: AuthenticationMethod) -> SSHPacket {
+    return SSHPacket::AuthenticationRequest(AuthenticationRequest {
+      user_name: "user".to_string(),
+      service_name: "ssh-connection".to_string(),
+      method: method
+    });
+  }
+
+  fn password(password: &str) -> SSHPacket {
+    return request(AuthenticationMethod::Password { password: password.to_string(), new_password: None });
+  }
+
+  fn signed_request(key: &PrivateKey) -> SSHPacket {
+    return signed_request_as("user", "ssh-ed25519", key.public_key_blob(), key);
+  }
+
+  /// A `publickey` request for `user_name` with the `public_key` blob of an
+  /// `algorithm` type, signed by `key`.
+  fn signed_request_as(user_name: &str, algorithm: &str, public_key: Vec<u8>, key: &PrivateKey) -> SSHPacket {
+    let mut request = AuthenticationRequest {
+      user_name: user_name.to_string(),
+      service_name: "ssh-connection".to_string(),
+      method: AuthenticationMethod::PublicKey {
+        algorithm: algorithm.to_string(),
+        public_key: public_key,
+        signature: None
+      }
+    };
+
+    let signature = key.sign("ssh-ed25519", &request.signed_data(&[1, 2, 3, 4]).unwrap()[..]);
+
+    if let AuthenticationMethod::PublicKey { signature: ref mut s, .. } = request.method {
+      *s = Some(signature);
+    }
+
+    return SSHPacket::AuthenticationRequest(request);
+  }
+
+  fn ca() -> PrivateKey {
+    return PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[1; 32]));
+  }
+
+  /// A request signed with `key()`, presenting a certificate for `alice`
+  /// with serial 42 signed by `ca()`.
+  fn certificate_request(user_name: &str) -> SSHPacket {
+    let mut certificate = Certificate::new(&key().public_key(), CertificateType::User);
+    certificate.serial = 42;
+    certificate.valid_principals = vec!["alice".to_string()];
+    certificate.sign(&ca());
+
+    let certificate = PublicKey { key: Key::Certificate(Box::new(certificate)), comment: String::new() };
+
+    return signed_request_as(user_name, "ssh-ed25519-cert-v01@openssh.com", certificate.blob(), &key());
+  }
+
+  /// The methods and partial success flag of each failure sent.
+  fn failures(script: &Script) -> Vec<(Vec<String>, bool)> {
+    return script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::AuthenticationFailure(ref f) => Some((f.methods.clone(), f.partial_success)),
+        _ => None
+      }
+    }).collect();
+  }
+
+  #[test]
+  fn accepts_passwords() {
+    let mut script = Script::new(vec![service(), request(AuthenticationMethod::NoneMethod), password("wrong"), password("hunter2")]);
+
+    assert_eq!(server().authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script).len(), 2);
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::AuthenticationSuccess(_)) => {},
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn answers_queries_and_verifies_signatures() {
+    let query = request(AuthenticationMethod::PublicKey {
+      algorithm: "ssh-ed25519".to_string(),
+      public_key: key().public_key_blob(),
+      signature: None
+    });
+
+    let mut script = Script::new(vec![service(), query, signed_request(&key())]);
+
+    assert_eq!(server().authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+
+    match script.outgoing[1] {
+      SSHPacket::AuthenticationMethodSpecific(60, _) => {},
+      ref p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn rejects_signatures_by_other_keys() {
+    let other = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[10; 32]));
+
+    let mut script = Script::new(vec![service(), signed_request(&other), password("hunter2")]);
+
+    assert_eq!(server().authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script).len(), 1);
+  }
+
+  #[test]
+  fn fails_malformed_keys_and_signatures() {
+    let garbage = |public_key: Vec<u8>, signature: Vec<u8>| {
+      request(AuthenticationMethod::PublicKey {
+        algorithm: "ssh-ed25519".to_string(),
+        public_key: public_key,
+        signature: Some(signature)
+      })
+    };
+
+    let blob = key().public_key_blob();
+
+    let mut script = Script::new(vec![
+      service(),
+      garbage(vec![0xff; 7], vec![0xff; 7]),
+      garbage(blob[..blob.len() - 1].to_vec(), vec![]),
+      garbage(blob.clone(), vec![0xff, 0xff, 0xff, 0xff, 0]),
+      garbage(blob.clone(), vec![0, 0, 0, 2, 0xff, 0xfe, 0, 0, 0, 0]),
+      garbage(blob.clone(), vec![0, 0, 0, 11, b's', b's', b'h', b'-', b'e', b'd', b'2', b'5', b'5', b'1', b'9', 0, 0, 0, 0]),
+      password("hunter2")
+    ]);
+
+    let mut server = server();
+    server.set_max_tries(10);
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script).len(), 5);
+  }
+
+  #[test]
+  fn checks_authorized_keys_options_against_the_address() {
+    let authorized_keys = AuthorizedKeys::parse(&format!("from=\"192.168.1.*\",no-pty {}", key().public_key().to_line()));
+
+    let mut server = Server::new(Box::new(KeyFile { authorized_keys: authorized_keys }));
+
+    let mut script = Script::new(vec![service(), signed_request(&key())]);
+    server.set_max_tries(1);
+
+    assert_eq!(server.authenticate(&mut script, "10.0.0.1"), Err(Error::TooManyTries));
+    assert_eq!(server.options(), &Options::default());
+
+    let mut script = Script::new(vec![service(), signed_request(&key())]);
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert!(server.options().no_pty);
+    assert!(!server.options().no_port_forwarding);
+  }
+
+  #[test]
+  fn restricts_certificates_to_their_extensions() {
+    let mut script = Script::new(vec![service(), certificate_request("alice")]);
+
+    let mut server = server();
+    server.add_trusted_user_ca(ca().public_key());
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("alice".to_string()));
+    assert_eq!(server.options(), &Options::default());
+  }
+
+  #[test]
+  fn requires_every_method_of_a_list() {
+    let mut script = Script::new(vec![service(), password("hunter2"), signed_request(&key()), password("hunter2")]);
+
+    let mut server = server();
+    server.set_authentication_methods("publickey,password");
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script), vec![
+      (vec!["publickey".to_string()], false),
+      (vec!["password".to_string()], true)
+    ]);
+  }
+
+  #[test]
+  fn runs_keyboard_interactive_challenges() {
+    let mut response = io::Cursor::new(Vec::new());
+    InfoResponse { responses: vec!["123456".to_string()] }.write(&mut response);
+
+    let mut script = Script::new(vec![
+      service(),
+      request(AuthenticationMethod::KeyboardInteractive { language: "".to_string(), submethods: "".to_string() }),
+      SSHPacket::AuthenticationMethodSpecific(61, response.into_inner())
+    ]);
+
+    assert_eq!(server().authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+  }
+
+  #[test]
+  fn disconnects_after_too_many_tries() {
+    let mut script = Script::new(vec![service(), password("a"), password("b"), password("c")]);
+
+    let mut server = server();
+    server.set_max_tries(3);
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Err(Error::TooManyTries));
+    assert_eq!(failures(&script).len(), 2);
+
+    match script.outgoing.last() {
+      Some(&SSHPacket::Disconnect(_)) => {},
+      p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn disconnects_after_the_grace_time() {
+    let mut script = Script::new(vec![service(), password("hunter2")]);
+
+    let mut server = server();
+    server.set_grace_time(Duration::from_secs(0));
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Err(Error::Timeout));
+  }
+
+  #[test]
+  fn sends_the_banner_once() {
+    let mut script = Script::new(vec![service(), password("wrong"), password("hunter2")]);
+
+    let mut server = server();
+    server.set_banner("Authorized users only");
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+
+    let banners = script.outgoing.iter().filter(|p| {
+      match p {
+        &&SSHPacket::AuthenticationBanner(_) => true,
+        _ => false
+      }
+    }).count();
+
+    assert_eq!(banners, 1);
+  }
+
+  #[test]
+  fn accepts_certificates_from_trusted_cas() {
+    let mut script = Script::new(vec![service(), certificate_request("bob"), certificate_request("alice")]);
+
+    let mut server = server();
+    server.add_trusted_user_ca(ca().public_key());
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("alice".to_string()));
+    assert_eq!(failures(&script).len(), 1);
+  }
+
+  #[test]
+  fn rejects_certificates_with_critical_options() {
+    let certificate = PublicKey::parse(certificate::tests::USER_CERTIFICATE).unwrap();
+    let (ref key, _) = keys::decode_private_key(certificate::tests::USER_KEY, None).unwrap()[0];
+
+    let request = signed_request_as("alice", "ssh-ed25519-cert-v01@openssh.com", certificate.blob(), key);
+
+    let mut script = Script::new(vec![service(), request, password("hunter2")]);
+
+    let mut server = server();
+    server.add_trusted_user_ca(PublicKey::parse(certificate::tests::CA).unwrap());
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script).len(), 1);
+  }
+
+  #[test]
+  fn rejects_certificates_from_unknown_cas() {
+    let mut script = Script::new(vec![service(), certificate_request("alice"), password("hunter2")]);
+
+    assert_eq!(server().authenticate(&mut script, "192.168.1.7"), Ok("user".to_string()));
+    assert_eq!(failures(&script).len(), 1);
+  }
+
+  #[test]
+  fn rejects_revoked_keys() {
+    let mut script = Script::new(vec![service(), certificate_request("alice"), signed_request(&key())]);
+
+    let mut revocation_list = RevocationList::default();
+    revocation_list.keys.push(key().public_key_blob());
+    revocation_list.certificates.push(RevokedCertificates { ca: None, serials: vec![(42, 42)], key_ids: Vec::new() });
+
+    let mut server = server();
+    server.add_trusted_user_ca(ca().public_key());
+    server.set_revocation_list(revocation_list);
+    server.set_max_tries(2);
+
+    assert_eq!(server.authenticate(&mut script, "192.168.1.7"), Err(Error::TooManyTries));
+  }
+}
@@ -0,0 +1,90 @@
+static ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `data` as padded base64, without line breaks.
+pub fn encode(data: &[u8]) -> String {
+  let mut result = String::new();
+
+  for chunk in data.chunks(3) {
+    let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+    let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+    for i in 0 .. 4 {
+      if i <= chunk.len() {
+        result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+      } else {
+        result.push('=');
+      }
+    }
+  }
+
+  return result;
+}
+
+/// Decodes base64, ignoring whitespace, returning `None` if it is malformed.
+pub fn decode(data: &str) -> Option<Vec<u8>> {
+  let mut result = Vec::new();
+
+  let mut n = 0u32;
+  let mut bits = 0;
+  let mut padding = 0;
+
+  for c in data.bytes() {
+    let v = match c {
+      b'A' ... b'Z' => c - b'A',
+      b'a' ... b'z' => c - b'a' + 26,
+      b'0' ... b'9' => c - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      b'=' => { padding += 1; continue }
+      b' ' | b'\t' | b'\r' | b'\n' => continue,
+      _ => return None
+    };
+
+    if padding > 0 {
+      return None;
+    }
+
+    n = n << 6 | v as u32;
+    bits += 6;
+
+    if bits >= 8 {
+      bits -= 8;
+      result.push((n >> bits) as u8);
+      n = n & ((1 << bits) - 1);
+    }
+  }
+
+  if padding > 2 || n != 0 {
+    return None;
+  }
+
+  return Some(result);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{encode, decode};
+
+  #[test]
+  fn encodes() {
+    assert_eq!(encode(b""), "");
+    assert_eq!(encode(b"f"), "Zg==");
+    assert_eq!(encode(b"fo"), "Zm8=");
+    assert_eq!(encode(b"foo"), "Zm9v");
+    assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+  }
+
+  #[test]
+  fn decodes() {
+    assert_eq!(decode("Zg=="), Some(b"f".to_vec()));
+    assert_eq!(decode("Zm8="), Some(b"fo".to_vec()));
+    assert_eq!(decode("Zm9v\nYmFy"), Some(b"foobar".to_vec()));
+    assert_eq!(decode("Zm9v!"), None);
+    assert_eq!(decode("Zg==Zg=="), None);
+  }
+
+  #[quickcheck]
+  fn roundtrips(data: Vec<u8>) -> bool {
+    return decode(&encode(&data[..])) == Some(data);
+  }
+}
@@ -0,0 +1,283 @@
+extern crate libc;
+
+use std::ptr;
+
+use num::{Zero, One, Integer};
+use num::bigint::{BigInt, BigUint, RandBigInt, Sign, ToBigInt};
+
+use rand;
+use rand::OsRng;
+
+/// Small primes for trial division before the Miller-Rabin test.
+static SMALL_PRIMES: &'static [u32] = &[
+  3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+  101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199,
+  211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307, 311, 313, 317, 331,
+  337, 347, 349, 353, 359, 367, 373, 379, 383, 389, 397, 401, 409, 419, 421, 431, 433, 439, 443, 449, 457,
+  461, 463, 467, 479, 487, 491, 499, 503, 509, 521, 523, 541, 547, 557, 563, 569, 571, 577, 587, 593, 599
+];
+
+/// Computes `base ^ exponent mod modulus`, for non-negative `base` and
+/// `exponent`.
+pub fn mod_exp(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+  let mut result: BigUint = One::one();
+  let mut base = base.to_biguint().unwrap();
+  let mut exponent = exponent.to_biguint().unwrap();
+  let modulus = modulus.to_biguint().unwrap();
+
+  while exponent > Zero::zero() {
+    let one: BigUint = One::one();
+    // Accumulate current base if current exponent bit is 1
+    if (&exponent & one) == One::one() {
+      result = result * &base;
+      result = result % &modulus;
+    }
+    // Get next base by squaring
+    base = &base * &base;
+    base = &base % &modulus;
+
+    // Get next bit of exponent
+    exponent = &exponent >> 1;
+  }
+
+  return result.to_bigint().unwrap();
+}
+
+/// Reduces `a` modulo `m`, always giving a non-negative result.
+pub fn modulo(a: &BigInt, m: &BigInt) -> BigInt {
+  return a.mod_floor(m);
+}
+
+/// Computes the inverse of `a` modulo `m`, which must exist.
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> BigInt {
+  let (mut r0, mut r1) = (m.clone(), modulo(a, m));
+  let (mut t0, mut t1): (BigInt, BigInt) = (Zero::zero(), One::one());
+
+  while !r1.is_zero() {
+    let q = &r0 / &r1;
+
+    let r2 = &r0 - &q * &r1;
+    r0 = r1;
+    r1 = r2;
+
+    let t2 = &t0 - &q * &t1;
+    t0 = t1;
+    t1 = t2;
+  }
+
+  if !r0.is_one() {
+    panic!("No inverse exists");
+  }
+
+  return modulo(&t0, m);
+}
+
+/// Interprets big-endian `bytes` as a non-negative integer.
+pub fn from_bytes_be(bytes: &[u8]) -> BigInt {
+  return BigInt::from_bytes_be(Sign::Plus, bytes);
+}
+
+/// Interprets little-endian `bytes` as a non-negative integer.
+pub fn from_bytes_le(bytes: &[u8]) -> BigInt {
+  return BigInt::from_bytes_le(Sign::Plus, bytes);
+}
+
+/// Writes non-negative `v` as exactly `length` big-endian bytes.
+pub fn to_bytes_be(v: &BigInt, length: usize) -> Vec<u8> {
+  let (_, bytes) = v.to_bytes_be();
+
+  if v.is_zero() {
+    return vec![0u8; length];
+  }
+
+  if bytes.len() > length {
+    panic!("Integer does not fit in {} bytes", length);
+  }
+
+  let mut result = vec![0u8; length - bytes.len()];
+  result.extend(bytes.into_iter());
+  return result;
+}
+
+/// Writes non-negative `v` as exactly `length` little-endian bytes.
+pub fn to_bytes_le(v: &BigInt, length: usize) -> Vec<u8> {
+  let mut result = to_bytes_be(v, length);
+  result.reverse();
+  return result;
+}
+
+/// A libcrypto `BIGNUM`, for handing integers to libcrypto.
+#[repr(C)] pub struct BIGNUM { _private: [u8; 0] }
+
+#[link(name = "crypto")]
+extern {
+  fn BN_bin2bn(data: *const u8, length: libc::c_int, result: *mut BIGNUM) -> *mut BIGNUM;
+  fn BN_bn2bin(v: *const BIGNUM, data: *mut u8) -> libc::c_int;
+  fn BN_num_bits(v: *const BIGNUM) -> libc::c_int;
+  pub fn BN_clear_free(v: *mut BIGNUM);
+}
+
+/// Copies non-negative `v` into a new `BIGNUM`, which the caller has to
+/// free, or hand to libcrypto.
+pub fn to_bn(v: &BigInt) -> *mut BIGNUM {
+  let (_, bytes) = v.to_bytes_be();
+
+  let bn = unsafe { BN_bin2bn(bytes.as_ptr(), bytes.len() as libc::c_int, ptr::null_mut()) };
+
+  if bn.is_null() {
+    panic!("Failed to allocate BIGNUM");
+  }
+
+  return bn;
+}
+
+/// Copies a `BIGNUM`, which must not be negative.
+pub fn from_bn(v: *const BIGNUM) -> BigInt {
+  let mut bytes = vec![0u8; (unsafe { BN_num_bits(v) } as usize + 7) / 8];
+
+  unsafe { BN_bn2bin(v, bytes.as_mut_ptr()) };
+
+  return from_bytes_be(&bytes[..]);
+}
+
+/// Tests whether `n` is prime, with trial division and `rounds` rounds of
+/// Miller-Rabin. A composite passes with probability at most `4^-rounds`.
+pub fn is_probable_prime(n: &BigInt, rounds: usize) -> bool {
+  let two = 2.to_bigint().unwrap();
+
+  if n < &two {
+    return false;
+  }
+
+  if n.is_even() {
+    return n == &two;
+  }
+
+  for &p in SMALL_PRIMES {
+    let p = p.to_bigint().unwrap();
+
+    if n == &p {
+      return true;
+    }
+
+    if (n % &p).is_zero() {
+      return false;
+    }
+  }
+
+  let one: BigInt = One::one();
+  let n_minus_one = n - &one;
+
+  let mut d = n_minus_one.clone();
+  let mut s = 0;
+
+  while d.is_even() {
+    d = d >> 1;
+    s += 1;
+  }
+
+  let mut rng = rand::thread_rng();
+
+  'witness: for _ in 0 .. rounds {
+    let a = rng.gen_bigint_range(&two, &n_minus_one);
+    let mut x = mod_exp(&a, &d, n);
+
+    if x == one || x == n_minus_one {
+      continue;
+    }
+
+    for _ in 1 .. s {
+      x = modulo(&(&x * &x), n);
+
+      if x == n_minus_one {
+        continue 'witness;
+      }
+    }
+
+    return false;
+  }
+
+  return true;
+}
+
+/// The operating system's random number generator, for key material, as
+/// `rand::thread_rng` is only seeded from it.
+pub fn os_rng() -> OsRng {
+  return match OsRng::new() {
+    Ok(rng) => rng,
+    Err(e) => panic!("Can't open the operating system's random number generator: {}", e)
+  };
+}
+
+/// Generates a random prime of exactly `bits` bits, with the top two bits
+/// set so that the product of two such primes has `2 * bits` bits.
+pub fn random_prime(bits: usize) -> BigInt {
+  assert!(bits >= 16);
+
+  let mut rng = os_rng();
+
+  let one: BigUint = One::one();
+  let top = (&one << (bits - 1)) | (&one << (bits - 2));
+
+  loop {
+    let candidate = (rng.gen_biguint(bits) | &top | &one).to_bigint().unwrap();
+
+    if is_probable_prime(&candidate, 40) {
+      return candidate;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use num::bigint::ToBigInt;
+
+  use super::{mod_exp, mod_inverse, modulo, to_bytes_be, to_bytes_le, from_bytes_be, from_bytes_le, is_probable_prime, random_prime};
+
+  #[test]
+  fn exponentiates() {
+    let v = mod_exp(&4.to_bigint().unwrap(), &13.to_bigint().unwrap(), &497.to_bigint().unwrap());
+
+    assert_eq!(v, 445.to_bigint().unwrap());
+  }
+
+  #[test]
+  fn inverts() {
+    assert_eq!(mod_inverse(&3.to_bigint().unwrap(), &11.to_bigint().unwrap()), 4.to_bigint().unwrap());
+    assert_eq!(mod_inverse(&(-3).to_bigint().unwrap(), &11.to_bigint().unwrap()), 7.to_bigint().unwrap());
+  }
+
+  #[test]
+  fn reduces_negative_numbers() {
+    assert_eq!(modulo(&(-1).to_bigint().unwrap(), &7.to_bigint().unwrap()), 6.to_bigint().unwrap());
+  }
+
+  #[test]
+  fn pads_bytes() {
+    let v = 0x0102.to_bigint().unwrap();
+
+    assert_eq!(to_bytes_be(&v, 4), vec![0, 0, 1, 2]);
+    assert_eq!(to_bytes_le(&v, 4), vec![2, 1, 0, 0]);
+    assert_eq!(from_bytes_be(&[0, 0, 1, 2]), v);
+    assert_eq!(from_bytes_le(&[2, 1, 0, 0]), v);
+  }
+
+  #[test]
+  fn tests_primality() {
+    assert!(is_probable_prime(&2.to_bigint().unwrap(), 10));
+    assert!(is_probable_prime(&7919.to_bigint().unwrap(), 10));
+    assert!(is_probable_prime(&((1u64 << 61) - 1).to_bigint().unwrap(), 10));
+    assert!(!is_probable_prime(&1.to_bigint().unwrap(), 10));
+    assert!(!is_probable_prime(&(7919 * 7927).to_bigint().unwrap(), 10));
+    // A Carmichael number, 1303 * 16927 * 157543
+    assert!(!is_probable_prime(&3474749660383u64.to_bigint().unwrap(), 10));
+  }
+
+  #[test]
+  fn generates_primes() {
+    let p = random_prime(128);
+
+    assert_eq!(p.bits(), 128);
+    assert!(is_probable_prime(&p, 20));
+  }
+}
@@ -0,0 +1,432 @@
+extern crate libc;
+
+/// A block cipher, for use with the modes of operation in this module.
+pub trait BlockCipher {
+  fn block_size(&self) -> usize;
+  fn encrypt_block(&self, block: &mut [u8]);
+  fn decrypt_block(&self, block: &mut [u8]);
+}
+
+#[repr(C)] struct AES_KEY { rd_key: [u32; 60], rounds: libc::c_int }
+
+#[link(name = "crypto")]
+extern {
+  fn AES_set_encrypt_key(user_key: *const u8, bits: libc::c_int, key: *mut AES_KEY) -> libc::c_int;
+  fn AES_set_decrypt_key(user_key: *const u8, bits: libc::c_int, key: *mut AES_KEY) -> libc::c_int;
+  fn AES_encrypt(input: *const u8, output: *mut u8, key: *const AES_KEY);
+  fn AES_decrypt(input: *const u8, output: *mut u8, key: *const AES_KEY);
+}
+
+/// AES with a 128, 192 or 256 bit key.
+pub struct AES {
+  encrypt: AES_KEY,
+  decrypt: AES_KEY
+}
+
+impl AES {
+  /// Constructs a new AES cipher, the key size is given by the length of
+  /// `key`.
+  pub fn new(key: &[u8]) -> AES {
+    let bits = match key.len() {
+      16 | 24 | 32 => (key.len() * 8) as libc::c_int,
+      _ => panic!("Invalid AES key size")
+    };
+
+    let mut aes = AES {
+      encrypt: AES_KEY { rd_key: [0; 60], rounds: 0 },
+      decrypt: AES_KEY { rd_key: [0; 60], rounds: 0 }
+    };
+
+    if unsafe { AES_set_encrypt_key(key.as_ptr(), bits, &mut aes.encrypt) } != 0 {
+      panic!("Failed to initialize AES state");
+    }
+
+    if unsafe { AES_set_decrypt_key(key.as_ptr(), bits, &mut aes.decrypt) } != 0 {
+      panic!("Failed to initialize AES state");
+    }
+
+    return aes;
+  }
+}
+
+impl BlockCipher for AES {
+  fn block_size(&self) -> usize {
+    return 16;
+  }
+
+  fn encrypt_block(&self, block: &mut [u8]) {
+    assert_eq!(block.len(), 16);
+
+    let input = block.to_vec();
+    unsafe { AES_encrypt(input.as_ptr(), block.as_mut_ptr(), &self.encrypt) };
+  }
+
+  fn decrypt_block(&self, block: &mut [u8]) {
+    assert_eq!(block.len(), 16);
+
+    let input = block.to_vec();
+    unsafe { AES_decrypt(input.as_ptr(), block.as_mut_ptr(), &self.decrypt) };
+  }
+}
+
+#[repr(C)] struct DES_key_schedule { ks: [u32; 32] }
+
+#[link(name = "crypto")]
+extern {
+  fn DES_set_key_unchecked(key: *const u8, schedule: *mut DES_key_schedule);
+  fn DES_ecb3_encrypt(input: *const u8, output: *mut u8, ks1: *const DES_key_schedule, ks2: *const DES_key_schedule, ks3: *const DES_key_schedule, enc: libc::c_int);
+}
+
+/// Triple DES, encrypt-decrypt-encrypt with three 64 bit keys.
+pub struct DES3 {
+  schedules: [DES_key_schedule; 3]
+}
+
+impl DES3 {
+  /// Constructs a new triple DES cipher from a 24 byte key.
+  pub fn new(key: &[u8]) -> DES3 {
+    if key.len() != 24 {
+      panic!("Invalid triple DES key size");
+    }
+
+    let mut des = DES3 {
+      schedules: [DES_key_schedule { ks: [0; 32] }, DES_key_schedule { ks: [0; 32] }, DES_key_schedule { ks: [0; 32] }]
+    };
+
+    for i in 0 .. 3 {
+      unsafe { DES_set_key_unchecked(key[8 * i ..].as_ptr(), &mut des.schedules[i]) };
+    }
+
+    return des;
+  }
+
+  fn crypt(&self, block: &mut [u8], encrypt: bool) {
+    assert_eq!(block.len(), 8);
+
+    let input = block.to_vec();
+
+    unsafe {
+      DES_ecb3_encrypt(input.as_ptr(), block.as_mut_ptr(), &self.schedules[0], &self.schedules[1], &self.schedules[2], if encrypt { 1 } else { 0 })
+    };
+  }
+}
+
+impl BlockCipher for DES3 {
+  fn block_size(&self) -> usize {
+    return 8;
+  }
+
+  fn encrypt_block(&self, block: &mut [u8]) {
+    self.crypt(block, true);
+  }
+
+  fn decrypt_block(&self, block: &mut [u8]) {
+    self.crypt(block, false);
+  }
+}
+
+/// Encrypts or decrypts `data` in counter mode, with the whole block as a
+/// big-endian counter starting at `iv`.
+pub fn ctr(cipher: &BlockCipher, iv: &[u8], data: &[u8]) -> Vec<u8> {
+  assert_eq!(iv.len(), cipher.block_size());
+
+  let mut counter = iv.to_vec();
+  let mut result = Vec::with_capacity(data.len());
+
+  for chunk in data.chunks(cipher.block_size()) {
+    let mut stream = counter.clone();
+    cipher.encrypt_block(&mut stream[..]);
+
+    for (a, b) in chunk.iter().zip(stream.iter()) {
+      result.push(a ^ b);
+    }
+
+    increment(&mut counter[..]);
+  }
+
+  return result;
+}
+
+/// Counter mode as a stream, for data that is encrypted or decrypted in
+/// parts, like the packets of a connection.
+pub struct CtrStream<C: BlockCipher> {
+  cipher: C,
+  counter: Vec<u8>,
+  /// The key stream of the current block, and how much of it was used
+  key_stream: Vec<u8>,
+  position: usize
+}
+
+impl<C: BlockCipher> CtrStream<C> {
+  pub fn new(cipher: C, iv: &[u8]) -> CtrStream<C> {
+    assert_eq!(iv.len(), cipher.block_size());
+
+    let block_size = cipher.block_size();
+
+    return CtrStream { cipher: cipher, counter: iv.to_vec(), key_stream: vec![0; block_size], position: block_size };
+  }
+
+  /// Encrypts or decrypts the next part of the stream.
+  pub fn apply(&mut self, data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+
+    for byte in data.iter() {
+      if self.position == self.key_stream.len() {
+        self.key_stream.copy_from_slice(&self.counter[..]);
+        self.cipher.encrypt_block(&mut self.key_stream[..]);
+        self.position = 0;
+
+        increment(&mut self.counter[..]);
+      }
+
+      result.push(byte ^ self.key_stream[self.position]);
+      self.position += 1;
+    }
+
+    return result;
+  }
+}
+
+/// Encrypts `data`, a multiple of the block size, in cipher block chaining
+/// mode.
+pub fn cbc_encrypt(cipher: &BlockCipher, iv: &[u8], data: &[u8]) -> Vec<u8> {
+  let size = cipher.block_size();
+
+  assert_eq!(iv.len(), size);
+  assert_eq!(data.len() % size, 0);
+
+  let mut previous = iv.to_vec();
+  let mut result = Vec::with_capacity(data.len());
+
+  for chunk in data.chunks(size) {
+    let mut block: Vec<u8> = chunk.iter().zip(previous.iter()).map(|(a, b)| { a ^ b }).collect();
+    cipher.encrypt_block(&mut block[..]);
+
+    result.extend(block.iter().cloned());
+    previous = block;
+  }
+
+  return result;
+}
+
+/// Decrypts `data`, a multiple of the block size, in cipher block chaining
+/// mode.
+pub fn cbc_decrypt(cipher: &BlockCipher, iv: &[u8], data: &[u8]) -> Vec<u8> {
+  let size = cipher.block_size();
+
+  assert_eq!(iv.len(), size);
+  assert_eq!(data.len() % size, 0);
+
+  let mut previous = iv;
+  let mut result = Vec::with_capacity(data.len());
+
+  for chunk in data.chunks(size) {
+    let mut block = chunk.to_vec();
+    cipher.decrypt_block(&mut block[..]);
+
+    result.extend(block.iter().zip(previous.iter()).map(|(a, b)| { a ^ b }));
+    previous = chunk;
+  }
+
+  return result;
+}
+
+/// Encrypts `data` in Galois/Counter mode with a 12 byte `iv`, returning the
+/// ciphertext and the 16 byte authentication tag.
+pub fn gcm_encrypt(cipher: &AES, iv: &[u8], aad: &[u8], data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+  let (j0, h) = gcm_setup(cipher, iv);
+
+  let mut counter = j0.clone();
+  increment(&mut counter[12 ..]);
+
+  let ciphertext = ctr(cipher, &counter[..], data);
+  let tag = gcm_tag(cipher, &j0[..], &h[..], aad, &ciphertext[..]);
+
+  return (ciphertext, tag);
+}
+
+/// Decrypts `data` in Galois/Counter mode with a 12 byte `iv`, returning
+/// `None` unless `tag` authenticates it.
+pub fn gcm_decrypt(cipher: &AES, iv: &[u8], aad: &[u8], data: &[u8], tag: &[u8]) -> Option<Vec<u8>> {
+  let (j0, h) = gcm_setup(cipher, iv);
+
+  let expected = gcm_tag(cipher, &j0[..], &h[..], aad, data);
+
+  if tag.len() != 16 || expected.iter().zip(tag.iter()).fold(0, |d, (a, b)| { d | (a ^ b) }) != 0 {
+    return None;
+  }
+
+  let mut counter = j0.clone();
+  increment(&mut counter[12 ..]);
+
+  return Some(ctr(cipher, &counter[..], data));
+}
+
+fn gcm_setup(cipher: &AES, iv: &[u8]) -> (Vec<u8>, Vec<u8>) {
+  assert_eq!(iv.len(), 12);
+
+  let mut h = vec![0u8; 16];
+  cipher.encrypt_block(&mut h[..]);
+
+  let mut j0 = iv.to_vec();
+  j0.extend([0, 0, 0, 1].iter().cloned());
+
+  return (j0, h);
+}
+
+fn gcm_tag(cipher: &AES, j0: &[u8], h: &[u8], aad: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+  let h = to_u64s(h);
+  let mut x = (0u64, 0u64);
+
+  for data in [aad, ciphertext].iter() {
+    for chunk in data.chunks(16) {
+      let mut block = chunk.to_vec();
+      block.resize(16, 0);
+
+      let b = to_u64s(&block[..]);
+      x = gf_multiply((x.0 ^ b.0, x.1 ^ b.1), h);
+    }
+  }
+
+  x = gf_multiply((x.0 ^ (aad.len() as u64 * 8), x.1 ^ (ciphertext.len() as u64 * 8)), h);
+
+  let mut tag = j0.to_vec();
+  cipher.encrypt_block(&mut tag[..]);
+
+  for i in 0 .. 8 {
+    tag[i] ^= (x.0 >> (56 - 8 * i)) as u8;
+    tag[i + 8] ^= (x.1 >> (56 - 8 * i)) as u8;
+  }
+
+  return tag;
+}
+
+fn to_u64s(block: &[u8]) -> (u64, u64) {
+  let mut x = (0u64, 0u64);
+
+  for i in 0 .. 8 {
+    x.0 = x.0 << 8 | block[i] as u64;
+    x.1 = x.1 << 8 | block[i + 8] as u64;
+  }
+
+  return x;
+}
+
+/// Multiplication in GF(2^128), with the bit order of GCM.
+fn gf_multiply(x: (u64, u64), y: (u64, u64)) -> (u64, u64) {
+  let mut z = (0u64, 0u64);
+  let mut v = y;
+
+  for i in 0 .. 128 {
+    let bit = if i < 64 { x.0 >> (63 - i) } else { x.1 >> (127 - i) } & 1;
+
+    if bit == 1 {
+      z = (z.0 ^ v.0, z.1 ^ v.1);
+    }
+
+    let lsb = v.1 & 1;
+
+    v = (v.0 >> 1, v.1 >> 1 | v.0 << 63);
+
+    if lsb == 1 {
+      v.0 ^= 0xE1 << 56;
+    }
+  }
+
+  return z;
+}
+
+/// Increments a big-endian counter, wrapping around.
+fn increment(counter: &mut [u8]) {
+  for byte in counter.iter_mut().rev() {
+    *byte = byte.wrapping_add(1);
+
+    if *byte != 0 {
+      break;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{AES, DES3, BlockCipher, CtrStream, ctr, cbc_encrypt, cbc_decrypt, gcm_encrypt, gcm_decrypt};
+
+  fn from_hex(s: &str) -> Vec<u8> {
+    return (0 .. s.len() / 2).map(|i| { u8::from_str_radix(&s[2 * i .. 2 * i + 2], 16).unwrap() }).collect();
+  }
+
+  static KEY: &'static str = "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4";
+  static PLAINTEXT: &'static str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51";
+
+  // NIST SP 800-38A, F.5.5
+  #[test]
+  fn encrypts_aes_256_ctr() {
+    let aes = AES::new(&from_hex(KEY)[..]);
+    let iv = from_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+
+    let ciphertext = ctr(&aes, &iv[..], &from_hex(PLAINTEXT)[..]);
+
+    assert_eq!(ciphertext, from_hex("601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5"));
+    assert_eq!(ctr(&aes, &iv[..], &ciphertext[..]), from_hex(PLAINTEXT));
+  }
+
+  #[test]
+  fn streams_aes_256_ctr_in_parts() {
+    let iv = from_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    let plaintext = from_hex(PLAINTEXT);
+
+    let mut stream = CtrStream::new(AES::new(&from_hex(KEY)[..]), &iv[..]);
+
+    let mut ciphertext = stream.apply(&plaintext[.. 5]);
+    ciphertext.extend(stream.apply(&plaintext[5 .. 21]));
+    ciphertext.extend(stream.apply(&plaintext[21 ..]));
+
+    assert_eq!(ciphertext, from_hex("601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5"));
+  }
+
+  // NIST SP 800-38A, F.2.5
+  #[test]
+  fn encrypts_aes_256_cbc() {
+    let aes = AES::new(&from_hex(KEY)[..]);
+    let iv = from_hex("000102030405060708090a0b0c0d0e0f");
+
+    let ciphertext = cbc_encrypt(&aes, &iv[..], &from_hex(PLAINTEXT)[..]);
+
+    assert_eq!(ciphertext, from_hex("f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d"));
+    assert_eq!(cbc_decrypt(&aes, &iv[..], &ciphertext[..]), from_hex(PLAINTEXT));
+  }
+
+  // NIST SP 800-67, appendix B.1
+  #[test]
+  fn encrypts_des3() {
+    let des = DES3::new(&from_hex("0123456789abcdef23456789abcdef01456789abcdef0123")[..]);
+
+    let mut block = from_hex("5468652071756663");
+    des.encrypt_block(&mut block[..]);
+
+    assert_eq!(block, from_hex("a826fd8ce53b855f"));
+
+    des.decrypt_block(&mut block[..]);
+
+    assert_eq!(block, from_hex("5468652071756663"));
+  }
+
+  // Test cases 13 and 14 from the GCM specification
+  #[test]
+  fn encrypts_aes_256_gcm() {
+    let aes = AES::new(&[0u8; 32]);
+
+    let (ciphertext, tag) = gcm_encrypt(&aes, &[0u8; 12], &[], &[]);
+
+    assert_eq!(ciphertext, vec![]);
+    assert_eq!(tag, from_hex("530f8afbc74536b9a963b4f1c4cb738b"));
+
+    let (ciphertext, tag) = gcm_encrypt(&aes, &[0u8; 12], &[], &[0u8; 16]);
+
+    assert_eq!(ciphertext, from_hex("cea7403d4d606b6e074ec5d3baf39d18"));
+    assert_eq!(tag, from_hex("d0d1c8a799996bf0265b98b5d48ab919"));
+
+    assert_eq!(gcm_decrypt(&aes, &[0u8; 12], &[], &ciphertext[..], &tag[..]), Some(vec![0u8; 16]));
+    assert_eq!(gcm_decrypt(&aes, &[0u8; 12], &[1], &ciphertext[..], &tag[..]), None);
+  }
+}
@@ -0,0 +1,667 @@
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+
+use packets::SSHPacket;
+use packets::disconnect::Disconnect;
+use packets::connection::{ChannelOpen, ChannelOpenConfirmation, ChannelOpenFailure, ChannelWindowAdjust};
+use packets::connection::{ChannelData, ChannelExtendedData, ChannelEof, ChannelClose};
+use packets::connection::{ChannelRequest, ChannelSuccess, ChannelFailure};
+
+use transport::ssh_transport::PacketStream;
+
+/// The window we give the peer on each channel, by default.
+pub const WINDOW_SIZE: u32 = 2 * 1024 * 1024;
+/// The largest data packet we accept, by default.
+pub const MAXIMUM_PACKET_SIZE: u32 = 32 * 1024;
+
+/// Why a channel operation failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+  /// The peer disconnected
+  Disconnected(Disconnect),
+  /// The peer would not open the channel
+  OpenFailed(ChannelOpenFailure),
+  /// The channel was closed, or sending was ended with EOF
+  Closed
+}
+
+/// Something the peer sent on a channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  Data(Vec<u8>),
+  /// Data of a type, like `EXTENDED_DATA_STDERR`
+  ExtendedData(u32, Vec<u8>),
+  /// The peer will send no more data
+  Eof,
+  /// A request, which needs a `reply` if it wants one
+  Request(ChannelRequest),
+  /// The channel is closed, the last event
+  Close
+}
+
+struct Channel {
+  remote_id: u32,
+  opened: bool,
+  open_failure: Option<ChannelOpenFailure>,
+  /// How much we may still send, and in how large packets
+  remote_window: u32,
+  remote_maximum_packet_size: u32,
+  /// How much the peer may still send, and how much of what it sent was
+  /// consumed, but not yet given back in a window adjustment
+  local_window: u32,
+  local_maximum_packet_size: u32,
+  consumed: u32,
+  events: VecDeque<Event>,
+  /// The reply to our last request wanting one
+  reply: Option<bool>,
+  eof_sent: bool,
+  close_sent: bool,
+  close_received: bool
+}
+
+impl Channel {
+  fn new(remote_id: u32, window_size: u32, maximum_packet_size: u32) -> Channel {
+    return Channel {
+      remote_id: remote_id,
+      opened: false,
+      open_failure: None,
+      remote_window: 0,
+      remote_maximum_packet_size: 0,
+      local_window: window_size,
+      local_maximum_packet_size: maximum_packet_size,
+      consumed: 0,
+      events: VecDeque::new(),
+      reply: None,
+      eof_sent: false,
+      close_sent: false,
+      close_received: false
+    };
+  }
+
+  /// Takes `length` bytes of data out of the window.
+  fn receive(&mut self, length: usize) {
+    if length as u64 > cmp::min(self.local_window, self.local_maximum_packet_size) as u64 {
+      panic!("Peer sent {} bytes, more than the window or packet size allows", length);
+    }
+
+    self.local_window -= length as u32;
+  }
+}
+
+/// Runs the channels of the connection protocol over a transport.
+///
+/// Each channel has a window for each direction. Data we send waits for the
+/// peer to adjust its window, and the window we give is adjusted once half
+/// of it was consumed as `Event`s.
+///
+/// See [RFC 4254, section 5](https://tools.ietf.org/html/rfc4254#section-5).
+pub struct Multiplexer<'a> {
+  stream: &'a mut PacketStream,
+  channels: HashMap<u32, Channel>,
+  next_id: u32,
+  /// Channels the peer wants to open, waiting to be accepted or rejected
+  opens: VecDeque<ChannelOpen>,
+  window_size: u32,
+  maximum_packet_size: u32
+}
+
+impl<'a> Multiplexer<'a> {
+  pub fn new(stream: &'a mut PacketStream) -> Multiplexer<'a> {
+    return Multiplexer {
+      stream: stream,
+      channels: HashMap::new(),
+      next_id: 0,
+      opens: VecDeque::new(),
+      window_size: WINDOW_SIZE,
+      maximum_packet_size: MAXIMUM_PACKET_SIZE
+    };
+  }
+
+  /// Sets the window and largest packet we give the peer on channels
+  /// opened from now on.
+  pub fn set_window(&mut self, window_size: u32, maximum_packet_size: u32) {
+    self.window_size = window_size;
+    self.maximum_packet_size = maximum_packet_size;
+  }
+
+  /// The transport the channels run over.
+  pub fn stream(&mut self) -> &mut PacketStream {
+    return self.stream;
+  }
+
+  fn add_channel(&mut self, remote_id: u32) -> u32 {
+    let id = self.next_id;
+
+    self.next_id += 1;
+    self.channels.insert(id, Channel::new(remote_id, self.window_size, self.maximum_packet_size));
+
+    return id;
+  }
+
+  /// Opens a channel of `channel_type`, with its type specific `data`,
+  /// returning its id once the peer confirms.
+  pub fn open(&mut self, channel_type: &str, data: &[u8]) -> Result<u32, Error> {
+    let id = self.add_channel(0);
+
+    self.stream.write(&SSHPacket::ChannelOpen(ChannelOpen {
+      channel_type: channel_type.to_string(),
+      sender_channel: id,
+      initial_window_size: self.window_size,
+      maximum_packet_size: self.maximum_packet_size,
+      data: data.to_vec()
+    }));
+
+    loop {
+      let failure = {
+        let channel = &self.channels[&id];
+
+        if channel.opened {
+          return Ok(id);
+        }
+
+        channel.open_failure.clone()
+      };
+
+      if let Some(failure) = failure {
+        self.channels.remove(&id);
+
+        return Err(Error::OpenFailed(failure));
+      }
+
+      try!(self.process());
+    }
+  }
+
+  /// The next channel the peer wants to open, if it asked for one.
+  pub fn take_open(&mut self) -> Option<ChannelOpen> {
+    return self.opens.pop_front();
+  }
+
+  /// Waits for the peer to want to open a channel.
+  pub fn wait_for_open(&mut self) -> Result<ChannelOpen, Error> {
+    loop {
+      if let Some(open) = self.opens.pop_front() {
+        return Ok(open);
+      }
+
+      try!(self.process());
+    }
+  }
+
+  /// Accepts a channel the peer wants to open, with the channel type
+  /// specific `data`, returning its id.
+  pub fn accept(&mut self, open: &ChannelOpen, data: &[u8]) -> u32 {
+    let id = self.add_channel(open.sender_channel);
+
+    {
+      let channel = self.channels.get_mut(&id).unwrap();
+
+      channel.opened = true;
+      channel.remote_window = open.initial_window_size;
+      channel.remote_maximum_packet_size = open.maximum_packet_size;
+    }
+
+    self.stream.write(&SSHPacket::ChannelOpenConfirmation(ChannelOpenConfirmation {
+      recipient_channel: open.sender_channel,
+      sender_channel: id,
+      initial_window_size: self.window_size,
+      maximum_packet_size: self.maximum_packet_size,
+      data: data.to_vec()
+    }));
+
+    return id;
+  }
+
+  /// Refuses a channel the peer wants to open, for a reason like
+  /// `UNKNOWN_CHANNEL_TYPE`.
+  pub fn reject(&mut self, open: &ChannelOpen, reason_code: u32, description: &str) {
+    self.stream.write(&SSHPacket::ChannelOpenFailure(ChannelOpenFailure {
+      recipient_channel: open.sender_channel,
+      reason_code: reason_code,
+      description: description.to_string(),
+      language: String::new()
+    }));
+  }
+
+  fn channel(&mut self, id: u32) -> &mut Channel {
+    return match self.channels.get_mut(&id) {
+      Some(channel) => channel,
+      None => panic!("Message for unknown channel {}", id)
+    };
+  }
+
+  /// A channel that is open for sending.
+  fn sending_channel(&mut self, id: u32) -> Result<&mut Channel, Error> {
+    return match self.channels.get_mut(&id) {
+      Some(channel) if channel.opened && !channel.eof_sent && !channel.close_sent => Ok(channel),
+      _ => Err(Error::Closed)
+    };
+  }
+
+  /// Reads a packet and takes note of what it means for the channels.
+  ///
+  /// The other methods do this while they wait. This is for callers that
+  /// know a packet has arrived, and want to take events without waiting.
+  pub fn process(&mut self) -> Result<(), Error> {
+    match self.stream.read() {
+      SSHPacket::ChannelOpen(open) => self.opens.push_back(open),
+      SSHPacket::ChannelOpenConfirmation(confirmation) => {
+        let channel = self.channel(confirmation.recipient_channel);
+
+        channel.remote_id = confirmation.sender_channel;
+        channel.remote_window = confirmation.initial_window_size;
+        channel.remote_maximum_packet_size = confirmation.maximum_packet_size;
+        channel.opened = true;
+      }
+      SSHPacket::ChannelOpenFailure(failure) => {
+        let id = failure.recipient_channel;
+
+        self.channel(id).open_failure = Some(failure);
+      }
+      SSHPacket::ChannelWindowAdjust(adjust) => {
+        let channel = self.channel(adjust.recipient_channel);
+
+        channel.remote_window = channel.remote_window.saturating_add(adjust.bytes_to_add);
+      }
+      SSHPacket::ChannelData(data) => {
+        let channel = self.channel(data.recipient_channel);
+
+        channel.receive(data.data.len());
+        channel.events.push_back(Event::Data(data.data));
+      }
+      SSHPacket::ChannelExtendedData(data) => {
+        let channel = self.channel(data.recipient_channel);
+
+        channel.receive(data.data.len());
+        channel.events.push_back(Event::ExtendedData(data.data_type_code, data.data));
+      }
+      SSHPacket::ChannelEof(eof) => self.channel(eof.recipient_channel).events.push_back(Event::Eof),
+      SSHPacket::ChannelClose(close) => {
+        let (remote_id, close_sent) = {
+          let channel = self.channel(close.recipient_channel);
+
+          channel.close_received = true;
+          channel.events.push_back(Event::Close);
+
+          (channel.remote_id, channel.close_sent)
+        };
+
+        if !close_sent {
+          self.channel(close.recipient_channel).close_sent = true;
+          self.stream.write(&SSHPacket::ChannelClose(ChannelClose { recipient_channel: remote_id }));
+        }
+      }
+      SSHPacket::ChannelRequest(request) => {
+        let id = request.recipient_channel;
+
+        self.channel(id).events.push_back(Event::Request(request));
+      }
+      SSHPacket::ChannelSuccess(success) => self.channel(success.recipient_channel).reply = Some(true),
+      SSHPacket::ChannelFailure(failure) => self.channel(failure.recipient_channel).reply = Some(false),
+      SSHPacket::GlobalRequest(request) => {
+        if request.want_reply {
+          self.stream.write(&SSHPacket::RequestFailure);
+        }
+      }
+      SSHPacket::RequestSuccess(_) | SSHPacket::RequestFailure => {}
+      SSHPacket::Disconnect(d) => return Err(Error::Disconnected(d)),
+      p => panic!("Unexpected packet on the connection: {:?}", p)
+    }
+
+    return Ok(());
+  }
+
+  /// The next event on the channel, waiting for one if there is none yet.
+  /// Data taken this way counts as consumed for the window.
+  pub fn next_event(&mut self, id: u32) -> Result<Event, Error> {
+    loop {
+      if let Some(event) = try!(self.take_event(id)) {
+        return Ok(event);
+      }
+
+      try!(self.process());
+    }
+  }
+
+  /// The next event on the channel, if one has arrived.
+  pub fn take_event(&mut self, id: u32) -> Result<Option<Event>, Error> {
+    let event = match self.channels.get_mut(&id) {
+      Some(channel) => channel.events.pop_front(),
+      None => return Err(Error::Closed)
+    };
+
+    match event {
+      Some(Event::Data(ref data)) | Some(Event::ExtendedData(_, ref data)) => self.consume(id, data.len() as u32),
+      Some(Event::Close) => { self.channels.remove(&id); }
+      _ => {}
+    }
+
+    return Ok(event);
+  }
+
+  /// Gives `length` consumed bytes back to the peer, once there is enough
+  /// to be worth a window adjustment.
+  fn consume(&mut self, id: u32, length: u32) {
+    let window_size = self.window_size;
+
+    let adjustment = {
+      let channel = self.channel(id);
+
+      channel.consumed += length;
+
+      if channel.consumed < window_size / 2 || channel.close_received {
+        return;
+      }
+
+      let bytes_to_add = channel.consumed;
+
+      channel.local_window += bytes_to_add;
+      channel.consumed = 0;
+
+      ChannelWindowAdjust { recipient_channel: channel.remote_id, bytes_to_add: bytes_to_add }
+    };
+
+    self.stream.write(&SSHPacket::ChannelWindowAdjust(adjustment));
+  }
+
+  /// Sends `data`, waiting for the peer to adjust the window when it is
+  /// full, with a `data_type` for extended data.
+  fn send(&mut self, id: u32, data_type: Option<u32>, data: &[u8]) -> Result<(), Error> {
+    let mut data = data;
+
+    while !data.is_empty() {
+      let (remote_id, length) = {
+        let channel = try!(self.sending_channel(id));
+        let length = cmp::min(channel.remote_window, channel.remote_maximum_packet_size) as usize;
+
+        (channel.remote_id, cmp::min(length, data.len()))
+      };
+
+      if length == 0 {
+        try!(self.process());
+
+        continue;
+      }
+
+      self.channel(id).remote_window -= length as u32;
+
+      self.stream.write(&match data_type {
+        Some(data_type) => SSHPacket::ChannelExtendedData(ChannelExtendedData {
+          recipient_channel: remote_id,
+          data_type_code: data_type,
+          data: data[.. length].to_vec()
+        }),
+        None => SSHPacket::ChannelData(ChannelData { recipient_channel: remote_id, data: data[.. length].to_vec() })
+      });
+
+      data = &data[length ..];
+    }
+
+    return Ok(());
+  }
+
+  pub fn send_data(&mut self, id: u32, data: &[u8]) -> Result<(), Error> {
+    return self.send(id, None, data);
+  }
+
+  pub fn send_extended_data(&mut self, id: u32, data_type: u32, data: &[u8]) -> Result<(), Error> {
+    return self.send(id, Some(data_type), data);
+  }
+
+  /// Tells the peer we will send no more data.
+  pub fn send_eof(&mut self, id: u32) -> Result<(), Error> {
+    let remote_id = {
+      let channel = try!(self.sending_channel(id));
+
+      channel.eof_sent = true;
+      channel.remote_id
+    };
+
+    self.stream.write(&SSHPacket::ChannelEof(ChannelEof { recipient_channel: remote_id }));
+
+    return Ok(());
+  }
+
+  /// Closes the channel. Events the peer sent before it closes its end too
+  /// can still be taken, up to `Event::Close`.
+  pub fn close(&mut self, id: u32) {
+    let remote_id = match self.channels.get_mut(&id) {
+      Some(channel) if !channel.close_sent => {
+        channel.close_sent = true;
+        channel.remote_id
+      }
+      _ => return
+    };
+
+    self.stream.write(&SSHPacket::ChannelClose(ChannelClose { recipient_channel: remote_id }));
+  }
+
+  /// Sends a request of `request_type`, with its type specific `data`. With
+  /// `want_reply`, waits for the peer to reply and returns whether it
+  /// succeeded.
+  pub fn request(&mut self, id: u32, request_type: &str, want_reply: bool, data: &[u8]) -> Result<bool, Error> {
+    let remote_id = match self.channels.get_mut(&id) {
+      Some(channel) if channel.opened && !channel.close_sent => {
+        channel.reply = None;
+        channel.remote_id
+      }
+      _ => return Err(Error::Closed)
+    };
+
+    self.stream.write(&SSHPacket::ChannelRequest(ChannelRequest {
+      recipient_channel: remote_id,
+      request_type: request_type.to_string(),
+      want_reply: want_reply,
+      data: data.to_vec()
+    }));
+
+    if !want_reply {
+      return Ok(true);
+    }
+
+    loop {
+      match self.channels.get(&id) {
+        Some(channel) if channel.reply.is_some() => return Ok(channel.reply.unwrap()),
+        Some(channel) if !channel.close_received => {}
+        _ => return Err(Error::Closed)
+      }
+
+      try!(self.process());
+    }
+  }
+
+  /// Answers a request from the peer that wants a reply.
+  pub fn reply(&mut self, id: u32, success: bool) {
+    let remote_id = match self.channels.get(&id) {
+      Some(channel) if !channel.close_sent => channel.remote_id,
+      _ => return
+    };
+
+    self.stream.write(&if success {
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: remote_id })
+    } else {
+      SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: remote_id })
+    });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use packets::SSHPacket;
+  use packets::connection::{self, ChannelOpen, ChannelOpenConfirmation, ChannelOpenFailure, ChannelWindowAdjust};
+  use packets::connection::{ChannelData, ChannelExtendedData, ChannelEof, ChannelClose};
+  use packets::connection::{ChannelRequest, ChannelSuccess, GlobalRequest};
+
+  use authentication::client::tests::Script;
+
+  use super::{Error, Event, Multiplexer};
+
+  fn confirmation(recipient_channel: u32, sender_channel: u32, window: u32, maximum_packet_size: u32) -> SSHPacket {
+    return SSHPacket::ChannelOpenConfirmation(ChannelOpenConfirmation {
+      recipient_channel: recipient_channel,
+      sender_channel: sender_channel,
+      initial_window_size: window,
+      maximum_packet_size: maximum_packet_size,
+      data: vec![]
+    });
+  }
+
+  fn outgoing(script: &Script) -> Vec<String> {
+    return script.outgoing.iter().map(|p| { format!("{:?}", p) }).collect();
+  }
+
+  #[test]
+  fn opens_channels() {
+    let failure = ChannelOpenFailure { recipient_channel: 1, reason_code: connection::ADMINISTRATIVELY_PROHIBITED, description: "no".to_string(), language: String::new() };
+
+    let mut script = Script::new(vec![confirmation(0, 7, 100, 10), SSHPacket::ChannelOpenFailure(failure.clone())]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+
+      assert_eq!(multiplexer.open("session", &[]), Ok(0));
+      assert_eq!(multiplexer.open("direct-tcpip", &[1]), Err(Error::OpenFailed(failure)));
+      assert_eq!(multiplexer.send_data(1, b"data"), Err(Error::Closed));
+    }
+
+    match script.outgoing[1] {
+      SSHPacket::ChannelOpen(ref open) => {
+        assert_eq!((open.channel_type.as_str(), open.sender_channel, &open.data[..]), ("direct-tcpip", 1, &[1][..]));
+      }
+      ref p => panic!("Unexpected packet {:?}", p)
+    }
+  }
+
+  #[test]
+  fn sends_within_the_window() {
+    let mut script = Script::new(vec![
+      confirmation(0, 7, 10, 4),
+      SSHPacket::ChannelWindowAdjust(ChannelWindowAdjust { recipient_channel: 0, bytes_to_add: 5 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let id = multiplexer.open("session", &[]).unwrap();
+
+      assert_eq!(multiplexer.send_data(id, b"0123456789abc"), Ok(()));
+      assert_eq!(multiplexer.send_eof(id), Ok(()));
+      assert_eq!(multiplexer.send_data(id, b"more"), Err(Error::Closed));
+    }
+
+    let packets = vec![
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 7, data: b"0123".to_vec() }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 7, data: b"4567".to_vec() }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 7, data: b"89".to_vec() }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 7, data: b"abc".to_vec() }),
+      SSHPacket::ChannelEof(ChannelEof { recipient_channel: 7 })
+    ];
+
+    assert_eq!(outgoing(&script)[1 ..].to_vec(), packets.iter().map(|p| { format!("{:?}", p) }).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn adjusts_the_window_as_data_is_consumed() {
+    let mut script = Script::new(vec![
+      confirmation(0, 7, 100, 100),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: vec![0; 4] }),
+      SSHPacket::ChannelExtendedData(ChannelExtendedData { recipient_channel: 0, data_type_code: connection::EXTENDED_DATA_STDERR, data: vec![1; 4] }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: vec![2; 8] }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: vec![3; 8] })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      multiplexer.set_window(16, 8);
+
+      let id = multiplexer.open("session", &[]).unwrap();
+
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Data(vec![0; 4])));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::ExtendedData(1, vec![1; 4])));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Data(vec![2; 8])));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Data(vec![3; 8])));
+    }
+
+    let adjustment = format!("{:?}", SSHPacket::ChannelWindowAdjust(ChannelWindowAdjust { recipient_channel: 7, bytes_to_add: 8 }));
+
+    assert_eq!(outgoing(&script)[1 ..].to_vec(), vec![adjustment.clone(), adjustment.clone(), adjustment]);
+  }
+
+  #[test]
+  #[should_panic]
+  fn refuses_data_beyond_the_window() {
+    let mut script = Script::new(vec![
+      confirmation(0, 7, 100, 100),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: vec![0; 20] })
+    ]);
+
+    let mut multiplexer = Multiplexer::new(&mut script);
+    multiplexer.set_window(16, 8);
+
+    let id = multiplexer.open("session", &[]).unwrap();
+    let _ = multiplexer.next_event(id);
+  }
+
+  #[test]
+  fn waits_for_replies_and_answers_close() {
+    let request = ChannelRequest { recipient_channel: 0, request_type: "keepalive@openssh.com".to_string(), want_reply: true, data: vec![] };
+
+    let mut script = Script::new(vec![
+      confirmation(0, 7, 100, 100),
+      SSHPacket::GlobalRequest(GlobalRequest { request_name: "hostkeys-00@openssh.com".to_string(), want_reply: true, data: vec![] }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: b"early".to_vec() }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelRequest(request.clone()),
+      SSHPacket::ChannelEof(ChannelEof { recipient_channel: 0 }),
+      SSHPacket::ChannelClose(ChannelClose { recipient_channel: 0 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let id = multiplexer.open("session", &[]).unwrap();
+
+      assert_eq!(multiplexer.request(id, "exec", true, &[]), Ok(true));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Data(b"early".to_vec())));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Request(request)));
+      multiplexer.reply(id, false);
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Eof));
+      assert_eq!(multiplexer.next_event(id), Ok(Event::Close));
+      assert_eq!(multiplexer.next_event(id), Err(Error::Closed));
+    }
+
+    let outgoing = outgoing(&script);
+
+    assert_eq!(outgoing[2], "RequestFailure");
+    assert!(outgoing[3].starts_with("ChannelFailure"));
+    assert_eq!(outgoing[4], format!("{:?}", SSHPacket::ChannelClose(ChannelClose { recipient_channel: 7 })));
+  }
+
+  #[test]
+  fn accepts_channels_from_the_peer() {
+    let open = ChannelOpen { channel_type: "auth-agent@openssh.com".to_string(), sender_channel: 3, initial_window_size: 10, maximum_packet_size: 10, data: vec![] };
+
+    let mut script = Script::new(vec![
+      SSHPacket::ChannelOpen(open.clone()),
+      SSHPacket::ChannelOpen(ChannelOpen { channel_type: "x11".to_string(), sender_channel: 4, ..open.clone() })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+
+      assert_eq!(multiplexer.take_open(), None);
+
+      let incoming = multiplexer.wait_for_open().unwrap();
+      let id = multiplexer.accept(&incoming, &[]);
+
+      assert_eq!(multiplexer.send_data(id, b"hello"), Ok(()));
+
+      let incoming = multiplexer.wait_for_open().unwrap();
+      multiplexer.reject(&incoming, connection::UNKNOWN_CHANNEL_TYPE, "no X11 here");
+    }
+
+    let outgoing = outgoing(&script);
+
+    assert!(outgoing[0].starts_with("ChannelOpenConfirmation(ChannelOpenConfirmation { recipient_channel: 3, sender_channel: 0"));
+    assert_eq!(outgoing[1], format!("{:?}", SSHPacket::ChannelData(ChannelData { recipient_channel: 3, data: b"hello".to_vec() })));
+    assert!(outgoing[2].starts_with("ChannelOpenFailure(ChannelOpenFailure { recipient_channel: 4, reason_code: 3"));
+  }
+}
@@ -0,0 +1,601 @@
+use std::io::Cursor;
+
+use packets::connection::{ChannelOpen, ChannelRequest, EXTENDED_DATA_STDERR};
+
+use sshio::{SSHRead, SSHWrite};
+
+use connection::multiplexer::{Error, Event, Multiplexer};
+use connection::terminal::{TerminalModes, WindowSize};
+
+use agent::forwarding;
+
+use authentication::authorized_keys::Options;
+
+/// The type of channels for sessions.
+pub static CHANNEL_TYPE: &'static str = "session";
+
+/// How a remote command ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Exit {
+  /// It exited with a status
+  Status(u32),
+  /// It was killed by a signal, named without the "SIG" prefix
+  Signal { signal_name: String, core_dumped: bool, message: String }
+}
+
+/// Output of a remote command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+  Stdout(Vec<u8>),
+  Stderr(Vec<u8>)
+}
+
+/// A session channel, running a remote command.
+///
+/// See [RFC 4254, section 6](https://tools.ietf.org/html/rfc4254#section-6).
+pub struct Session<'m, 'a: 'm> {
+  multiplexer: &'m mut Multiplexer<'a>,
+  id: u32,
+  exit: Option<Exit>,
+  closed: bool
+}
+
+impl<'m, 'a> Session<'m, 'a> {
+  /// Opens a session channel.
+  pub fn open(multiplexer: &'m mut Multiplexer<'a>) -> Result<Session<'m, 'a>, Error> {
+    let id = try!(multiplexer.open(CHANNEL_TYPE, &[]));
+
+    return Ok(Session { multiplexer: multiplexer, id: id, exit: None, closed: false });
+  }
+
+  /// The id of the session's channel.
+  pub fn id(&self) -> u32 {
+    return self.id;
+  }
+
+  /// Sends a request with its encoded `data`, returning whether the server
+  /// did it.
+  fn request(&mut self, request_type: &str, data: &[u8]) -> Result<bool, Error> {
+    return self.multiplexer.request(self.id, request_type, true, data);
+  }
+
+  /// Runs `command`, returning whether the server started it.
+  pub fn exec(&mut self, command: &str) -> Result<bool, Error> {
+    let mut data = Cursor::new(Vec::new());
+    data.write_string(command);
+
+    return self.request("exec", &data.into_inner()[..]);
+  }
+
+  /// Asks the server to forward connections to an agent socket it makes for
+  /// the session, returning whether it will. Each connection arrives as an
+  /// `auth-agent@openssh.com` channel, for `forwarding::AgentChannels`.
+  pub fn request_agent_forwarding(&mut self) -> Result<bool, Error> {
+    return self.request(forwarding::REQUEST, &[]);
+  }
+
+  /// The multiplexer the session's channel runs on, to serve other channels
+  /// alongside it.
+  pub fn multiplexer(&mut self) -> &mut Multiplexer<'a> {
+    return self.multiplexer;
+  }
+
+  /// Asks for a pseudo-terminal for the command or shell, of type `term`,
+  /// like `xterm`, with `size` and `modes`.
+  pub fn request_pty(&mut self, term: &str, size: WindowSize, modes: &TerminalModes) -> Result<bool, Error> {
+    let mut data = Cursor::new(Vec::new());
+
+    data.write_string(term);
+    data.write_uint32(size.columns);
+    data.write_uint32(size.rows);
+    data.write_uint32(size.width);
+    data.write_uint32(size.height);
+    data.write_binary_string(&modes.encode()[..]);
+
+    return self.request("pty-req", &data.into_inner()[..]);
+  }
+
+  /// Starts the user's login shell, returning whether the server did.
+  pub fn shell(&mut self) -> Result<bool, Error> {
+    return self.request("shell", &[]);
+  }
+
+  /// Tells the server the terminal was resized to `size`.
+  pub fn window_change(&mut self, size: WindowSize) -> Result<(), Error> {
+    let mut data = Cursor::new(Vec::new());
+
+    data.write_uint32(size.columns);
+    data.write_uint32(size.rows);
+    data.write_uint32(size.width);
+    data.write_uint32(size.height);
+
+    return self.multiplexer.request(self.id, "window-change", false, &data.into_inner()[..]).map(|_| { () });
+  }
+
+  /// Sets the environment variable `name` to `value` for the command or
+  /// shell started next, returning whether the server accepted it. Servers
+  /// often only accept some names.
+  pub fn set_env(&mut self, name: &str, value: &str) -> Result<bool, Error> {
+    let mut data = Cursor::new(Vec::new());
+
+    data.write_string(name);
+    data.write_string(value);
+
+    return self.request("env", &data.into_inner()[..]);
+  }
+
+  /// Sends the signal `signal_name` to the command, named without the "SIG"
+  /// prefix, like `TERM` or `INT`.
+  ///
+  /// See [RFC 4254, section 6.9](https://tools.ietf.org/html/rfc4254#section-6.9).
+  pub fn signal(&mut self, signal_name: &str) -> Result<(), Error> {
+    let mut data = Cursor::new(Vec::new());
+    data.write_string(signal_name);
+
+    return self.multiplexer.request(self.id, "signal", false, &data.into_inner()[..]).map(|_| { () });
+  }
+
+  /// Sends a break of `length` milliseconds, as on a serial line, returning
+  /// whether the server did.
+  ///
+  /// See [RFC 4335](https://tools.ietf.org/html/rfc4335).
+  pub fn send_break(&mut self, length: u32) -> Result<bool, Error> {
+    let mut data = Cursor::new(Vec::new());
+    data.write_uint32(length);
+
+    return self.request("break", &data.into_inner()[..]);
+  }
+
+  /// Sends `data` to the command's stdin.
+  pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+    return self.multiplexer.send_data(self.id, data);
+  }
+
+  /// Ends the command's stdin.
+  pub fn send_eof(&mut self) -> Result<(), Error> {
+    return self.multiplexer.send_eof(self.id);
+  }
+
+  /// The next output of the command, waiting for some, or `None` once the
+  /// session is closed. Requests the server makes are handled on the way.
+  pub fn read(&mut self) -> Result<Option<Output>, Error> {
+    while !self.closed {
+      if let Some(output) = try!(self.take_output()) {
+        return Ok(Some(output));
+      }
+
+      if !self.closed {
+        try!(self.multiplexer.process());
+      }
+    }
+
+    return Ok(None);
+  }
+
+  /// The next output of the command that has arrived, if any.
+  pub fn take_output(&mut self) -> Result<Option<Output>, Error> {
+    while !self.closed {
+      match try!(self.multiplexer.take_event(self.id)) {
+        Some(Event::Data(data)) => return Ok(Some(Output::Stdout(data))),
+        Some(Event::ExtendedData(EXTENDED_DATA_STDERR, data)) => return Ok(Some(Output::Stderr(data))),
+        Some(Event::ExtendedData(_, _)) | Some(Event::Eof) => {}
+        Some(Event::Request(request)) => self.handle_request(request),
+        Some(Event::Close) => self.closed = true,
+        None => break
+      }
+    }
+
+    return Ok(None);
+  }
+
+  /// Reads a packet that has arrived, for `take_output`.
+  pub fn process(&mut self) -> Result<(), Error> {
+    return self.multiplexer.process();
+  }
+
+  /// Whether the session was closed.
+  pub fn is_closed(&self) -> bool {
+    return self.closed;
+  }
+
+  fn handle_request(&mut self, request: ChannelRequest) {
+    let mut data = Cursor::new(request.data);
+
+    let handled = match &request.request_type[..] {
+      "exit-status" => {
+        self.exit = Some(Exit::Status(data.read_uint32()));
+        true
+      }
+      "exit-signal" => {
+        let signal_name = data.read_string();
+        let core_dumped = data.read_bool();
+        let message = data.read_string();
+
+        self.exit = Some(Exit::Signal { signal_name: signal_name, core_dumped: core_dumped, message: message });
+        true
+      }
+      _ => false
+    };
+
+    if request.want_reply {
+      self.multiplexer.reply(self.id, handled);
+    }
+  }
+
+  /// Reads the rest of the output, until the session is closed, returning
+  /// stdout and stderr.
+  pub fn collect(&mut self) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+
+    while let Some(output) = try!(self.read()) {
+      match output {
+        Output::Stdout(data) => stdout.extend(data),
+        Output::Stderr(data) => stderr.extend(data)
+      }
+    }
+
+    return Ok((stdout, stderr));
+  }
+
+  /// How the command ended, once the server said so.
+  pub fn exit(&self) -> Option<&Exit> {
+    return self.exit.as_ref();
+  }
+
+  /// Closes the session, ending it early if it is still running.
+  pub fn close(&mut self) {
+    self.multiplexer.close(self.id);
+  }
+}
+
+/// Whether the `authorized_keys` options of the key the client logged in
+/// with let it open a channel. Port forwarding, `direct-tcpip` and
+/// `direct-streamlocal@openssh.com`, is refused with `no-port-forwarding`,
+/// or to a destination not in `permitopen` if there are any.
+pub fn allows_open(options: &Options, open: &ChannelOpen) -> bool {
+  return match &open.channel_type[..] {
+    "direct-tcpip" => {
+      let mut data = Cursor::new(&open.data[..]);
+
+      let destination = match (data.try_read_string(), data.try_read_uint32()) {
+        (Ok(host), Ok(port)) => (host, port),
+        _ => return false
+      };
+
+      !options.no_port_forwarding && (options.permit_open.is_empty() || options.permit_open.iter().any(|p| {
+        permits(p, &destination.0, destination.1)
+      }))
+    }
+    "direct-streamlocal@openssh.com" => !options.no_port_forwarding,
+    _ => true
+  };
+}
+
+/// Whether a `permitopen` destination, `host:port`, allows `host` and
+/// `port`. The port may be `*`.
+fn permits(destination: &str, host: &str, port: u32) -> bool {
+  let (allowed_host, allowed_port) = match destination.rfind(':') {
+    Some(i) => (&destination[.. i], &destination[i + 1 ..]),
+    None => return false
+  };
+
+  let allowed_host = allowed_host.trim_left_matches('[').trim_right_matches(']');
+
+  return allowed_host.eq_ignore_ascii_case(host) && (allowed_port == "*" || allowed_port == port.to_string());
+}
+
+/// Applies the `authorized_keys` options of the key the client logged in
+/// with to a request it made on the session channel `id`, returning the
+/// request to carry out, or `None` if it was refused, which is answered.
+///
+/// Terminals, X11 and agent forwarding are refused if the options forbid
+/// them, and with a forced `command` every command, shell or subsystem runs
+/// that command instead. The `environment` is left to the application.
+pub fn restrict(multiplexer: &mut Multiplexer, id: u32, options: &Options, request: ChannelRequest) -> Option<ChannelRequest> {
+  let refused = match &request.request_type[..] {
+    "pty-req" => options.no_pty,
+    "x11-req" => options.no_x11_forwarding,
+    t if t == forwarding::REQUEST => options.no_agent_forwarding,
+    _ => false
+  };
+
+  if refused {
+    if request.want_reply {
+      multiplexer.reply(id, false);
+    }
+
+    return None;
+  }
+
+  return match (&request.request_type[..], &options.command) {
+    ("exec", &Some(ref command)) | ("shell", &Some(ref command)) | ("subsystem", &Some(ref command)) => {
+      let mut data = Cursor::new(Vec::new());
+      data.write_string(command);
+
+      Some(ChannelRequest { request_type: "exec".to_string(), data: data.into_inner(), ..request })
+    }
+    _ => Some(request)
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use packets::SSHPacket;
+  use packets::connection::{self, ChannelOpenConfirmation, ChannelData, ChannelExtendedData, ChannelEof, ChannelClose};
+  use packets::connection::{ChannelOpen, ChannelRequest, ChannelSuccess, ChannelFailure};
+
+  use sshio::SSHWrite;
+
+  use authentication::client::tests::Script;
+
+  use connection::multiplexer::Multiplexer;
+  use connection::terminal::{self, TerminalModes, WindowSize};
+
+  use authentication::authorized_keys::Options;
+
+  use super::{allows_open, restrict, Exit, Output, Session};
+
+  fn confirmation() -> SSHPacket {
+    return SSHPacket::ChannelOpenConfirmation(ChannelOpenConfirmation {
+      recipient_channel: 0,
+      sender_channel: 5,
+      initial_window_size: 1024,
+      maximum_packet_size: 1024,
+      data: vec![]
+    });
+  }
+
+  fn request(request_type: &str, want_reply: bool, data: Vec<u8>) -> SSHPacket {
+    return SSHPacket::ChannelRequest(ChannelRequest {
+      recipient_channel: 0,
+      request_type: request_type.to_string(),
+      want_reply: want_reply,
+      data: data
+    });
+  }
+
+  #[test]
+  fn runs_commands() {
+    let mut exit_status = Cursor::new(Vec::new());
+    exit_status.write_uint32(3);
+
+    let mut script = Script::new(vec![
+      confirmation(),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: b"out".to_vec() }),
+      SSHPacket::ChannelExtendedData(ChannelExtendedData { recipient_channel: 0, data_type_code: connection::EXTENDED_DATA_STDERR, data: b"err".to_vec() }),
+      request("keepalive@openssh.com", true, vec![]),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: b"put".to_vec() }),
+      SSHPacket::ChannelEof(ChannelEof { recipient_channel: 0 }),
+      request("exit-status", false, exit_status.into_inner()),
+      SSHPacket::ChannelClose(ChannelClose { recipient_channel: 0 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let mut session = Session::open(&mut multiplexer).unwrap();
+
+      assert_eq!(session.exec("cat; false"), Ok(true));
+      assert_eq!(session.write(b"in"), Ok(()));
+      assert_eq!(session.send_eof(), Ok(()));
+      assert_eq!(session.read(), Ok(Some(Output::Stdout(b"out".to_vec()))));
+      assert_eq!(session.collect(), Ok((b"put".to_vec(), b"err".to_vec())));
+      assert_eq!(session.exit(), Some(&Exit::Status(3)));
+      assert_eq!(session.read(), Ok(None));
+    }
+
+    match script.outgoing[1] {
+      SSHPacket::ChannelRequest(ref request) => {
+        assert_eq!((request.recipient_channel, &request.request_type[..], request.want_reply), (5, "exec", true));
+        assert_eq!(request.data, b"\x00\x00\x00\x0acat; false".to_vec());
+      }
+      ref p => panic!("Unexpected packet {:?}", p)
+    }
+
+    assert_eq!(format!("{:?}", script.outgoing[4]), format!("{:?}", SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: 5 })));
+  }
+
+  #[test]
+  fn reports_signals() {
+    let mut exit_signal = Cursor::new(Vec::new());
+    exit_signal.write_string("SEGV");
+    exit_signal.write_bool(true);
+    exit_signal.write_string("Segmentation fault");
+    exit_signal.write_string("");
+
+    let mut script = Script::new(vec![
+      confirmation(),
+      SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: 0 }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      request("exit-signal", false, exit_signal.into_inner()),
+      SSHPacket::ChannelClose(ChannelClose { recipient_channel: 0 })
+    ]);
+
+    let mut multiplexer = Multiplexer::new(&mut script);
+    let mut session = Session::open(&mut multiplexer).unwrap();
+
+    assert_eq!(session.exec("missing"), Ok(false));
+    assert_eq!(session.exec("crash"), Ok(true));
+    assert_eq!(session.collect(), Ok((vec![], vec![])));
+    assert_eq!(session.exit(), Some(&Exit::Signal {
+      signal_name: "SEGV".to_string(),
+      core_dumped: true,
+      message: "Segmentation fault".to_string()
+    }));
+  }
+
+  #[test]
+  fn starts_shells_on_terminals() {
+    let mut script = Script::new(vec![
+      confirmation(),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelData(ChannelData { recipient_channel: 0, data: b"$ ".to_vec() }),
+      SSHPacket::ChannelClose(ChannelClose { recipient_channel: 0 })
+    ]);
+
+    let mut modes = TerminalModes::new();
+    modes.set(terminal::ECHO, 1);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let mut session = Session::open(&mut multiplexer).unwrap();
+
+      assert_eq!(session.request_pty("xterm", WindowSize { columns: 80, rows: 24, width: 640, height: 480 }, &modes), Ok(true));
+      assert_eq!(session.shell(), Ok(true));
+      assert_eq!(session.take_output(), Ok(None));
+
+      session.process().unwrap();
+
+      assert_eq!(session.take_output(), Ok(Some(Output::Stdout(b"$ ".to_vec()))));
+      assert_eq!(session.window_change(WindowSize { columns: 100, rows: 30, width: 0, height: 0 }), Ok(()));
+
+      session.process().unwrap();
+
+      assert_eq!(session.take_output(), Ok(None));
+      assert!(session.is_closed());
+    }
+
+    let requests: Vec<(String, bool, Vec<u8>)> = script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::ChannelRequest(ref r) => Some((r.request_type.clone(), r.want_reply, r.data.clone())),
+        _ => None
+      }
+    }).collect();
+
+    assert_eq!(requests, vec![
+      ("pty-req".to_string(), true, vec![
+        0, 0, 0, 5, b'x', b't', b'e', b'r', b'm', 0, 0, 0, 80, 0, 0, 0, 24, 0, 0, 2, 128, 0, 0, 1, 224,
+        0, 0, 0, 6, 53, 0, 0, 0, 1, 0
+      ]),
+      ("shell".to_string(), true, vec![]),
+      ("window-change".to_string(), false, vec![0, 0, 0, 100, 0, 0, 0, 30, 0, 0, 0, 0, 0, 0, 0, 0])
+    ]);
+  }
+
+  #[test]
+  fn requests_agent_forwarding() {
+    let mut script = Script::new(vec![
+      confirmation(),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let mut session = Session::open(&mut multiplexer).unwrap();
+
+      assert_eq!(session.request_agent_forwarding(), Ok(true));
+      assert_eq!(session.exec("job"), Ok(true));
+    }
+
+    let requests: Vec<(String, bool, Vec<u8>)> = script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::ChannelRequest(ref r) => Some((r.request_type.clone(), r.want_reply, r.data.clone())),
+        _ => None
+      }
+    }).collect();
+
+    assert_eq!(requests[0], ("auth-agent-req@openssh.com".to_string(), true, vec![]));
+    assert_eq!(requests[1].0, "exec");
+  }
+
+  #[test]
+  fn sends_environment_signals_and_breaks() {
+    let mut script = Script::new(vec![
+      confirmation(),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: 0 }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 }),
+      SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 0 })
+    ]);
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let mut session = Session::open(&mut multiplexer).unwrap();
+
+      assert_eq!(session.set_env("LANG", "C"), Ok(true));
+      assert_eq!(session.set_env("SECRET", "x"), Ok(false));
+      assert_eq!(session.exec("job"), Ok(true));
+      assert_eq!(session.signal("TERM"), Ok(()));
+      assert_eq!(session.send_break(500), Ok(true));
+    }
+
+    let requests: Vec<(String, bool, Vec<u8>)> = script.outgoing.iter().filter_map(|p| {
+      match p {
+        &SSHPacket::ChannelRequest(ref r) => Some((r.request_type.clone(), r.want_reply, r.data.clone())),
+        _ => None
+      }
+    }).collect();
+
+    assert_eq!(requests[0], ("env".to_string(), true, vec![0, 0, 0, 4, b'L', b'A', b'N', b'G', 0, 0, 0, 1, b'C']));
+    assert_eq!(requests[3], ("signal".to_string(), false, vec![0, 0, 0, 4, b'T', b'E', b'R', b'M']));
+    assert_eq!(requests[4], ("break".to_string(), true, vec![0, 0, 1, 244]));
+  }
+
+  fn direct_tcpip(host: &str, port: u32) -> ChannelOpen {
+    let mut data = Cursor::new(Vec::new());
+
+    data.write_string(host);
+    data.write_uint32(port);
+    data.write_string("127.0.0.1");
+    data.write_uint32(50000);
+
+    return ChannelOpen { channel_type: "direct-tcpip".to_string(), data: data.into_inner(), ..ChannelOpen::default() };
+  }
+
+  #[test]
+  fn restricts_port_forwarding() {
+    let session = ChannelOpen { channel_type: "session".to_string(), ..ChannelOpen::default() };
+
+    assert!(allows_open(&Options::default(), &direct_tcpip("db", 5432)));
+
+    let options = Options { no_port_forwarding: true, ..Options::default() };
+
+    assert!(!allows_open(&options, &direct_tcpip("db", 5432)));
+    assert!(allows_open(&options, &session));
+
+    let options = Options { permit_open: vec!["db:5432".to_string(), "[::1]:*".to_string()], ..Options::default() };
+
+    assert!(allows_open(&options, &direct_tcpip("DB", 5432)));
+    assert!(allows_open(&options, &direct_tcpip("::1", 22)));
+    assert!(!allows_open(&options, &direct_tcpip("db", 22)));
+    assert!(!allows_open(&options, &direct_tcpip("web", 5432)));
+  }
+
+  #[test]
+  fn restricts_session_requests() {
+    let mut script = Script::new(vec![confirmation()]);
+
+    let options = Options { command: Some("uptime".to_string()), no_pty: true, ..Options::default() };
+
+    {
+      let mut multiplexer = Multiplexer::new(&mut script);
+      let id = multiplexer.open("session", &[]).unwrap();
+
+      let pty = ChannelRequest { recipient_channel: id, request_type: "pty-req".to_string(), want_reply: true, data: vec![] };
+      assert_eq!(restrict(&mut multiplexer, id, &options, pty), None);
+
+      let env = ChannelRequest { recipient_channel: id, request_type: "env".to_string(), want_reply: false, data: vec![1] };
+      assert_eq!(restrict(&mut multiplexer, id, &options, env.clone()), Some(env));
+
+      let shell = ChannelRequest { recipient_channel: id, request_type: "shell".to_string(), want_reply: true, data: vec![] };
+      assert_eq!(restrict(&mut multiplexer, id, &options, shell), Some(ChannelRequest {
+        recipient_channel: id,
+        request_type: "exec".to_string(),
+        want_reply: true,
+        data: b"\x00\x00\x00\x06uptime".to_vec()
+      }));
+
+      let agent = ChannelRequest { recipient_channel: id, request_type: "auth-agent-req@openssh.com".to_string(), want_reply: false, data: vec![] };
+      assert_eq!(restrict(&mut multiplexer, id, &options, agent.clone()), Some(agent.clone()));
+      assert_eq!(restrict(&mut multiplexer, id, &Options { no_agent_forwarding: true, ..Options::default() }, agent), None);
+    }
+
+    assert_eq!(format!("{:?}", script.outgoing[1]), format!("{:?}", SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: 5 })));
+    assert_eq!(script.outgoing.len(), 2);
+  }
+}
@@ -0,0 +1,394 @@
+extern crate libc;
+
+use std::io::Cursor;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+#[cfg(test)]
+use quickcheck::{Arbitrary, Gen};
+
+// Opcodes of encoded terminal modes, from RFC 4254, section 8, and IUTF8
+// from RFC 8160. Modes up to 159 have a uint32 argument, later ones stop
+// the parsing.
+pub const TTY_OP_END: u8 = 0;
+pub const VINTR: u8 = 1;
+pub const VQUIT: u8 = 2;
+pub const VERASE: u8 = 3;
+pub const VKILL: u8 = 4;
+pub const VEOF: u8 = 5;
+pub const VEOL: u8 = 6;
+pub const VEOL2: u8 = 7;
+pub const VSTART: u8 = 8;
+pub const VSTOP: u8 = 9;
+pub const VSUSP: u8 = 10;
+pub const VDSUSP: u8 = 11;
+pub const VREPRINT: u8 = 12;
+pub const VWERASE: u8 = 13;
+pub const VLNEXT: u8 = 14;
+pub const VFLUSH: u8 = 15;
+pub const VSWTCH: u8 = 16;
+pub const VSTATUS: u8 = 17;
+pub const VDISCARD: u8 = 18;
+pub const IGNPAR: u8 = 30;
+pub const PARMRK: u8 = 31;
+pub const INPCK: u8 = 32;
+pub const ISTRIP: u8 = 33;
+pub const INLCR: u8 = 34;
+pub const IGNCR: u8 = 35;
+pub const ICRNL: u8 = 36;
+pub const IUCLC: u8 = 37;
+pub const IXON: u8 = 38;
+pub const IXANY: u8 = 39;
+pub const IXOFF: u8 = 40;
+pub const IMAXBEL: u8 = 41;
+pub const IUTF8: u8 = 42;
+pub const ISIG: u8 = 50;
+pub const ICANON: u8 = 51;
+pub const XCASE: u8 = 52;
+pub const ECHO: u8 = 53;
+pub const ECHOE: u8 = 54;
+pub const ECHOK: u8 = 55;
+pub const ECHONL: u8 = 56;
+pub const NOFLSH: u8 = 57;
+pub const TOSTOP: u8 = 58;
+pub const IEXTEN: u8 = 59;
+pub const ECHOCTL: u8 = 60;
+pub const ECHOKE: u8 = 61;
+pub const PENDIN: u8 = 62;
+pub const OPOST: u8 = 70;
+pub const OLCUC: u8 = 71;
+pub const ONLCR: u8 = 72;
+pub const OCRNL: u8 = 73;
+pub const ONOCR: u8 = 74;
+pub const ONLRET: u8 = 75;
+pub const CS7: u8 = 90;
+pub const CS8: u8 = 91;
+pub const PARENB: u8 = 92;
+pub const PARODD: u8 = 93;
+pub const TTY_OP_ISPEED: u8 = 128;
+pub const TTY_OP_OSPEED: u8 = 129;
+
+/// The size of a terminal, in characters and in pixels. Pixels are zero when
+/// unknown.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowSize {
+  pub columns: u32,
+  pub rows: u32,
+  pub width: u32,
+  pub height: u32
+}
+
+/// Terminal modes, as opcodes with their values, for `pty-req`.
+///
+/// See [RFC 4254, section 8](https://tools.ietf.org/html/rfc4254#section-8).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TerminalModes {
+  pub modes: Vec<(u8, u32)>
+}
+
+impl TerminalModes {
+  pub fn new() -> TerminalModes {
+    return TerminalModes { modes: Vec::new() };
+  }
+
+  /// Sets `opcode` to `value`, replacing what it was set to.
+  pub fn set(&mut self, opcode: u8, value: u32) {
+    assert!(opcode != TTY_OP_END && opcode < 160, "Opcode {} has no argument", opcode);
+
+    match self.modes.iter_mut().find(|&&mut (o, _)| { o == opcode }) {
+      Some(mode) => { mode.1 = value; return; }
+      None => {}
+    }
+
+    self.modes.push((opcode, value));
+  }
+
+  pub fn get(&self, opcode: u8) -> Option<u32> {
+    return self.modes.iter().find(|&&(o, _)| { o == opcode }).map(|&(_, v)| { v });
+  }
+
+  /// Encodes the modes, ending with `TTY_OP_END`.
+  pub fn encode(&self) -> Vec<u8> {
+    let mut writer = Cursor::new(Vec::new());
+
+    for &(opcode, value) in self.modes.iter() {
+      writer.write_u8(opcode).unwrap();
+      writer.write_u32::<BigEndian>(value).unwrap();
+    }
+
+    writer.write_u8(TTY_OP_END).unwrap();
+
+    return writer.into_inner();
+  }
+
+  /// Decodes encoded modes, up to `TTY_OP_END`, the end of the data, or an
+  /// opcode whose argument we can't know.
+  pub fn decode(data: &[u8]) -> TerminalModes {
+    let mut reader = Cursor::new(data);
+    let mut modes = TerminalModes::new();
+
+    loop {
+      let opcode = match reader.read_u8() {
+        Ok(opcode) if opcode != TTY_OP_END && opcode < 160 => opcode,
+        _ => break
+      };
+
+      match reader.read_u32::<BigEndian>() {
+        Ok(value) => modes.modes.push((opcode, value)),
+        Err(_) => break
+      }
+    }
+
+    return modes;
+  }
+}
+
+pub use self::local::Terminal;
+
+// The local terminal, through termios. The layout and flags are Linux's, so
+// elsewhere standard input is never treated as a terminal.
+#[cfg(target_os = "linux")]
+mod local {
+  use super::*;
+
+  const NCCS: usize = 32;
+
+  #[repr(C)] #[derive(Clone, Copy)]
+  struct termios {
+    c_iflag: u32,
+    c_oflag: u32,
+    c_cflag: u32,
+    c_lflag: u32,
+    c_line: u8,
+    c_cc: [u8; NCCS],
+    c_ispeed: u32,
+    c_ospeed: u32
+  }
+
+  #[repr(C)] struct winsize { ws_row: u16, ws_col: u16, ws_xpixel: u16, ws_ypixel: u16 }
+
+  extern {
+    fn isatty(fd: libc::c_int) -> libc::c_int;
+    fn tcgetattr(fd: libc::c_int, termios: *mut termios) -> libc::c_int;
+    fn tcsetattr(fd: libc::c_int, optional_actions: libc::c_int, termios: *const termios) -> libc::c_int;
+    fn cfgetispeed(termios: *const termios) -> u32;
+    fn cfgetospeed(termios: *const termios) -> u32;
+    fn ioctl(fd: libc::c_int, request: libc::c_ulong, ...) -> libc::c_int;
+  }
+
+  const TCSADRAIN: libc::c_int = 1;
+  const TIOCGWINSZ: libc::c_ulong = 0x5413;
+
+  /// Control characters, as indexes into `c_cc`.
+  static CONTROL_CHARACTERS: &'static [(u8, usize)] = &[
+    (VINTR, 0), (VQUIT, 1), (VERASE, 2), (VKILL, 3), (VEOF, 4), (VSWTCH, 7),
+    (VSTART, 8), (VSTOP, 9), (VSUSP, 10), (VEOL, 11), (VREPRINT, 12),
+    (VDISCARD, 13), (VWERASE, 14), (VLNEXT, 15), (VEOL2, 16)
+  ];
+
+  static INPUT_FLAGS: &'static [(u8, u32)] = &[
+    (IGNPAR, 0o4), (PARMRK, 0o10), (INPCK, 0o20), (ISTRIP, 0o40), (INLCR, 0o100),
+    (IGNCR, 0o200), (ICRNL, 0o400), (IUCLC, 0o1000), (IXON, 0o2000),
+    (IXANY, 0o4000), (IXOFF, 0o10000), (IMAXBEL, 0o20000), (IUTF8, 0o40000)
+  ];
+
+  static LOCAL_FLAGS: &'static [(u8, u32)] = &[
+    (ISIG, 0o1), (ICANON, 0o2), (XCASE, 0o4), (ECHO, 0o10), (ECHOE, 0o20),
+    (ECHOK, 0o40), (ECHONL, 0o100), (NOFLSH, 0o200), (TOSTOP, 0o400),
+    (ECHOCTL, 0o1000), (ECHOKE, 0o4000), (PENDIN, 0o40000), (IEXTEN, 0o100000)
+  ];
+
+  static OUTPUT_FLAGS: &'static [(u8, u32)] = &[
+    (OPOST, 0o1), (OLCUC, 0o2), (ONLCR, 0o4), (OCRNL, 0o10), (ONOCR, 0o20),
+    (ONLRET, 0o40)
+  ];
+
+  static CONTROL_FLAGS: &'static [(u8, u32)] = &[
+    (PARENB, 0o400), (PARODD, 0o1000)
+  ];
+
+  const CSIZE: u32 = 0o60;
+
+  /// Speeds, as `B` constants.
+  static SPEEDS: &'static [(u32, u32)] = &[
+    (0o1, 50), (0o2, 75), (0o3, 110), (0o4, 134), (0o5, 150), (0o6, 200),
+    (0o7, 300), (0o10, 600), (0o11, 1200), (0o12, 1800), (0o13, 2400),
+    (0o14, 4800), (0o15, 9600), (0o16, 19200), (0o17, 38400), (0o10001, 57600),
+    (0o10002, 115200), (0o10003, 230400)
+  ];
+
+  /// Indexes of `c_cc` that only matter in raw mode.
+  const VTIME_INDEX: usize = 5;
+  const VMIN_INDEX: usize = 6;
+
+  /// The flags in `table` for `opcodes`.
+  fn flags(table: &[(u8, u32)], opcodes: &[u8]) -> u32 {
+    return table.iter().filter(|&&(o, _)| { opcodes.contains(&o) }).fold(0, |flags, &(_, f)| { flags | f });
+  }
+
+  fn speed(constant: u32) -> u32 {
+    return SPEEDS.iter().find(|&&(c, _)| { c == constant }).map(|&(_, s)| { s }).unwrap_or(38400);
+  }
+
+  /// The terminal standard input is attached to, which can be put in raw mode
+  /// while a session uses it. Its mode is restored when dropped.
+  pub struct Terminal {
+    saved: termios
+  }
+
+  impl Terminal {
+    /// The terminal on standard input, if it is one.
+    pub fn stdin() -> Option<Terminal> {
+      let mut saved = termios { c_iflag: 0, c_oflag: 0, c_cflag: 0, c_lflag: 0, c_line: 0, c_cc: [0; NCCS], c_ispeed: 0, c_ospeed: 0 };
+
+      if unsafe { isatty(0) } != 1 || unsafe { tcgetattr(0, &mut saved) } != 0 {
+        return None;
+      }
+
+      return Some(Terminal { saved: saved });
+    }
+
+    /// The modes the terminal had, to give the remote one.
+    pub fn modes(&self) -> TerminalModes {
+      let t = &self.saved;
+      let mut modes = TerminalModes::new();
+
+      modes.set(TTY_OP_ISPEED, speed(unsafe { cfgetispeed(t) }));
+      modes.set(TTY_OP_OSPEED, speed(unsafe { cfgetospeed(t) }));
+
+      for &(opcode, index) in CONTROL_CHARACTERS.iter() {
+        modes.set(opcode, t.c_cc[index] as u32);
+      }
+
+      for &(flags, table) in [(t.c_iflag, INPUT_FLAGS), (t.c_lflag, LOCAL_FLAGS), (t.c_oflag, OUTPUT_FLAGS), (t.c_cflag, CONTROL_FLAGS)].iter() {
+        for &(opcode, flag) in table.iter() {
+          modes.set(opcode, if flags & flag != 0 { 1 } else { 0 });
+        }
+      }
+
+      modes.set(CS7, if t.c_cflag & CSIZE == 0o40 { 1 } else { 0 });
+      modes.set(CS8, if t.c_cflag & CSIZE == 0o60 { 1 } else { 0 });
+
+      return modes;
+    }
+
+    /// The current size of the terminal.
+    pub fn size(&self) -> WindowSize {
+      let mut size = winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+
+      if unsafe { ioctl(0, TIOCGWINSZ, &mut size as *mut winsize) } != 0 {
+        return WindowSize { columns: 80, rows: 24, width: 0, height: 0 };
+      }
+
+      return WindowSize {
+        columns: size.ws_col as u32,
+        rows: size.ws_row as u32,
+        width: size.ws_xpixel as u32,
+        height: size.ws_ypixel as u32
+      };
+    }
+
+    /// Passes keys through as they are typed, without echo or line editing,
+    /// for the remote terminal to handle, like OpenSSH does.
+    pub fn make_raw(&self) {
+      let mut raw = self.saved;
+
+      raw.c_iflag |= flags(INPUT_FLAGS, &[IGNPAR]);
+      raw.c_iflag &= !flags(INPUT_FLAGS, &[ISTRIP, INLCR, IGNCR, ICRNL, IUCLC, IXON, IXANY, IXOFF]);
+      raw.c_lflag &= !flags(LOCAL_FLAGS, &[ISIG, ICANON, ECHO, ECHOE, ECHOK, ECHONL, IEXTEN]);
+      raw.c_oflag &= !flags(OUTPUT_FLAGS, &[OPOST]);
+      raw.c_cc[VMIN_INDEX] = 1;
+      raw.c_cc[VTIME_INDEX] = 0;
+
+      unsafe { tcsetattr(0, TCSADRAIN, &raw) };
+    }
+
+    /// Turns off echo, for reading passwords.
+    pub fn hide_input(&self) {
+      let mut hidden = self.saved;
+
+      hidden.c_lflag &= !flags(LOCAL_FLAGS, &[ECHO]);
+
+      unsafe { tcsetattr(0, TCSADRAIN, &hidden) };
+    }
+
+    /// Puts the terminal back in the mode it had.
+    pub fn restore(&self) {
+      unsafe { tcsetattr(0, TCSADRAIN, &self.saved) };
+    }
+  }
+
+  impl Drop for Terminal {
+    fn drop(&mut self) {
+      self.restore();
+    }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod local {
+  use super::{TerminalModes, WindowSize};
+
+  pub struct Terminal {
+    _private: ()
+  }
+
+  impl Terminal {
+    pub fn stdin() -> Option<Terminal> {
+      return None;
+    }
+
+    pub fn modes(&self) -> TerminalModes {
+      return TerminalModes::new();
+    }
+
+    pub fn size(&self) -> WindowSize {
+      return WindowSize { columns: 80, rows: 24, width: 0, height: 0 };
+    }
+
+    pub fn make_raw(&self) {}
+
+    pub fn hide_input(&self) {}
+
+    pub fn restore(&self) {}
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for TerminalModes {
+  fn arbitrary<G: Gen>(g: &mut G) -> TerminalModes {
+    let modes: Vec<(u8, u32)> = Arbitrary::arbitrary(g);
+
+    return TerminalModes { modes: modes.into_iter().map(|(o, v)| { (o % 159 + 1, v) }).collect() };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{TerminalModes, ECHO, ICANON, TTY_OP_ISPEED, VINTR};
+
+  #[quickcheck]
+  fn roundtrips_terminal_modes(modes: TerminalModes) -> bool {
+    return TerminalModes::decode(&modes.encode()[..]) == modes;
+  }
+
+  #[test]
+  fn encodes_terminal_modes() {
+    let mut modes = TerminalModes::new();
+
+    modes.set(VINTR, 3);
+    modes.set(ECHO, 0);
+    modes.set(TTY_OP_ISPEED, 38400);
+    modes.set(ECHO, 1);
+
+    assert_eq!(modes.get(ECHO), Some(1));
+    assert_eq!(modes.get(ICANON), None);
+    assert_eq!(modes.encode(), vec![1, 0, 0, 0, 3, 53, 0, 0, 0, 1, 128, 0, 0, 0x96, 0, 0]);
+  }
+
+  #[test]
+  fn stops_decoding_at_unknown_opcodes() {
+    assert_eq!(TerminalModes::decode(&[53, 0, 0, 0, 1, 160, 1, 2, 3, 4, 54, 0, 0, 0, 1]).modes, vec![(ECHO, 1)]);
+    assert_eq!(TerminalModes::decode(&[53, 0, 0]).modes, vec![]);
+  }
+}
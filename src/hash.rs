@@ -456,3 +456,60 @@ fn calculates_foxy_sha512_hash() {
   }
 }
 
+/// HMAC-SHA1 of `data` with `key`.
+///
+/// See [RFC 2104](https://tools.ietf.org/html/rfc2104).
+pub fn hmac_sha1(key: &[u8], data: &[u8]) -> Vec<u8> {
+  let mut block = [0u8; 64];
+
+  if key.len() > block.len() {
+    let mut hash = SHA1::new();
+    hash.update(key);
+
+    let digest = hash.digest();
+    block[.. digest.len()].copy_from_slice(&digest[..]);
+  } else {
+    block[.. key.len()].copy_from_slice(key);
+  }
+
+  let inner_pad: Vec<u8> = block.iter().map(|b| { b ^ 0x36 }).collect();
+  let outer_pad: Vec<u8> = block.iter().map(|b| { b ^ 0x5c }).collect();
+
+  let mut inner = SHA1::new();
+  inner.update(&inner_pad[..]);
+  inner.update(data);
+
+  let mut outer = SHA1::new();
+  outer.update(&outer_pad[..]);
+  outer.update(&inner.digest()[..]);
+
+  return outer.digest();
+}
+
+#[test]
+fn calculates_hmac_sha1() {
+  // RFC 2202, test case 2
+  assert_eq!(hmac_sha1(b"Jefe", b"what do ya want for nothing?"), vec![
+    0xef, 0xfc, 0xdf, 0x6a, 0xe5, 0xeb, 0x2f, 0xa2, 0xd2, 0x74,
+    0x16, 0xd5, 0xf1, 0x84, 0xdf, 0x9c, 0x25, 0x9a, 0x7c, 0x79
+  ]);
+}
+
+#[link(name = "crypto")]
+extern {
+  fn CRYPTO_memcmp(a: *const u8, b: *const u8, length: libc::size_t) -> libc::c_int;
+}
+
+/// Whether `a` and `b` are equal, taking time that only depends on their
+/// lengths, for comparing MACs and other secrets.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  return a.len() == b.len() && unsafe { CRYPTO_memcmp(a.as_ptr(), b.as_ptr(), a.len() as libc::size_t) } == 0;
+}
+
+#[test]
+fn compares_in_constant_time() {
+  assert!(constant_time_eq(b"", b""));
+  assert!(constant_time_eq(b"secret", b"secret"));
+  assert!(!constant_time_eq(b"secret", b"secreT"));
+  assert!(!constant_time_eq(b"secret", b"secrets"));
+}
@@ -0,0 +1,167 @@
+use num::bigint::{BigInt, Sign};
+
+use keys::Error;
+
+pub const INTEGER: u8 = 0x02;
+pub const BIT_STRING: u8 = 0x03;
+pub const OCTET_STRING: u8 = 0x04;
+pub const NULL: u8 = 0x05;
+pub const OBJECT_IDENTIFIER: u8 = 0x06;
+pub const SEQUENCE: u8 = 0x30;
+
+/// The tag of a constructed, context-specific value, like `[0]`.
+pub fn context(n: u8) -> u8 {
+  return 0xA0 | n;
+}
+
+/// Reads DER encoded ASN.1, just enough of it for private key files.
+///
+/// See [X.690](https://www.itu.int/rec/T-REC-X.690).
+pub struct Reader<'a> {
+  data: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+  pub fn new(data: &'a [u8]) -> Reader<'a> {
+    return Reader { data: data };
+  }
+
+  pub fn is_empty(&self) -> bool {
+    return self.data.is_empty();
+  }
+
+  /// The tag of the next value, if any.
+  pub fn peek(&self) -> Option<u8> {
+    return self.data.first().cloned();
+  }
+
+  /// Reads the next value, returning its tag and contents.
+  pub fn read(&mut self) -> Result<(u8, &'a [u8]), Error> {
+    if self.data.len() < 2 {
+      return Err(Error::InvalidFormat);
+    }
+
+    let tag = self.data[0];
+
+    let (length, offset) = if self.data[1] & 0x80 == 0 {
+      (self.data[1] as usize, 2)
+    } else {
+      let n = (self.data[1] & 0x7F) as usize;
+
+      if n == 0 || n > 4 || self.data.len() < 2 + n {
+        return Err(Error::InvalidFormat);
+      }
+
+      (self.data[2 .. 2 + n].iter().fold(0, |l, &b| { l << 8 | b as usize }), 2 + n)
+    };
+
+    if self.data.len() < offset + length {
+      return Err(Error::InvalidFormat);
+    }
+
+    let contents = &self.data[offset .. offset + length];
+    self.data = &self.data[offset + length ..];
+
+    return Ok((tag, contents));
+  }
+
+  /// Reads the next value, which must have the tag `expected`.
+  pub fn expect(&mut self, expected: u8) -> Result<&'a [u8], Error> {
+    let (tag, contents) = try!(self.read());
+
+    if tag != expected {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(contents);
+  }
+
+  pub fn sequence(&mut self) -> Result<Reader<'a>, Error> {
+    return self.expect(SEQUENCE).map(Reader::new);
+  }
+
+  pub fn integer(&mut self) -> Result<BigInt, Error> {
+    let contents = try!(self.expect(INTEGER));
+
+    if contents.is_empty() {
+      return Err(Error::InvalidFormat);
+    }
+
+    if contents[0] & 0x80 != 0 {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(BigInt::from_bytes_be(Sign::Plus, contents));
+  }
+
+  pub fn octet_string(&mut self) -> Result<&'a [u8], Error> {
+    return self.expect(OCTET_STRING);
+  }
+
+  /// Reads a bit string, which must be a whole number of bytes.
+  pub fn bit_string(&mut self) -> Result<&'a [u8], Error> {
+    let contents = try!(self.expect(BIT_STRING));
+
+    if contents.first() != Some(&0) {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(&contents[1 ..]);
+  }
+
+  /// Reads an object identifier in dotted form, like `1.2.840.113549.1.1.1`.
+  pub fn object_identifier(&mut self) -> Result<String, Error> {
+    let contents = try!(self.expect(OBJECT_IDENTIFIER));
+
+    if contents.is_empty() {
+      return Err(Error::InvalidFormat);
+    }
+
+    let mut components = vec![(contents[0] / 40) as u64, (contents[0] % 40) as u64];
+    let mut value = 0u64;
+
+    for &b in contents[1 ..].iter() {
+      value = value << 7 | (b & 0x7F) as u64;
+
+      if b & 0x80 == 0 {
+        components.push(value);
+        value = 0;
+      }
+    }
+
+    return Ok(components.iter().map(|c| { c.to_string() }).collect::<Vec<String>>().join("."));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use num::bigint::ToBigInt;
+
+  use super::{Reader, context};
+
+  #[test]
+  fn reads_nested_values() {
+    let data = [
+      0x30, 0x81, 0x0F,
+        0x02, 0x02, 0x00, 0x80,
+        0x06, 0x06, 0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D,
+        0xA0, 0x01, 0x05
+    ];
+
+    let mut reader = Reader::new(&data);
+    let mut sequence = reader.sequence().unwrap();
+
+    assert!(reader.is_empty());
+    assert_eq!(sequence.integer().unwrap(), 128.to_bigint().unwrap());
+    assert_eq!(sequence.object_identifier().unwrap(), "1.2.840.113549");
+    assert_eq!(sequence.peek(), Some(context(0)));
+    assert_eq!(sequence.expect(context(0)).unwrap(), &[0x05]);
+    assert!(sequence.is_empty());
+  }
+
+  #[test]
+  fn fails_on_truncated_values() {
+    assert!(Reader::new(&[0x30, 0x05, 0x02, 0x01]).read().is_err());
+    assert!(Reader::new(&[0x02, 0x01, 0x01]).sequence().is_err());
+  }
+}
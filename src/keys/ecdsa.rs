@@ -0,0 +1,578 @@
+extern crate libc;
+
+use std::io::{self, Read, Write};
+use std::ptr;
+
+use num::{Zero, One, Num, Integer};
+use num::bigint::{BigInt, RandBigInt, ToBigInt};
+
+use bignum;
+use bignum::{BN_clear_free, BIGNUM};
+
+use hash::{Hash, SHA256, SHA384, SHA512};
+
+use sshio::{SSHRead, SSHWrite};
+
+use keys::Error;
+
+#[repr(C)] struct EC_KEY { _private: [u8; 0] }
+#[repr(C)] struct EC_GROUP { _private: [u8; 0] }
+#[repr(C)] struct EC_POINT { _private: [u8; 0] }
+#[repr(C)] struct ECDSA_SIG { _private: [u8; 0] }
+
+const POINT_CONVERSION_UNCOMPRESSED: libc::c_int = 4;
+
+#[link(name = "crypto")]
+extern {
+  fn EC_KEY_new_by_curve_name(nid: libc::c_int) -> *mut EC_KEY;
+  fn EC_KEY_free(key: *mut EC_KEY);
+  fn EC_KEY_get0_group(key: *const EC_KEY) -> *const EC_GROUP;
+  fn EC_KEY_set_private_key(key: *mut EC_KEY, scalar: *const BIGNUM) -> libc::c_int;
+  fn EC_KEY_set_public_key(key: *mut EC_KEY, point: *const EC_POINT) -> libc::c_int;
+  fn EC_POINT_new(group: *const EC_GROUP) -> *mut EC_POINT;
+  fn EC_POINT_free(point: *mut EC_POINT);
+  fn EC_POINT_mul(group: *const EC_GROUP, result: *mut EC_POINT, scalar: *const BIGNUM, point: *const EC_POINT, point_scalar: *const BIGNUM, context: *mut libc::c_void) -> libc::c_int;
+  fn EC_POINT_point2oct(group: *const EC_GROUP, point: *const EC_POINT, form: libc::c_int, data: *mut u8, length: libc::size_t, context: *mut libc::c_void) -> libc::size_t;
+  fn ECDSA_do_sign(digest: *const u8, digest_length: libc::c_int, key: *mut EC_KEY) -> *mut ECDSA_SIG;
+  fn ECDSA_SIG_get0(signature: *const ECDSA_SIG, r: *mut *const BIGNUM, s: *mut *const BIGNUM);
+  fn ECDSA_SIG_free(signature: *mut ECDSA_SIG);
+}
+
+/// A private key in libcrypto, whose scalar multiplications and signatures
+/// take constant time, freed when dropped.
+struct EcKey {
+  key: *mut EC_KEY,
+  /// The public point, uncompressed
+  point: Vec<u8>
+}
+
+impl EcKey {
+  fn new(curve: Curve, scalar: &BigInt) -> EcKey {
+    let key = unsafe { EC_KEY_new_by_curve_name(curve.nid()) };
+
+    if key.is_null() {
+      panic!("Failed to make {} key", curve.name());
+    }
+
+    let mut point = vec![0; 1 + 2 * curve.field_size()];
+
+    let loaded = unsafe {
+      let group = EC_KEY_get0_group(key);
+      let public = EC_POINT_new(group);
+      let scalar = bignum::to_bn(scalar);
+
+      let loaded = !public.is_null() &&
+        EC_KEY_set_private_key(key, scalar) == 1 &&
+        EC_POINT_mul(group, public, scalar, ptr::null(), ptr::null(), ptr::null_mut()) == 1 &&
+        EC_KEY_set_public_key(key, public) == 1 &&
+        EC_POINT_point2oct(group, public, POINT_CONVERSION_UNCOMPRESSED, point.as_mut_ptr(), point.len() as libc::size_t, ptr::null_mut()) == point.len() as libc::size_t;
+
+      BN_clear_free(scalar);
+      EC_POINT_free(public);
+
+      loaded
+    };
+
+    let key = EcKey { key: key, point: point };
+
+    if !loaded {
+      panic!("Failed to load {} key", curve.name());
+    }
+
+    return key;
+  }
+}
+
+impl Drop for EcKey {
+  fn drop(&mut self) {
+    unsafe { EC_KEY_free(self.key) };
+  }
+}
+
+/// The NIST curves used with ECDSA.
+///
+/// See [RFC 5656](https://tools.ietf.org/html/rfc5656).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+  NistP256,
+  NistP384,
+  NistP521
+}
+
+/// Domain parameters of a short Weierstrass curve, `y^2 = x^3 - 3x + b`.
+struct Parameters {
+  p: BigInt,
+  b: BigInt,
+  n: BigInt,
+  gx: BigInt,
+  gy: BigInt
+}
+
+impl Curve {
+  /// The curve identifier, like `nistp256`.
+  pub fn name(&self) -> &'static str {
+    return match self {
+      &Curve::NistP256 => "nistp256",
+      &Curve::NistP384 => "nistp384",
+      &Curve::NistP521 => "nistp521"
+    };
+  }
+
+  /// The key and signature algorithm, like `ecdsa-sha2-nistp256`.
+  pub fn algorithm(&self) -> &'static str {
+    return match self {
+      &Curve::NistP256 => "ecdsa-sha2-nistp256",
+      &Curve::NistP384 => "ecdsa-sha2-nistp384",
+      &Curve::NistP521 => "ecdsa-sha2-nistp521"
+    };
+  }
+
+  pub fn from_name(name: &str) -> Option<Curve> {
+    return match name {
+      "nistp256" => Some(Curve::NistP256),
+      "nistp384" => Some(Curve::NistP384),
+      "nistp521" => Some(Curve::NistP521),
+      _ => None
+    };
+  }
+
+  pub fn from_algorithm(algorithm: &str) -> Option<Curve> {
+    return match algorithm {
+      "ecdsa-sha2-nistp256" => Some(Curve::NistP256),
+      "ecdsa-sha2-nistp384" => Some(Curve::NistP384),
+      "ecdsa-sha2-nistp521" => Some(Curve::NistP521),
+      _ => None
+    };
+  }
+
+  /// Size of the curve in bits.
+  pub fn bits(&self) -> usize {
+    return match self {
+      &Curve::NistP256 => 256,
+      &Curve::NistP384 => 384,
+      &Curve::NistP521 => 521
+    };
+  }
+
+  /// The curve's libcrypto NID.
+  fn nid(&self) -> libc::c_int {
+    return match self {
+      &Curve::NistP256 => 415,
+      &Curve::NistP384 => 715,
+      &Curve::NistP521 => 716
+    };
+  }
+
+  /// Size of a field element in bytes.
+  fn field_size(&self) -> usize {
+    return (self.bits() + 7) / 8;
+  }
+
+  fn digest(&self, data: &[u8]) -> Vec<u8> {
+    let mut hash: Box<Hash> = match self {
+      &Curve::NistP256 => Box::new(SHA256::new()),
+      &Curve::NistP384 => Box::new(SHA384::new()),
+      &Curve::NistP521 => Box::new(SHA512::new())
+    };
+
+    hash.update(data);
+    return hash.digest();
+  }
+
+  fn parameters(&self) -> Parameters {
+    let (p, b, n, gx, gy) = match self {
+      &Curve::NistP256 => (
+        "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF",
+        "5AC635D8AA3A93E7B3EBBD55769886BC651D06B0CC53B0F63BCE3C3E27D2604B",
+        "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551",
+        "6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296",
+        "4FE342E2FE1A7F9B8EE7EB4A7C0F9E162BCE33576B315ECECBB6406837BF51F5"
+      ),
+      &Curve::NistP384 => (
+        "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFFFF0000000000000000FFFFFFFF",
+        "B3312FA7E23EE7E4988E056BE3F82D19181D9C6EFE8141120314088F5013875AC656398D8A2ED19D2A85C8EDD3EC2AEF",
+        "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFC7634D81F4372DDF581A0DB248B0A77AECEC196ACCC52973",
+        "AA87CA22BE8B05378EB1C71EF320AD746E1D3B628BA79B9859F741E082542A385502F25DBF55296C3A545E3872760AB7",
+        "3617DE4A96262C6F5D9E98BF9292DC29F8F41DBD289A147CE9DA3113B5F0B8C00A60B1CE1D7E819D7A431D7C90EA0E5F"
+      ),
+      &Curve::NistP521 => (
+        "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+        "0051953EB9618E1C9A1F929A21A0B68540EEA2DA725B99B315F3B8B489918EF109E156193951EC7E937B1652C0BD3BB1BF073573DF883D2C34F1EF451FD46B503F00",
+        "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFA51868783BF2F966B7FCC0148F709A5D03BB5C9B8899C47AEBB6FB71E91386409",
+        "00C6858E06B70404E9CD9E3ECB662395B4429C648139053FB521F828AF606B4D3DBAA14B5E77EFE75928FE1DC127A2FFA8DE3348B3C1856A429BF97E7E31C2E5BD66",
+        "011839296A789A3BC0045C8A5FB42C7D1BD998F54449579B446817AFBD17273E662C97EE72995EF42640C550B9013FAD0761353C7086A272C24088BE94769FD16650"
+      )
+    };
+
+    return Parameters {
+      p: BigInt::from_str_radix(p, 16).unwrap(),
+      b: BigInt::from_str_radix(b, 16).unwrap(),
+      n: BigInt::from_str_radix(n, 16).unwrap(),
+      gx: BigInt::from_str_radix(gx, 16).unwrap(),
+      gy: BigInt::from_str_radix(gy, 16).unwrap()
+    };
+  }
+}
+
+/// An ECDSA public key, `ecdsa-sha2-*`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+  pub curve: Curve,
+  /// The public point, uncompressed, `0x04 || x || y`
+  pub point: Vec<u8>
+}
+
+impl PublicKey {
+  /// Reads the key fields, following the `ecdsa-sha2-*` name, of a public key
+  /// blob.
+  pub fn read(reader: &mut Read) -> Result<PublicKey, Error> {
+    let mut reader = reader;
+
+    let curve = try!(reader.try_read_string());
+
+    let curve = match Curve::from_name(curve.as_str()) {
+      Some(curve) => curve,
+      None => return Err(Error::UnsupportedKeyType(curve))
+    };
+
+    return Ok(PublicKey { curve: curve, point: try!(reader.try_read_binary_string()) });
+  }
+
+  /// Writes the key fields, following the `ecdsa-sha2-*` name, of a public
+  /// key blob.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.curve.name());
+    writer.write_binary_string(&self.point[..]);
+  }
+
+  /// Verifies an encoded `signature`, `mpint r, mpint s`, of `data`.
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+    let parameters = self.curve.parameters();
+
+    let q = match decode_point(&parameters, self.curve.field_size(), &self.point[..]) {
+      Some(q) => q,
+      None => return false
+    };
+
+    let mut reader = io::Cursor::new(signature);
+
+    let (r, s) = match (reader.try_read_mpint(), reader.try_read_mpint()) {
+      (Ok(r), Ok(s)) => (r, s),
+      _ => return false
+    };
+
+    let n = &parameters.n;
+
+    if r <= Zero::zero() || &r >= n || s <= Zero::zero() || &s >= n {
+      return false;
+    }
+
+    let e = truncate(&self.curve.digest(data)[..], n);
+    let w = bignum::mod_inverse(&s, n);
+
+    let u1 = bignum::modulo(&(&e * &w), n);
+    let u2 = bignum::modulo(&(&r * &w), n);
+
+    let g = Point::affine(parameters.gx.clone(), parameters.gy.clone());
+
+    let x = multiply(&parameters, &g, &u1).add(&parameters, &multiply(&parameters, &q, &u2));
+
+    return match x.to_affine(&parameters) {
+      Some((x, _)) => bignum::modulo(&x, n) == r,
+      None => false
+    };
+  }
+}
+
+/// An ECDSA private key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivateKey {
+  pub curve: Curve,
+  /// The public point, uncompressed
+  pub point: Vec<u8>,
+  /// The private scalar
+  pub scalar: BigInt
+}
+
+impl PrivateKey {
+  /// Reads the key fields of an `ecdsa-sha2-*` key in an OpenSSH private key
+  /// file, the curve, public point and private scalar.
+  pub fn read(reader: &mut Read) -> Result<PrivateKey, Error> {
+    let mut reader = reader;
+
+    let public = try!(PublicKey::read(reader));
+    let scalar = try!(reader.try_read_mpint());
+
+    if scalar <= Zero::zero() || scalar >= public.curve.parameters().n || EcKey::new(public.curve, &scalar).point != public.point {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(PrivateKey { curve: public.curve, point: public.point, scalar: scalar });
+  }
+
+  /// Writes the key fields of an `ecdsa-sha2-*` key in an OpenSSH private key
+  /// file.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    self.public_key().write(writer);
+    writer.write_mpint(&self.scalar);
+  }
+
+  /// Constructs a private key from its scalar, computing the public point.
+  pub fn from_scalar(curve: Curve, scalar: BigInt) -> PrivateKey {
+    let point = EcKey::new(curve, &scalar).point.clone();
+
+    return PrivateKey { curve: curve, point: point, scalar: scalar };
+  }
+
+  /// Generates a key on `curve`.
+  pub fn generate(curve: Curve) -> PrivateKey {
+    let n = curve.parameters().n;
+
+    let scalar = bignum::os_rng().gen_bigint_range(&One::one(), &n);
+
+    return PrivateKey::from_scalar(curve, scalar);
+  }
+
+  pub fn public_key(&self) -> PublicKey {
+    return PublicKey { curve: self.curve, point: self.point.clone() };
+  }
+
+  /// Signs `data` with libcrypto, returning the encoded signature,
+  /// `mpint r, mpint s`.
+  pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+    let key = EcKey::new(self.curve, &self.scalar);
+    let digest = self.curve.digest(data);
+
+    let (r, s) = unsafe {
+      let signature = ECDSA_do_sign(digest.as_ptr(), digest.len() as libc::c_int, key.key);
+
+      if signature.is_null() {
+        panic!("Failed to sign with {} key", self.curve.name());
+      }
+
+      let (mut r, mut s) = (ptr::null(), ptr::null());
+      ECDSA_SIG_get0(signature, &mut r, &mut s);
+
+      let rs = (bignum::from_bn(r), bignum::from_bn(s));
+
+      ECDSA_SIG_free(signature);
+
+      rs
+    };
+
+    let mut writer = io::Cursor::new(Vec::new());
+
+    writer.write_mpint(&r);
+    writer.write_mpint(&s);
+
+    return writer.into_inner();
+  }
+}
+
+/// Converts a digest to an integer, keeping only as many leftmost bits as the
+/// group order has.
+fn truncate(digest: &[u8], n: &BigInt) -> BigInt {
+  let e = bignum::from_bytes_be(digest);
+  let bits = digest.len() * 8;
+
+  if bits > n.bits() {
+    return e >> (bits - n.bits());
+  } else {
+    return e;
+  }
+}
+
+/// A point in Jacobian coordinates, `(X / Z^2, Y / Z^3)`, where `Z = 0` is
+/// the point at infinity.
+#[derive(Clone, Debug)]
+struct Point {
+  x: BigInt,
+  y: BigInt,
+  z: BigInt
+}
+
+impl Point {
+  fn affine(x: BigInt, y: BigInt) -> Point {
+    return Point { x: x, y: y, z: One::one() };
+  }
+
+  fn infinity() -> Point {
+    return Point { x: One::one(), y: One::one(), z: Zero::zero() };
+  }
+
+  fn is_infinity(&self) -> bool {
+    return self.z.is_zero();
+  }
+
+  fn to_affine(&self, c: &Parameters) -> Option<(BigInt, BigInt)> {
+    if self.is_infinity() {
+      return None;
+    }
+
+    let z_inv = bignum::mod_inverse(&self.z, &c.p);
+    let z_inv2 = bignum::modulo(&(&z_inv * &z_inv), &c.p);
+    let z_inv3 = bignum::modulo(&(&z_inv2 * &z_inv), &c.p);
+
+    return Some((bignum::modulo(&(&self.x * &z_inv2), &c.p), bignum::modulo(&(&self.y * &z_inv3), &c.p)));
+  }
+
+  fn double(&self, c: &Parameters) -> Point {
+    if self.is_infinity() || self.y.is_zero() {
+      return Point::infinity();
+    }
+
+    let p = &c.p;
+
+    let y2 = bignum::modulo(&(&self.y * &self.y), p);
+    let z2 = bignum::modulo(&(&self.z * &self.z), p);
+
+    let s = bignum::modulo(&(4.to_bigint().unwrap() * &self.x * &y2), p);
+    // a = -3, so 3 X^2 + a Z^4 = 3 (X - Z^2) (X + Z^2)
+    let m = bignum::modulo(&(3.to_bigint().unwrap() * (&self.x - &z2) * (&self.x + &z2)), p);
+
+    let x = bignum::modulo(&(&m * &m - 2.to_bigint().unwrap() * &s), p);
+    let y = bignum::modulo(&(&m * (&s - &x) - 8.to_bigint().unwrap() * &y2 * &y2), p);
+    let z = bignum::modulo(&(2.to_bigint().unwrap() * &self.y * &self.z), p);
+
+    return Point { x: x, y: y, z: z };
+  }
+
+  fn add(&self, c: &Parameters, other: &Point) -> Point {
+    if self.is_infinity() {
+      return other.clone();
+    }
+
+    if other.is_infinity() {
+      return self.clone();
+    }
+
+    let p = &c.p;
+
+    let z1z1 = bignum::modulo(&(&self.z * &self.z), p);
+    let z2z2 = bignum::modulo(&(&other.z * &other.z), p);
+
+    let u1 = bignum::modulo(&(&self.x * &z2z2), p);
+    let u2 = bignum::modulo(&(&other.x * &z1z1), p);
+    let s1 = bignum::modulo(&(&self.y * &other.z * &z2z2), p);
+    let s2 = bignum::modulo(&(&other.y * &self.z * &z1z1), p);
+
+    if u1 == u2 {
+      if s1 == s2 {
+        return self.double(c);
+      } else {
+        return Point::infinity();
+      }
+    }
+
+    let h = bignum::modulo(&(&u2 - &u1), p);
+    let r = bignum::modulo(&(&s2 - &s1), p);
+
+    let h2 = bignum::modulo(&(&h * &h), p);
+    let h3 = bignum::modulo(&(&h2 * &h), p);
+    let u1h2 = bignum::modulo(&(&u1 * &h2), p);
+
+    let x = bignum::modulo(&(&r * &r - &h3 - 2.to_bigint().unwrap() * &u1h2), p);
+    let y = bignum::modulo(&(&r * (&u1h2 - &x) - &s1 * &h3), p);
+    let z = bignum::modulo(&(&h * &self.z * &other.z), p);
+
+    return Point { x: x, y: y, z: z };
+  }
+}
+
+fn multiply(c: &Parameters, point: &Point, k: &BigInt) -> Point {
+  let mut result = Point::infinity();
+
+  let k = k.to_biguint().unwrap();
+
+  for i in (0 .. k.bits()).rev() {
+    result = result.double(c);
+
+    if (&k >> i).is_odd() {
+      result = result.add(c, point);
+    }
+  }
+
+  return result;
+}
+
+/// Decodes an uncompressed point, checking that it is on the curve.
+fn decode_point(c: &Parameters, size: usize, data: &[u8]) -> Option<Point> {
+  if data.len() != 1 + 2 * size || data[0] != 0x04 {
+    return None;
+  }
+
+  let x = bignum::from_bytes_be(&data[1 .. 1 + size]);
+  let y = bignum::from_bytes_be(&data[1 + size ..]);
+
+  if x >= c.p || y >= c.p {
+    return None;
+  }
+
+  let lhs = bignum::modulo(&(&y * &y), &c.p);
+  let rhs = bignum::modulo(&(&x * &x * &x - 3.to_bigint().unwrap() * &x + &c.b), &c.p);
+
+  if lhs != rhs {
+    return None;
+  }
+
+  return Some(Point::affine(x, y));
+}
+
+fn encode_point(c: &Parameters, size: usize, point: &Point) -> Vec<u8> {
+  let (x, y) = point.to_affine(c).unwrap();
+
+  let mut result = vec![0x04];
+  result.extend(bignum::to_bytes_be(&x, size));
+  result.extend(bignum::to_bytes_be(&y, size));
+  return result;
+}
+
+#[cfg(test)]
+mod tests {
+  use num::bigint::ToBigInt;
+
+  use super::{Curve, PrivateKey};
+
+  #[test]
+  fn computes_public_points_on_the_curve() {
+    for &curve in [Curve::NistP256, Curve::NistP384, Curve::NistP521].iter() {
+      let key = PrivateKey::from_scalar(curve, 123456789.to_bigint().unwrap());
+      let parameters = curve.parameters();
+
+      assert!(super::decode_point(&parameters, curve.field_size(), &key.point[..]).is_some());
+    }
+  }
+
+  #[test]
+  fn generates_keys_on_the_curve() {
+    let key = PrivateKey::generate(Curve::NistP384);
+
+    assert_eq!(PrivateKey::from_scalar(Curve::NistP384, key.scalar.clone()), key);
+    assert!(key.public_key().verify(b"data", &key.sign(b"data")[..]));
+  }
+
+  #[test]
+  fn signs_and_verifies() {
+    for &curve in [Curve::NistP256, Curve::NistP384, Curve::NistP521].iter() {
+      let key = PrivateKey::from_scalar(curve, 987654321.to_bigint().unwrap());
+
+      let signature = key.sign(b"The quick brown fox jumps over the lazy dog");
+
+      assert!(key.public_key().verify(b"The quick brown fox jumps over the lazy dog", &signature[..]));
+      assert!(!key.public_key().verify(b"The quick brown fox jumps over the lazy cat", &signature[..]));
+    }
+  }
+
+  #[test]
+  fn rejects_malformed_signatures() {
+    let key = PrivateKey::from_scalar(Curve::NistP256, 987654321.to_bigint().unwrap()).public_key();
+
+    assert!(!key.verify(b"data", &[]));
+    assert!(!key.verify(b"data", &[0, 0, 0, 0, 0, 0, 0, 0]));
+    assert!(!key.verify(b"data", &[0, 0, 0, 1, 1, 0, 0]));
+  }
+}
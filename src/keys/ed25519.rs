@@ -0,0 +1,403 @@
+extern crate libc;
+
+use std::io::{Read, Write};
+use std::ptr;
+
+use num::{Zero, One, Num, Integer};
+use num::bigint::{BigInt, ToBigInt};
+
+use rand::Rng;
+
+use bignum;
+
+use hash::{Hash, SHA512};
+
+use sshio::{SSHRead, SSHWrite};
+
+use keys::Error;
+
+#[repr(C)] struct EVP_PKEY { _private: [u8; 0] }
+#[repr(C)] struct EVP_MD_CTX { _private: [u8; 0] }
+
+const EVP_PKEY_ED25519: libc::c_int = 1087;
+
+#[link(name = "crypto")]
+extern {
+  fn EVP_PKEY_new_raw_private_key(key_type: libc::c_int, engine: *mut libc::c_void, key: *const u8, length: libc::size_t) -> *mut EVP_PKEY;
+  fn EVP_PKEY_get_raw_public_key(key: *const EVP_PKEY, public: *mut u8, length: *mut libc::size_t) -> libc::c_int;
+  fn EVP_PKEY_free(key: *mut EVP_PKEY);
+  fn EVP_MD_CTX_new() -> *mut EVP_MD_CTX;
+  fn EVP_MD_CTX_free(context: *mut EVP_MD_CTX);
+  fn EVP_DigestSignInit(context: *mut EVP_MD_CTX, key_context: *mut *mut libc::c_void, digest: *const libc::c_void, engine: *mut libc::c_void, key: *mut EVP_PKEY) -> libc::c_int;
+  fn EVP_DigestSign(context: *mut EVP_MD_CTX, signature: *mut u8, signature_length: *mut libc::size_t, data: *const u8, length: libc::size_t) -> libc::c_int;
+}
+
+/// A private key in libcrypto, which signs in constant time, freed when
+/// dropped.
+struct EvpKey {
+  key: *mut EVP_PKEY
+}
+
+impl EvpKey {
+  fn new(seed: &[u8]) -> EvpKey {
+    let key = unsafe { EVP_PKEY_new_raw_private_key(EVP_PKEY_ED25519, ptr::null_mut(), seed.as_ptr(), seed.len() as libc::size_t) };
+
+    if key.is_null() {
+      panic!("Failed to load Ed25519 key");
+    }
+
+    return EvpKey { key: key };
+  }
+}
+
+impl Drop for EvpKey {
+  fn drop(&mut self) {
+    unsafe { EVP_PKEY_free(self.key) };
+  }
+}
+
+/// An Ed25519 public key, `ssh-ed25519`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+  /// The encoded public point, 32 bytes
+  pub key: Vec<u8>
+}
+
+impl PublicKey {
+  /// Reads the key fields, following the `ssh-ed25519` name, of a public key
+  /// blob.
+  pub fn read(reader: &mut Read) -> Result<PublicKey, Error> {
+    let mut reader = reader;
+
+    return Ok(PublicKey { key: try!(reader.try_read_binary_string()) });
+  }
+
+  /// Writes the key fields, following the `ssh-ed25519` name, of a public
+  /// key blob.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_binary_string(&self.key[..]);
+  }
+
+  /// Verifies a raw 64 byte `signature` of `data`.
+  ///
+  /// See [RFC 8032, section 5.1.7](https://tools.ietf.org/html/rfc8032#section-5.1.7).
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+    if self.key.len() != 32 || signature.len() != 64 {
+      return false;
+    }
+
+    let c = Curve::new();
+
+    let a = match c.decode(&self.key[..]) {
+      Some(a) => a,
+      None => return false
+    };
+
+    let r = match c.decode(&signature[.. 32]) {
+      Some(r) => r,
+      None => return false
+    };
+
+    let s = bignum::from_bytes_le(&signature[32 ..]);
+
+    if s >= c.l {
+      return false;
+    }
+
+    let k = bignum::modulo(&bignum::from_bytes_le(&sha512(&[&signature[.. 32], &self.key[..], data])[..]), &c.l);
+
+    let lhs = c.multiply(&c.base, &s);
+    let rhs = c.add(&r, &c.multiply(&a, &k));
+
+    return c.encode(&lhs) == c.encode(&rhs);
+  }
+}
+
+/// An Ed25519 private key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivateKey {
+  /// The encoded public point, 32 bytes
+  pub public: Vec<u8>,
+  /// The private seed, 32 bytes
+  pub seed: Vec<u8>
+}
+
+impl PrivateKey {
+  /// Reads the key fields of an `ssh-ed25519` key in an OpenSSH private key
+  /// file, the public key and the seed followed by the public key.
+  pub fn read(reader: &mut Read) -> Result<PrivateKey, Error> {
+    let mut reader = reader;
+
+    let public = try!(reader.try_read_binary_string());
+    let private = try!(reader.try_read_binary_string());
+
+    if public.len() != 32 || private.len() != 64 {
+      return Err(Error::InvalidFormat);
+    }
+
+    let key = PrivateKey::from_seed(&private[.. 32]);
+
+    if key.public != public {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(key);
+  }
+
+  /// Writes the key fields of an `ssh-ed25519` key in an OpenSSH private key
+  /// file.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    let mut private = self.seed.clone();
+    private.extend(self.public.iter().cloned());
+
+    writer.write_binary_string(&self.public[..]);
+    writer.write_binary_string(&private[..]);
+  }
+
+  /// Constructs a private key from its 32 byte seed, computing the public
+  /// key.
+  pub fn from_seed(seed: &[u8]) -> PrivateKey {
+    assert_eq!(seed.len(), 32);
+
+    let key = EvpKey::new(seed);
+
+    let mut public = vec![0; 32];
+    let mut length = public.len() as libc::size_t;
+
+    if unsafe { EVP_PKEY_get_raw_public_key(key.key, public.as_mut_ptr(), &mut length) } != 1 || length != 32 {
+      panic!("Failed to compute Ed25519 public key");
+    }
+
+    return PrivateKey { public: public, seed: seed.to_vec() };
+  }
+
+  /// Generates a key from a random seed.
+  pub fn generate() -> PrivateKey {
+    let mut seed = [0u8; 32];
+
+    bignum::os_rng().fill_bytes(&mut seed);
+
+    return PrivateKey::from_seed(&seed);
+  }
+
+  pub fn public_key(&self) -> PublicKey {
+    return PublicKey { key: self.public.clone() };
+  }
+
+  /// Signs `data` with libcrypto, returning the raw 64 byte signature.
+  ///
+  /// See [RFC 8032, section 5.1.6](https://tools.ietf.org/html/rfc8032#section-5.1.6).
+  pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+    let key = EvpKey::new(&self.seed[..]);
+
+    let mut signature = vec![0; 64];
+    let mut length = signature.len() as libc::size_t;
+
+    let signed = unsafe {
+      let context = EVP_MD_CTX_new();
+
+      let signed = !context.is_null() &&
+        EVP_DigestSignInit(context, ptr::null_mut(), ptr::null(), ptr::null_mut(), key.key) == 1 &&
+        EVP_DigestSign(context, signature.as_mut_ptr(), &mut length, data.as_ptr(), data.len() as libc::size_t) == 1;
+
+      EVP_MD_CTX_free(context);
+
+      signed
+    };
+
+    if !signed || length != 64 {
+      panic!("Failed to sign with Ed25519 key");
+    }
+
+    return signature;
+  }
+}
+
+fn sha512(parts: &[&[u8]]) -> Vec<u8> {
+  let mut hash = SHA512::new();
+
+  for part in parts.iter() {
+    hash.update(part);
+  }
+
+  return hash.digest();
+}
+
+/// A point in extended coordinates, `(X / Z, Y / Z)` with `X Y = Z T`.
+#[derive(Clone, Debug)]
+struct Point {
+  x: BigInt,
+  y: BigInt,
+  z: BigInt,
+  t: BigInt
+}
+
+/// The twisted Edwards curve `-x^2 + y^2 = 1 + d x^2 y^2` over `2^255 - 19`.
+struct Curve {
+  p: BigInt,
+  d: BigInt,
+  /// A square root of -1
+  i: BigInt,
+  /// Order of the base point
+  l: BigInt,
+  base: Point
+}
+
+impl Curve {
+  fn new() -> Curve {
+    let two = 2.to_bigint().unwrap();
+
+    let p: BigInt = (BigInt::one() << 255) - 19.to_bigint().unwrap();
+    let l: BigInt = (BigInt::one() << 252) + BigInt::from_str_radix("27742317777372353535851937790883648493", 10).unwrap();
+
+    let d = bignum::modulo(&(-121665.to_bigint().unwrap() * bignum::mod_inverse(&121666.to_bigint().unwrap(), &p)), &p);
+    let i = bignum::mod_exp(&two, &((&p - BigInt::one()) / 4.to_bigint().unwrap()), &p);
+
+    let mut curve = Curve {
+      p: p,
+      d: d,
+      i: i,
+      l: l,
+      base: Point { x: Zero::zero(), y: Zero::zero(), z: One::one(), t: Zero::zero() }
+    };
+
+    let y = bignum::modulo(&(4.to_bigint().unwrap() * bignum::mod_inverse(&5.to_bigint().unwrap(), &curve.p)), &curve.p);
+    curve.base = curve.decode(&bignum::to_bytes_le(&y, 32)[..]).unwrap();
+
+    return curve;
+  }
+
+  fn add(&self, a: &Point, b: &Point) -> Point {
+    let p = &self.p;
+
+    let aa = bignum::modulo(&((&a.y - &a.x) * (&b.y - &b.x)), p);
+    let bb = bignum::modulo(&((&a.y + &a.x) * (&b.y + &b.x)), p);
+    let cc = bignum::modulo(&(2.to_bigint().unwrap() * &self.d * &a.t * &b.t), p);
+    let dd = bignum::modulo(&(2.to_bigint().unwrap() * &a.z * &b.z), p);
+
+    let e = &bb - &aa;
+    let f = &dd - &cc;
+    let g = &dd + &cc;
+    let h = &bb + &aa;
+
+    return Point {
+      x: bignum::modulo(&(&e * &f), p),
+      y: bignum::modulo(&(&g * &h), p),
+      z: bignum::modulo(&(&f * &g), p),
+      t: bignum::modulo(&(&e * &h), p)
+    };
+  }
+
+  fn multiply(&self, point: &Point, k: &BigInt) -> Point {
+    let mut result = Point { x: Zero::zero(), y: One::one(), z: One::one(), t: Zero::zero() };
+
+    let k = k.to_biguint().unwrap();
+
+    for i in (0 .. k.bits()).rev() {
+      result = self.add(&result, &result);
+
+      if (&k >> i).is_odd() {
+        result = self.add(&result, point);
+      }
+    }
+
+    return result;
+  }
+
+  fn encode(&self, point: &Point) -> Vec<u8> {
+    let z_inv = bignum::mod_inverse(&point.z, &self.p);
+
+    let x = bignum::modulo(&(&point.x * &z_inv), &self.p);
+    let y = bignum::modulo(&(&point.y * &z_inv), &self.p);
+
+    let mut result = bignum::to_bytes_le(&y, 32);
+
+    if x.is_odd() {
+      result[31] |= 0x80;
+    }
+
+    return result;
+  }
+
+  fn decode(&self, data: &[u8]) -> Option<Point> {
+    if data.len() != 32 {
+      return None;
+    }
+
+    let mut data = data.to_vec();
+
+    let sign = data[31] & 0x80 != 0;
+    data[31] &= 0x7F;
+
+    let p = &self.p;
+    let y = bignum::from_bytes_le(&data[..]);
+
+    if &y >= p {
+      return None;
+    }
+
+    let y2 = bignum::modulo(&(&y * &y), p);
+    let u = bignum::modulo(&(&y2 - BigInt::one()), p);
+    let v = bignum::modulo(&(&self.d * &y2 + BigInt::one()), p);
+    let x2 = bignum::modulo(&(u * bignum::mod_inverse(&v, p)), p);
+
+    let mut x = bignum::mod_exp(&x2, &((p + 3.to_bigint().unwrap()) / 8.to_bigint().unwrap()), p);
+
+    if bignum::modulo(&(&x * &x), p) != x2 {
+      x = bignum::modulo(&(&x * &self.i), p);
+    }
+
+    if bignum::modulo(&(&x * &x), p) != x2 {
+      return None;
+    }
+
+    if x.is_zero() && sign {
+      return None;
+    }
+
+    if x.is_odd() != sign {
+      x = p - &x;
+    }
+
+    let t = bignum::modulo(&(&x * &y), p);
+
+    return Some(Point { x: x, y: y, z: One::one(), t: t });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::PrivateKey;
+
+  fn from_hex(s: &str) -> Vec<u8> {
+    return (0 .. s.len() / 2).map(|i| { u8::from_str_radix(&s[2 * i .. 2 * i + 2], 16).unwrap() }).collect();
+  }
+
+  // Test 1 and 2 of RFC 8032, section 7.1
+  #[test]
+  fn signs_test_vectors() {
+    let key = PrivateKey::from_seed(&from_hex("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")[..]);
+
+    assert_eq!(key.public, from_hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"));
+    assert_eq!(key.sign(b""), from_hex("e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"));
+
+    let key = PrivateKey::from_seed(&from_hex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb")[..]);
+
+    assert_eq!(key.public, from_hex("3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c"));
+    assert_eq!(key.sign(&[0x72]), from_hex("92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"));
+  }
+
+  #[test]
+  fn verifies_signatures() {
+    let key = PrivateKey::from_seed(&from_hex("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb")[..]);
+
+    let signature = key.sign(b"The quick brown fox jumps over the lazy dog");
+
+    assert!(key.public_key().verify(b"The quick brown fox jumps over the lazy dog", &signature[..]));
+    assert!(!key.public_key().verify(b"The quick brown fox jumps over the lazy cat", &signature[..]));
+  }
+}
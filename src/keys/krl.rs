@@ -0,0 +1,391 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use num::bigint::Sign;
+
+use hash::{Hash, SHA1, SHA256};
+
+use keys::{Error, Key, PublicKey};
+use keys::certificate::Certificate;
+
+use sshio::SSHRead;
+
+/// What KRL files start with, `SSHKRL\n\0`.
+static MAGIC: &'static [u8] = b"SSHKRL\n\0";
+
+static FORMAT_VERSION: u32 = 1;
+
+const SECTION_CERTIFICATES: u8 = 1;
+const SECTION_EXPLICIT_KEY: u8 = 2;
+const SECTION_FINGERPRINT_SHA1: u8 = 3;
+const SECTION_SIGNATURE: u8 = 4;
+const SECTION_FINGERPRINT_SHA256: u8 = 5;
+
+const SECTION_CERT_SERIAL_LIST: u8 = 0x20;
+const SECTION_CERT_SERIAL_RANGE: u8 = 0x21;
+const SECTION_CERT_SERIAL_BITMAP: u8 = 0x22;
+const SECTION_CERT_KEY_ID: u8 = 0x23;
+
+/// Certificates revoked by serial number or key ID, all signed by one CA.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RevokedCertificates {
+  /// The CA, or `None` for certificates signed by any CA
+  pub ca: Option<PublicKey>,
+  /// Ranges of serial numbers, inclusive
+  pub serials: Vec<(u64, u64)>,
+  pub key_ids: Vec<String>
+}
+
+impl RevokedCertificates {
+  fn revokes(&self, certificate: &Certificate) -> bool {
+    if let Some(ref ca) = self.ca {
+      if ca.blob() != certificate.signature_key.blob() {
+        return false;
+      }
+    }
+
+    return self.serials.iter().any(|&(from, to)| { from <= certificate.serial && certificate.serial <= to }) ||
+      self.key_ids.iter().any(|id| { *id == certificate.key_id });
+  }
+}
+
+/// An OpenSSH key revocation list, as made by `ssh-keygen -k`.
+///
+/// See [PROTOCOL.krl](https://cvsweb.openbsd.org/src/usr.bin/ssh/PROTOCOL.krl).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RevocationList {
+  pub version: u64,
+  /// When the list was made, in seconds since the epoch
+  pub generated_date: u64,
+  pub comment: String,
+  pub certificates: Vec<RevokedCertificates>,
+  /// Public key blobs
+  pub keys: Vec<Vec<u8>>,
+  /// SHA-1 hashes of public key blobs
+  pub sha1_hashes: Vec<Vec<u8>>,
+  /// SHA-256 hashes of public key blobs
+  pub sha256_hashes: Vec<Vec<u8>>,
+  /// The keys that signed the list, whose signatures have been verified
+  pub signing_keys: Vec<PublicKey>
+}
+
+impl RevocationList {
+  /// Parses a KRL, verifying its signatures if it has any.
+  pub fn parse(data: &[u8]) -> Result<RevocationList, Error> {
+    let mut reader = io::Cursor::new(data);
+
+    if !data.starts_with(MAGIC) {
+      return Err(Error::InvalidFormat);
+    }
+
+    reader.set_position(MAGIC.len() as u64);
+
+    if try!(reader.try_read_uint32()) != FORMAT_VERSION {
+      return Err(Error::InvalidFormat);
+    }
+
+    let mut list = RevocationList::default();
+
+    list.version = try!(reader.try_read_uint64());
+    list.generated_date = try!(reader.try_read_uint64());
+    try!(reader.try_read_uint64()); // flags
+    try!(reader.try_read_binary_string()); // reserved
+    list.comment = try!(reader.try_read_string());
+
+    while (reader.position() as usize) < data.len() {
+      let section_type = try!(reader.try_read_n(1))[0];
+
+      if section_type == SECTION_SIGNATURE {
+        let signing_key = try!(PublicKey::from_blob(&try!(reader.try_read_binary_string())[..]));
+        let signed = &data[.. reader.position() as usize];
+        let signature = try!(reader.try_read_binary_string());
+
+        if !signing_key.verify(signed, &signature[..]) {
+          return Err(Error::InvalidFormat);
+        }
+
+        list.signing_keys.push(signing_key);
+        continue;
+      }
+
+      // Signatures have to come last.
+      if !list.signing_keys.is_empty() {
+        return Err(Error::InvalidFormat);
+      }
+
+      let section = try!(reader.try_read_binary_string());
+      let mut section_reader = io::Cursor::new(&section[..]);
+
+      match section_type {
+        SECTION_CERTIFICATES => list.certificates.push(try!(read_certificates(&mut section_reader, section.len()))),
+        SECTION_EXPLICIT_KEY => list.keys.extend(try!(read_strings(&mut section_reader, section.len()))),
+        SECTION_FINGERPRINT_SHA1 => list.sha1_hashes.extend(try!(read_strings(&mut section_reader, section.len()))),
+        SECTION_FINGERPRINT_SHA256 => list.sha256_hashes.extend(try!(read_strings(&mut section_reader, section.len()))),
+        _ => return Err(Error::InvalidFormat)
+      }
+    }
+
+    return Ok(list);
+  }
+
+  pub fn read_file(path: &Path) -> Result<RevocationList, Error> {
+    let mut data = Vec::new();
+
+    try!(try!(File::open(path)).read_to_end(&mut data));
+
+    return RevocationList::parse(&data[..]);
+  }
+
+  /// Whether `key` is revoked, either itself or, for a certificate, the
+  /// certificate, the certified key or the CA key.
+  pub fn is_revoked(&self, key: &PublicKey) -> bool {
+    if let Key::Certificate(ref c) = key.key {
+      return self.certificates.iter().any(|r| { r.revokes(c) }) ||
+        self.is_revoked(&c.public_key()) ||
+        self.is_revoked(&c.signature_key);
+    }
+
+    let blob = key.blob();
+
+    if self.keys.contains(&blob) {
+      return true;
+    }
+
+    let mut sha1 = SHA1::new();
+    sha1.update(&blob[..]);
+
+    let mut sha256 = SHA256::new();
+    sha256.update(&blob[..]);
+
+    return self.sha1_hashes.contains(&sha1.digest()) || self.sha256_hashes.contains(&sha256.digest());
+  }
+}
+
+fn read_strings(reader: &mut io::Cursor<&[u8]>, length: usize) -> Result<Vec<Vec<u8>>, Error> {
+  let mut strings = Vec::new();
+
+  while (reader.position() as usize) < length {
+    strings.push(try!(reader.try_read_binary_string()));
+  }
+
+  return Ok(strings);
+}
+
+fn read_certificates(reader: &mut io::Cursor<&[u8]>, length: usize) -> Result<RevokedCertificates, Error> {
+  let ca = try!(reader.try_read_binary_string());
+
+  let mut revoked = RevokedCertificates {
+    ca: if ca.is_empty() { None } else { Some(try!(PublicKey::from_blob(&ca[..]))) },
+    serials: Vec::new(),
+    key_ids: Vec::new()
+  };
+
+  try!(reader.try_read_binary_string()); // reserved
+
+  while (reader.position() as usize) < length {
+    let section_type = try!(reader.try_read_n(1))[0];
+    let section = try!(reader.try_read_binary_string());
+
+    let mut section_reader = io::Cursor::new(&section[..]);
+
+    match section_type {
+      SECTION_CERT_SERIAL_LIST => {
+        while (section_reader.position() as usize) < section.len() {
+          let serial = try!(section_reader.try_read_uint64());
+          revoked.serials.push((serial, serial));
+        }
+      }
+      SECTION_CERT_SERIAL_RANGE => {
+        let from = try!(section_reader.try_read_uint64());
+        let to = try!(section_reader.try_read_uint64());
+
+        revoked.serials.push((from, to));
+      }
+      SECTION_CERT_SERIAL_BITMAP => {
+        let offset = try!(section_reader.try_read_uint64());
+        let (sign, bitmap) = try!(section_reader.try_read_mpint()).to_bytes_le();
+
+        if sign == Sign::Minus {
+          return Err(Error::InvalidFormat);
+        }
+
+        // Bit i, counting from the least significant, revokes offset + i.
+        for (i, byte) in bitmap.iter().enumerate() {
+          for bit in 0 .. 8 {
+            if byte & (1 << bit) != 0 {
+              let serial = match offset.checked_add((i * 8 + bit) as u64) {
+                Some(serial) => serial,
+                None => return Err(Error::InvalidFormat)
+              };
+
+              match revoked.serials.last_mut() {
+                Some(&mut (_, ref mut to)) if to.checked_add(1) == Some(serial) => *to = serial,
+                _ => revoked.serials.push((serial, serial))
+              }
+            }
+          }
+        }
+      }
+      SECTION_CERT_KEY_ID => {
+        while (section_reader.position() as usize) < section.len() {
+          revoked.key_ids.push(try!(section_reader.try_read_string()));
+        }
+      }
+      _ => return Err(Error::InvalidFormat)
+    }
+  }
+
+  return Ok(revoked);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{self, Write};
+  use std::path::Path;
+
+  use byteorder::WriteBytesExt;
+
+  use base64;
+
+  use keys::{ed25519, Error, PrivateKey, PublicKey};
+  use keys::certificate;
+
+  use sshio::SSHWrite;
+
+  use super::RevocationList;
+
+  /// Made by OpenSSH's `ssh-keygen -k`, revoking serials 10 to 20, 42, 100,
+  /// 102 and 103 and the key ID `leaked-key` of certificates signed by
+  /// `certificate::tests::CA`, the key `REVOKED` and the SHA-256 hash of
+  /// `HASHED`.
+  static KRL: &'static str = "U1NIS1JMCgAAAAABAAAAAAAAAAAAAAAAatV8wQAAAAAAAAAAAAAAAAAAAAABAAAAawAAADMAAAALc3NoLWVkMjU1MTkAAAAgvCt08Pfs63PmsQrYtsByVlFDTYnnsFwuKs5vOZ8YQWIAAAAAIgAAABgAAAAAAAAACgAAAAw0AAAAAAAAAQAAB/8jAAAADgAAAApsZWFrZWQta2V5AgAAADcAAAAzAAAAC3NzaC1lZDI1NTE5AAAAIC7tX4zqGj47OGPDihULDn9M9DVfjAHtjMM9zsVbmAKtBQAAACQAAAAgLoZodTDSJe9m5KxA0ntPzJ1owpyv/1QhlEwzA1tzlfI=";
+
+  static REVOKED: &'static str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIC7tX4zqGj47OGPDihULDn9M9DVfjAHtjMM9zsVbmAKt rev";
+  static HASHED: &'static str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIIrReQUSG/5E2TzILMxoMxfCe0ntbwIOCx8em6kwYT3h rev2";
+  static ALLOWED: &'static str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIMtav73hX6KmPNkVTBRy3q8RABQXbyZJ56CieU6kMvaY rev3";
+
+  fn krl() -> RevocationList {
+    return RevocationList::parse(&base64::decode(KRL).unwrap()[..]).unwrap();
+  }
+
+  #[test]
+  fn reads_openssh_krls() {
+    let krl = krl();
+
+    assert_eq!(krl.certificates.len(), 1);
+    assert_eq!(krl.certificates[0].ca.as_ref().map(|k| { k.blob() }), Some(PublicKey::parse(certificate::tests::CA).unwrap().blob()));
+    assert_eq!(krl.certificates[0].serials, vec![(10, 20), (42, 42), (100, 100), (102, 103)]);
+    assert_eq!(krl.certificates[0].key_ids, vec!["leaked-key".to_string()]);
+    assert_eq!(krl.keys.len(), 1);
+    assert_eq!(krl.sha256_hashes.len(), 1);
+  }
+
+  #[test]
+  fn revokes_keys() {
+    let krl = krl();
+
+    assert!(krl.is_revoked(&PublicKey::parse(REVOKED).unwrap()));
+    assert!(krl.is_revoked(&PublicKey::parse(HASHED).unwrap()));
+    assert!(!krl.is_revoked(&PublicKey::parse(ALLOWED).unwrap()));
+  }
+
+  #[test]
+  fn revokes_certificates() {
+    let krl = krl();
+
+    // Serial 42
+    assert!(krl.is_revoked(&PublicKey::parse(certificate::tests::USER_CERTIFICATE).unwrap()));
+    // Serial 0, and no ID
+    assert!(!krl.is_revoked(&PublicKey::parse(certificate::tests::HOST_CERTIFICATE).unwrap()));
+  }
+
+  /// A KRL with only a header, signed by `key`.
+  fn signed_krl(key: &PrivateKey) -> Vec<u8> {
+    let mut writer = io::Cursor::new(Vec::new());
+
+    writer.write_all(b"SSHKRL\n\0").unwrap();
+    writer.write_uint32(1);
+    writer.write_uint64(1);
+    writer.write_uint64(0);
+    writer.write_uint64(0);
+    writer.write_binary_string(&[]);
+    writer.write_string("");
+    writer.write_u8(4).unwrap();
+    writer.write_binary_string(&key.public_key_blob()[..]);
+
+    let signature = key.sign("ssh-ed25519", &writer.get_ref()[..]);
+    writer.write_binary_string(&signature[..]);
+
+    return writer.into_inner();
+  }
+
+  #[test]
+  fn verifies_signatures() {
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[3; 32]));
+    let mut data = signed_krl(&key);
+
+    assert_eq!(RevocationList::parse(&data[..]).unwrap().signing_keys, vec![key.public_key()]);
+
+    data[16] ^= 1;
+
+    assert_eq!(RevocationList::parse(&data[..]), Err(Error::InvalidFormat));
+  }
+
+  #[test]
+  fn ends_ranges_at_the_last_serial() {
+    let mut section = io::Cursor::new(Vec::new());
+
+    section.write_binary_string(&[]);
+    section.write_binary_string(&[]);
+    section.write_u8(0x21).unwrap();
+    section.write_binary_string(&[0, 0, 0, 0, 0, 0, 0, 5, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    section.write_u8(0x22).unwrap();
+    section.write_binary_string(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+
+    let mut writer = io::Cursor::new(Vec::new());
+
+    writer.write_all(b"SSHKRL\n\0").unwrap();
+    writer.write_uint32(1);
+    writer.write_uint64(1);
+    writer.write_uint64(0);
+    writer.write_uint64(0);
+    writer.write_binary_string(&[]);
+    writer.write_string("");
+    writer.write_u8(1).unwrap();
+    writer.write_binary_string(&section.into_inner()[..]);
+
+    let krl = RevocationList::parse(&writer.into_inner()[..]).unwrap();
+
+    assert_eq!(krl.certificates[0].serials, vec![(5, u64::max_value()), (0, 0)]);
+  }
+
+  #[test]
+  fn rejects_truncated_krls() {
+    let data = base64::decode(KRL).unwrap();
+
+    // Some prefixes end between sections and are complete lists.
+    for n in 0 .. data.len() {
+      match RevocationList::parse(&data[.. n]) {
+        Ok(_) | Err(Error::InvalidFormat) => {},
+        result => panic!("Unexpected result {:?}", result)
+      }
+    }
+
+    assert_eq!(RevocationList::parse(&data[.. data.len() - 1]), Err(Error::InvalidFormat));
+
+    let key = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[3; 32]));
+    let data = signed_krl(&key);
+
+    assert_eq!(RevocationList::parse(&data[.. data.len() - 1]), Err(Error::InvalidFormat));
+    assert_eq!(RevocationList::parse(&data[.. 20]), Err(Error::InvalidFormat));
+  }
+
+  #[test]
+  fn reports_missing_files() {
+    match RevocationList::read_file(Path::new("/nonexistent/revoked_keys")) {
+      Err(Error::Io(_)) => {},
+      result => panic!("Unexpected result {:?}", result)
+    }
+  }
+}
@@ -0,0 +1,727 @@
+/// RSA keys
+pub mod rsa;
+/// ECDSA keys over the NIST curves
+pub mod ecdsa;
+/// Ed25519 keys
+pub mod ed25519;
+/// OpenSSH private key files, `openssh-key-v1`
+pub mod openssh;
+/// The key derivation function of encrypted OpenSSH private keys
+pub mod bcrypt_pbkdf;
+/// Legacy PEM private key files, as written by OpenSSL
+pub mod pem;
+/// DER encoded ASN.1, as found in PEM key files
+pub mod der;
+/// OpenSSH certificates, keys signed by a certificate authority
+pub mod certificate;
+/// OpenSSH key revocation lists
+pub mod krl;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str;
+
+use base64;
+
+use hash::{Hash, SHA256};
+
+use sshio::{Malformed, SSHRead, SSHWrite};
+
+/// Why a key could not be loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+  /// The data is not in a format we understand
+  InvalidFormat,
+  /// The key type is not one we support
+  UnsupportedKeyType(String),
+  /// The key is encrypted with a cipher or KDF we do not support
+  UnsupportedCipher(String),
+  /// The key is encrypted, and no passphrase was given
+  PassphraseRequired,
+  /// The key is encrypted, and the passphrase is wrong
+  WrongPassphrase,
+  /// Keys of the requested type can not have this many bits
+  InvalidKeySize(usize),
+  /// The file could not be read
+  Io(String)
+}
+
+impl From<Malformed> for Error {
+  fn from(_: Malformed) -> Error {
+    return Error::InvalidFormat;
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(e: io::Error) -> Error {
+    return Error::Io(e.to_string());
+  }
+}
+
+/// Decodes a private key file in either the OpenSSH or the legacy PEM format,
+/// returning the keys it contains with their comments.
+///
+/// PEM files have no comments, so theirs are empty.
+pub fn decode_private_key(text: &str, passphrase: Option<&str>) -> Result<Vec<(PrivateKey, String)>, Error> {
+  if text.contains(openssh::LABEL) {
+    return openssh::decode(text, passphrase);
+  }
+
+  let key = try!(pem::decode(text, passphrase));
+
+  return Ok(vec![(key, String::new())]);
+}
+
+/// Reads the private key file at `path`, in either the OpenSSH or the legacy
+/// PEM format.
+pub fn read_private_key_file(path: &Path, passphrase: Option<&str>) -> Result<Vec<(PrivateKey, String)>, Error> {
+  let mut text = String::new();
+
+  try!(try!(File::open(path)).read_to_string(&mut text));
+
+  return decode_private_key(&text, passphrase);
+}
+
+/// A private key of any supported type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrivateKey {
+  Rsa(rsa::PrivateKey),
+  Ecdsa(ecdsa::PrivateKey),
+  Ed25519(ed25519::PrivateKey)
+}
+
+impl PrivateKey {
+  /// Generates a key of `key_type`, `rsa`, `ecdsa` or `ed25519`, like
+  /// `ssh-keygen -t`.
+  ///
+  /// RSA keys have 2048 to 8192 bits, 3072 by default, and ECDSA keys 256,
+  /// 384 or 521, 256 by default. Ed25519 keys are always 256 bits.
+  pub fn generate(key_type: &str, bits: Option<usize>) -> Result<PrivateKey, Error> {
+    return match key_type {
+      "rsa" => match bits.unwrap_or(3072) {
+        bits @ 2048 ... 8192 if bits % 8 == 0 => Ok(PrivateKey::Rsa(rsa::PrivateKey::generate(bits))),
+        bits => Err(Error::InvalidKeySize(bits))
+      },
+      "ecdsa" => {
+        let curve = match bits.unwrap_or(256) {
+          256 => ecdsa::Curve::NistP256,
+          384 => ecdsa::Curve::NistP384,
+          521 => ecdsa::Curve::NistP521,
+          bits => return Err(Error::InvalidKeySize(bits))
+        };
+
+        Ok(PrivateKey::Ecdsa(ecdsa::PrivateKey::generate(curve)))
+      }
+      "ed25519" => match bits {
+        None | Some(256) => Ok(PrivateKey::Ed25519(ed25519::PrivateKey::generate())),
+        Some(bits) => Err(Error::InvalidKeySize(bits))
+      },
+      _ => Err(Error::UnsupportedKeyType(key_type.to_string()))
+    };
+  }
+
+  /// Reads a key type name followed by the key fields, as in the private
+  /// section of an OpenSSH private key file.
+  pub fn read(reader: &mut Read) -> Result<PrivateKey, Error> {
+    let mut reader = reader;
+
+    let name = try!(reader.try_read_string());
+
+    return match name.as_str() {
+      "ssh-rsa" => Ok(PrivateKey::Rsa(try!(rsa::PrivateKey::read(reader)))),
+      "ssh-ed25519" => Ok(PrivateKey::Ed25519(try!(ed25519::PrivateKey::read(reader)))),
+      _ => match ecdsa::Curve::from_algorithm(name.as_str()) {
+        Some(curve) => {
+          let key = try!(ecdsa::PrivateKey::read(reader));
+
+          if key.curve != curve {
+            return Err(Error::InvalidFormat);
+          }
+
+          Ok(PrivateKey::Ecdsa(key))
+        }
+        None => Err(Error::UnsupportedKeyType(name))
+      }
+    };
+  }
+
+  /// Writes the key type name followed by the key fields.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.algorithm());
+
+    match self {
+      &PrivateKey::Rsa(ref k) => k.write(writer),
+      &PrivateKey::Ecdsa(ref k) => k.write(writer),
+      &PrivateKey::Ed25519(ref k) => k.write(writer)
+    }
+  }
+
+  /// The key type, like `ssh-ed25519`.
+  pub fn algorithm(&self) -> &'static str {
+    return match self {
+      &PrivateKey::Rsa(_) => "ssh-rsa",
+      &PrivateKey::Ecdsa(ref k) => k.curve.algorithm(),
+      &PrivateKey::Ed25519(_) => "ssh-ed25519"
+    };
+  }
+
+  /// The signature algorithms this key can sign with, in order of
+  /// preference.
+  pub fn signature_algorithms(&self) -> Vec<&'static str> {
+    return match self {
+      &PrivateKey::Rsa(_) => rsa::SIGNATURE_ALGORITHMS.to_vec(),
+      _ => vec![self.algorithm()]
+    };
+  }
+
+  /// The public half of the key, without a comment.
+  pub fn public_key(&self) -> PublicKey {
+    let key = match self {
+      &PrivateKey::Rsa(ref k) => Key::Rsa(k.public_key()),
+      &PrivateKey::Ecdsa(ref k) => Key::Ecdsa(k.public_key()),
+      &PrivateKey::Ed25519(ref k) => Key::Ed25519(k.public_key())
+    };
+
+    return PublicKey { key: key, comment: String::new() };
+  }
+
+  /// The public key blob, the key type name followed by the public key
+  /// fields.
+  pub fn public_key_blob(&self) -> Vec<u8> {
+    return self.public_key().blob();
+  }
+
+  /// Signs `data` with one of `signature_algorithms`, returning the encoded
+  /// signature, the algorithm name followed by the signature blob.
+  pub fn sign(&self, algorithm: &str, data: &[u8]) -> Vec<u8> {
+    if !self.signature_algorithms().contains(&algorithm) {
+      panic!("Can't sign with {} using a {} key", algorithm, self.algorithm());
+    }
+
+    let signature = match self {
+      &PrivateKey::Rsa(ref k) => k.sign(algorithm, data),
+      &PrivateKey::Ecdsa(ref k) => k.sign(data),
+      &PrivateKey::Ed25519(ref k) => k.sign(data)
+    };
+
+    let mut writer = io::Cursor::new(Vec::new());
+
+    writer.write_string(algorithm);
+    writer.write_binary_string(&signature[..]);
+
+    return writer.into_inner();
+  }
+}
+
+/// The fields of a public key of any supported type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Key {
+  Rsa(rsa::PublicKey),
+  Ecdsa(ecdsa::PublicKey),
+  Ed25519(ed25519::PublicKey),
+  Certificate(Box<certificate::Certificate>)
+}
+
+impl Key {
+  /// Reads the fields of a key of the type `name`.
+  pub fn read(name: &str, reader: &mut Read) -> Result<Key, Error> {
+    if name.ends_with(certificate::SUFFIX) {
+      let base = &name[.. name.len() - certificate::SUFFIX.len()];
+      let certificate = try!(certificate::Certificate::read(base, reader));
+
+      if certificate.algorithm() != name {
+        return Err(Error::InvalidFormat);
+      }
+
+      return Ok(Key::Certificate(Box::new(certificate)));
+    }
+
+    return match name {
+      "ssh-rsa" => Ok(Key::Rsa(try!(rsa::PublicKey::read(reader)))),
+      "ssh-ed25519" => Ok(Key::Ed25519(try!(ed25519::PublicKey::read(reader)))),
+      _ => match ecdsa::Curve::from_algorithm(name) {
+        Some(curve) => {
+          let key = try!(ecdsa::PublicKey::read(reader));
+
+          if key.curve != curve {
+            return Err(Error::InvalidFormat);
+          }
+
+          Ok(Key::Ecdsa(key))
+        }
+        None => Err(Error::UnsupportedKeyType(name.to_string()))
+      }
+    };
+  }
+
+  /// Writes the fields of the key, following its type name.
+  pub fn write(&self, writer: &mut Write) {
+    match self {
+      &Key::Rsa(ref k) => k.write(writer),
+      &Key::Ecdsa(ref k) => k.write(writer),
+      &Key::Ed25519(ref k) => k.write(writer),
+      &Key::Certificate(ref c) => c.write(writer)
+    }
+  }
+
+  /// The key type, like `ssh-ed25519`.
+  pub fn algorithm(&self) -> &'static str {
+    return match self {
+      &Key::Rsa(_) => "ssh-rsa",
+      &Key::Ecdsa(ref k) => k.curve.algorithm(),
+      &Key::Ed25519(_) => "ssh-ed25519",
+      &Key::Certificate(ref c) => c.algorithm()
+    };
+  }
+}
+
+/// A public key with its comment, as found in `.pub` files.
+///
+/// The text forms are the single line OpenSSH format,
+/// `ssh-ed25519 AAAAC3Nza... comment`, and the
+/// [RFC 4716](https://tools.ietf.org/html/rfc4716) format, a
+/// `---- BEGIN SSH2 PUBLIC KEY ----` block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+  pub key: Key,
+  pub comment: String
+}
+
+static RFC4716_BEGIN: &'static str = "---- BEGIN SSH2 PUBLIC KEY ----";
+static RFC4716_END: &'static str = "---- END SSH2 PUBLIC KEY ----";
+
+impl PublicKey {
+  /// Reads a key type name followed by the key fields, as in a public key
+  /// blob.
+  pub fn read(reader: &mut Read) -> Result<PublicKey, Error> {
+    let mut reader = reader;
+
+    let name = try!(reader.try_read_string());
+    let key = try!(Key::read(name.as_str(), reader));
+
+    return Ok(PublicKey { key: key, comment: String::new() });
+  }
+
+  /// Writes the key type name followed by the key fields.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.algorithm());
+    self.key.write(writer);
+  }
+
+  /// Decodes a public key blob, which must contain nothing but the key.
+  pub fn from_blob(blob: &[u8]) -> Result<PublicKey, Error> {
+    let mut reader = io::Cursor::new(blob);
+
+    let key = try!(PublicKey::read(&mut reader));
+
+    if reader.position() != blob.len() as u64 || key.blob() != blob {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(key);
+  }
+
+  /// The public key blob.
+  pub fn blob(&self) -> Vec<u8> {
+    let mut writer = io::Cursor::new(Vec::new());
+
+    self.write(&mut writer);
+
+    return writer.into_inner();
+  }
+
+  /// The key type, like `ssh-ed25519`.
+  pub fn algorithm(&self) -> &'static str {
+    return self.key.algorithm();
+  }
+
+  /// The signature algorithms this key can verify, in order of preference.
+  ///
+  /// For certificates, these are the names used for the public key
+  /// algorithm, like `rsa-sha2-512-cert-v01@openssh.com`, while signatures
+  /// use the names of the certified key's algorithms.
+  pub fn signature_algorithms(&self) -> Vec<&'static str> {
+    return match self.key {
+      Key::Rsa(_) => rsa::SIGNATURE_ALGORITHMS.to_vec(),
+      Key::Certificate(ref c) => match c.key {
+        Key::Rsa(_) => certificate::RSA_ALGORITHMS.to_vec(),
+        _ => vec![self.algorithm()]
+      },
+      _ => vec![self.algorithm()]
+    };
+  }
+
+  /// Size of the key in bits.
+  pub fn bits(&self) -> usize {
+    return match self.key {
+      Key::Rsa(ref k) => k.bits(),
+      Key::Ecdsa(ref k) => k.curve.bits(),
+      Key::Ed25519(_) => 256,
+      Key::Certificate(ref c) => c.public_key().bits()
+    };
+  }
+
+  pub fn comment(&self) -> &str {
+    return &self.comment;
+  }
+
+  /// The SHA-256 fingerprint of the key blob, like `SHA256:+DiY3wvv...`, as
+  /// shown by `ssh-keygen -l`.
+  ///
+  /// Certificates have the fingerprint of the certified key.
+  pub fn fingerprint(&self) -> String {
+    if let Key::Certificate(ref c) = self.key {
+      return c.public_key().fingerprint();
+    }
+
+    let mut hash = SHA256::new();
+
+    hash.update(&self.blob()[..]);
+
+    return format!("SHA256:{}", base64::encode(&hash.digest()[..]).trim_right_matches('='));
+  }
+
+  /// Verifies an encoded `signature`, the algorithm name followed by the
+  /// signature blob, of `data`.
+  ///
+  /// Certificates verify signatures made with the certified key.
+  pub fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+    if let Key::Certificate(ref c) = self.key {
+      return c.public_key().verify(data, signature);
+    }
+
+    let mut reader = io::Cursor::new(signature);
+
+    let (algorithm, signature) = match (reader.try_read_string(), reader.try_read_binary_string()) {
+      (Ok(algorithm), Ok(signature)) => (algorithm, signature),
+      _ => return false
+    };
+
+    if !self.signature_algorithms().contains(&algorithm.as_str()) {
+      return false;
+    }
+
+    return match self.key {
+      Key::Rsa(ref k) => k.verify(&algorithm, data, &signature[..]),
+      Key::Ecdsa(ref k) => k.verify(data, &signature[..]),
+      Key::Ed25519(ref k) => k.verify(data, &signature[..]),
+      Key::Certificate(_) => unreachable!()
+    };
+  }
+
+  /// Parses a key in either the OpenSSH or the RFC 4716 format.
+  pub fn decode(text: &str) -> Result<PublicKey, Error> {
+    if text.trim_left().starts_with(RFC4716_BEGIN) {
+      return PublicKey::parse_rfc4716(text);
+    }
+
+    return PublicKey::parse(text.trim());
+  }
+
+  /// Reads the public key file at `path`, in either the OpenSSH or the
+  /// RFC 4716 format.
+  pub fn read_file(path: &Path) -> Result<PublicKey, Error> {
+    let mut text = String::new();
+
+    try!(try!(File::open(path)).read_to_string(&mut text));
+
+    return PublicKey::decode(&text);
+  }
+
+  /// Parses an OpenSSH public key line, `type base64 [comment]`.
+  pub fn parse(line: &str) -> Result<PublicKey, Error> {
+    let mut fields = line.trim().splitn(3, |c: char| { c == ' ' || c == '\t' });
+
+    let name = fields.next().unwrap_or("");
+
+    let blob = match fields.next().and_then(base64::decode) {
+      Some(blob) => blob,
+      None => return Err(Error::InvalidFormat)
+    };
+
+    let mut key = try!(PublicKey::from_blob(&blob[..]));
+
+    if key.algorithm() != name {
+      return Err(Error::InvalidFormat);
+    }
+
+    key.comment = fields.next().unwrap_or("").trim().to_string();
+
+    return Ok(key);
+  }
+
+  /// Formats the key as an OpenSSH public key line, without a line break.
+  pub fn to_line(&self) -> String {
+    let mut line = format!("{} {}", self.algorithm(), base64::encode(&self.blob()[..]));
+
+    if !self.comment.is_empty() {
+      line.push(' ');
+      line.push_str(&self.comment);
+    }
+
+    return line;
+  }
+
+  /// Parses an RFC 4716 public key block.
+  ///
+  /// Headers other than `Comment` are ignored.
+  ///
+  /// See [RFC 4716, section 3](https://tools.ietf.org/html/rfc4716#section-3).
+  pub fn parse_rfc4716(text: &str) -> Result<PublicKey, Error> {
+    let mut lines = text.lines().map(|l| { l.trim_right() }).skip_while(|l| { l.trim() != RFC4716_BEGIN });
+
+    if lines.next().is_none() {
+      return Err(Error::InvalidFormat);
+    }
+
+    let mut comment = String::new();
+    let mut header = String::new();
+    let mut body = String::new();
+
+    for line in lines {
+      if line.trim() == RFC4716_END {
+        let blob = match base64::decode(&body) {
+          Some(blob) => blob,
+          None => return Err(Error::InvalidFormat)
+        };
+
+        let mut key = try!(PublicKey::from_blob(&blob[..]));
+        key.comment = comment;
+
+        return Ok(key);
+      }
+
+      if !header.is_empty() || (body.is_empty() && line.contains(':')) {
+        if line.ends_with('\\') {
+          header.push_str(&line[.. line.len() - 1]);
+          continue;
+        }
+
+        header.push_str(line);
+
+        let (tag, value) = header.split_at(header.find(':').unwrap());
+
+        if tag.trim().to_lowercase() == "comment" {
+          let value = value[1 ..].trim();
+
+          comment = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            value[1 .. value.len() - 1].to_string()
+          } else {
+            value.to_string()
+          };
+        }
+
+        header.clear();
+        continue;
+      }
+
+      body.push_str(line.trim());
+    }
+
+    return Err(Error::InvalidFormat);
+  }
+
+  /// Formats the key as an RFC 4716 public key block, with a trailing line
+  /// break.
+  pub fn to_rfc4716(&self) -> String {
+    let mut text = String::new();
+
+    text.push_str(RFC4716_BEGIN);
+    text.push('\n');
+
+    if !self.comment.is_empty() {
+      let header = format!("Comment: \"{}\"", self.comment);
+      let mut length = 0;
+
+      // Header lines are limited to 72 bytes, longer ones are continued
+      // after a backslash.
+      for c in header.chars() {
+        if length + c.len_utf8() > 70 {
+          text.push_str("\\\n");
+          length = 0;
+        }
+
+        text.push(c);
+        length += c.len_utf8();
+      }
+
+      text.push('\n');
+    }
+
+    let body = base64::encode(&self.blob()[..]);
+
+    for chunk in body.as_bytes().chunks(70) {
+      text.push_str(str::from_utf8(chunk).unwrap());
+      text.push('\n');
+    }
+
+    text.push_str(RFC4716_END);
+    text.push('\n');
+
+    return text;
+  }
+}
+
+/// Wraps `data` in base64 lines of 70 characters between
+/// `-----BEGIN label-----` and `-----END label-----` lines.
+fn armor(data: &[u8], label: &str) -> String {
+  let mut text = format!("-----BEGIN {}-----\n", label);
+
+  for chunk in base64::encode(data).as_bytes().chunks(70) {
+    text.push_str(str::from_utf8(chunk).unwrap());
+    text.push('\n');
+  }
+
+  text.push_str(&format!("-----END {}-----\n", label));
+
+  return text;
+}
+
+/// Extracts the base64 body between `-----BEGIN label-----` and
+/// `-----END label-----` lines.
+fn dearmor(text: &str, label: &str) -> Result<Vec<u8>, Error> {
+  let begin = format!("-----BEGIN {}-----", label);
+  let end = format!("-----END {}-----", label);
+
+  let mut lines = text.lines().map(|l| { l.trim() }).skip_while(|l| { *l != begin });
+
+  if lines.next().is_none() {
+    return Err(Error::InvalidFormat);
+  }
+
+  let mut body = String::new();
+
+  for line in lines {
+    if line == end {
+      return base64::decode(&body).ok_or(Error::InvalidFormat);
+    }
+
+    body.push_str(line);
+  }
+
+  return Err(Error::InvalidFormat);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+  use std::path::Path;
+
+  use sshio::SSHWrite;
+
+  use super::{ed25519, Error, PrivateKey, PublicKey};
+
+  static ED25519: &'static str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIC/if5K7Qs4g31VdM9NitvIotVGzDfkbYpEPflZBWP1l ed25519 test key";
+
+  static RSA: &'static str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQC4KO2OG3lDKh6ovV4YIlGZTleU19F2WLKOcB8iIufeNZmzMX2KUhtN6Xw+5pPWHMCg/lip7CN6dRw6HOzN7BA1OBq6DsBlGEUTu5d/0PWK+p5geW79IBeSLpf/+j3W7WaxdJpuLB3ZMibO4i4T/O5k1ODpG06+MhcB5qln+bpa/w== rsa test key";
+
+  static RSA_RFC4716: &'static str = "---- BEGIN SSH2 PUBLIC KEY ----\nComment: \"1024-bit RSA, converted by root@vm from OpenSSH\"\nAAAAB3NzaC1yc2EAAAADAQABAAAAgQC4KO2OG3lDKh6ovV4YIlGZTleU19F2WLKOcB8iIu\nfeNZmzMX2KUhtN6Xw+5pPWHMCg/lip7CN6dRw6HOzN7BA1OBq6DsBlGEUTu5d/0PWK+p5g\neW79IBeSLpf/+j3W7WaxdJpuLB3ZMibO4i4T/O5k1ODpG06+MhcB5qln+bpa/w==\n---- END SSH2 PUBLIC KEY ----";
+
+  #[test]
+  fn parses_openssh_line() {
+    let key = PublicKey::parse(ED25519).unwrap();
+
+    assert_eq!(key.algorithm(), "ssh-ed25519");
+    assert_eq!(key.bits(), 256);
+    assert_eq!(key.comment(), "ed25519 test key");
+    assert_eq!(key.to_line(), ED25519);
+    assert_eq!(key.fingerprint(), "SHA256:csyvy2fgIpmJ2A+exoVztN1kwwXwXp00zL7bqz36/1A");
+  }
+
+  #[test]
+  fn parses_rfc4716_block() {
+    let key = PublicKey::decode(RSA_RFC4716).unwrap();
+
+    assert_eq!(key.algorithm(), "ssh-rsa");
+    assert_eq!(key.bits(), 1024);
+    assert_eq!(key.comment(), "1024-bit RSA, converted by root@vm from OpenSSH");
+    assert_eq!(key.blob(), PublicKey::decode(RSA).unwrap().blob());
+  }
+
+  #[test]
+  fn roundtrips_rfc4716_block() {
+    let mut key = PublicKey::parse(RSA).unwrap();
+    key.comment = "a rather long comment, which does not fit on a single header line of the block".to_string();
+
+    let text = key.to_rfc4716();
+
+    assert!(text.lines().all(|l| { l.len() <= 72 }));
+    assert_eq!(PublicKey::parse_rfc4716(&text), Ok(key));
+  }
+
+  #[test]
+  fn rejects_mismatched_type() {
+    assert!(PublicKey::parse(&ED25519.replace("ssh-ed25519", "ssh-rsa")).is_err());
+    assert!(PublicKey::parse("ssh-ed25519 not-base64").is_err());
+  }
+
+  #[test]
+  fn rejects_unknown_curves() {
+    let mut blob = Cursor::new(Vec::new());
+
+    blob.write_string("ecdsa-sha2-nistp256");
+    blob.write_string("nistp999");
+    blob.write_binary_string(&[4; 65]);
+
+    assert_eq!(PublicKey::from_blob(&blob.into_inner()[..]), Err(Error::UnsupportedKeyType("nistp999".to_string())));
+  }
+
+  #[test]
+  fn verifies_signatures() {
+    let private = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[7; 32]));
+    let public = private.public_key();
+
+    let signature = private.sign("ssh-ed25519", b"data");
+
+    assert!(public.verify(b"data", &signature[..]));
+    assert!(!public.verify(b"other data", &signature[..]));
+  }
+
+  #[test]
+  fn rejects_truncated_blobs() {
+    assert_eq!(PublicKey::parse("ssh-ed25519 AAAA"), Err(Error::InvalidFormat));
+
+    let blob = PublicKey::parse(ED25519).unwrap().blob();
+
+    for n in 0..blob.len() {
+      assert_eq!(PublicKey::from_blob(&blob[..n]), Err(Error::InvalidFormat));
+    }
+
+    let rsa = PublicKey::parse(RSA).unwrap().blob();
+
+    assert_eq!(PublicKey::from_blob(&rsa[..rsa.len() - 1]), Err(Error::InvalidFormat));
+    assert_eq!(PublicKey::from_blob(&[0, 0, 0, 2, 0xff, 0xfe]), Err(Error::InvalidFormat));
+  }
+
+  #[test]
+  fn rejects_truncated_signatures() {
+    let private = PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[7; 32]));
+    let public = private.public_key();
+
+    let signature = private.sign("ssh-ed25519", b"data");
+
+    for n in 0..signature.len() {
+      assert!(!public.verify(b"data", &signature[..n]));
+    }
+
+    let mut oversized = Cursor::new(Vec::new());
+
+    oversized.write_string("ssh-ed25519");
+    oversized.write_uint32(0xffffffff);
+
+    assert!(!public.verify(b"data", &oversized.into_inner()[..]));
+  }
+
+  #[test]
+  fn reports_missing_files() {
+    match PublicKey::read_file(Path::new("/nonexistent/id_ed25519.pub")) {
+      Err(Error::Io(_)) => (),
+      result => panic!("{:?}", result)
+    }
+  }
+}
@@ -0,0 +1,272 @@
+extern crate libc;
+
+use std::io::{Read, Write};
+
+use num::{Zero, One, Integer};
+use num::bigint::{BigInt, ToBigInt};
+
+use bignum;
+use bignum::{to_bn, BIGNUM};
+
+use hash::{Hash, SHA1, SHA256, SHA512};
+
+use sshio::{SSHRead, SSHWrite};
+
+use keys::Error;
+
+/// DER encoded `DigestInfo` prefixes for EMSA-PKCS1-v1_5.
+///
+/// See [RFC 8017, section 9.2](https://tools.ietf.org/html/rfc8017#section-9.2).
+static SHA1_PREFIX: &'static [u8] = &[0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2B, 0x0E, 0x03, 0x02, 0x1A, 0x05, 0x00, 0x04, 0x14];
+static SHA256_PREFIX: &'static [u8] = &[0x30, 0x31, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20];
+static SHA512_PREFIX: &'static [u8] = &[0x30, 0x51, 0x30, 0x0D, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40];
+
+#[repr(C)] struct RSA { _private: [u8; 0] }
+
+const NID_SHA1: libc::c_int = 64;
+const NID_SHA256: libc::c_int = 672;
+const NID_SHA512: libc::c_int = 674;
+
+#[link(name = "crypto")]
+extern {
+  fn RSA_new() -> *mut RSA;
+  fn RSA_free(rsa: *mut RSA);
+  fn RSA_set0_key(rsa: *mut RSA, n: *mut BIGNUM, e: *mut BIGNUM, d: *mut BIGNUM) -> libc::c_int;
+  fn RSA_set0_factors(rsa: *mut RSA, p: *mut BIGNUM, q: *mut BIGNUM) -> libc::c_int;
+  fn RSA_set0_crt_params(rsa: *mut RSA, dmp1: *mut BIGNUM, dmq1: *mut BIGNUM, iqmp: *mut BIGNUM) -> libc::c_int;
+  fn RSA_sign(digest_type: libc::c_int, digest: *const u8, digest_length: libc::c_uint, signature: *mut u8, signature_length: *mut libc::c_uint, rsa: *mut RSA) -> libc::c_int;
+}
+
+/// Signature algorithms for RSA keys, in order of preference.
+pub static SIGNATURE_ALGORITHMS: &'static [&'static str] = &["rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"];
+
+/// An RSA public key, `ssh-rsa`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+  pub e: BigInt,
+  pub n: BigInt
+}
+
+impl PublicKey {
+  /// Reads the key fields, following the `ssh-rsa` name, of a public key
+  /// blob.
+  pub fn read(reader: &mut Read) -> Result<PublicKey, Error> {
+    let mut reader = reader;
+
+    return Ok(PublicKey {
+      e: try!(reader.try_read_mpint()),
+      n: try!(reader.try_read_mpint())
+    });
+  }
+
+  /// Writes the key fields, following the `ssh-rsa` name, of a public key
+  /// blob.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_mpint(&self.e);
+    writer.write_mpint(&self.n);
+  }
+
+  /// Size of the modulus in bits.
+  pub fn bits(&self) -> usize {
+    return self.n.bits();
+  }
+
+  /// Verifies a raw PKCS #1 v1.5 `signature` of `data`.
+  pub fn verify(&self, algorithm: &str, data: &[u8], signature: &[u8]) -> bool {
+    let k = (self.bits() + 7) / 8;
+
+    let s = bignum::from_bytes_be(signature);
+
+    if signature.len() > k || s >= self.n {
+      return false;
+    }
+
+    let m = bignum::mod_exp(&s, &self.e, &self.n);
+
+    return match encode(algorithm, data, k) {
+      Some(em) => bignum::to_bytes_be(&m, k) == em,
+      None => false
+    };
+  }
+}
+
+/// An RSA private key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrivateKey {
+  pub n: BigInt,
+  pub e: BigInt,
+  pub d: BigInt,
+  pub iqmp: BigInt,
+  pub p: BigInt,
+  pub q: BigInt
+}
+
+impl PrivateKey {
+  /// Reads the key fields of an `ssh-rsa` key in an OpenSSH private key
+  /// file, `n`, `e`, `d`, `iqmp`, `p` and `q`.
+  pub fn read(reader: &mut Read) -> Result<PrivateKey, Error> {
+    let mut reader = reader;
+
+    let key = PrivateKey {
+      n: try!(reader.try_read_mpint()),
+      e: try!(reader.try_read_mpint()),
+      d: try!(reader.try_read_mpint()),
+      iqmp: try!(reader.try_read_mpint()),
+      p: try!(reader.try_read_mpint()),
+      q: try!(reader.try_read_mpint())
+    };
+
+    let one: BigInt = One::one();
+
+    if key.p <= one || key.q <= one || &key.p * &key.q != key.n || key.e <= one || key.d <= one || key.iqmp <= Zero::zero() {
+      return Err(Error::InvalidFormat);
+    }
+
+    return Ok(key);
+  }
+
+  /// Writes the key fields of an `ssh-rsa` key in an OpenSSH private key
+  /// file.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_mpint(&self.n);
+    writer.write_mpint(&self.e);
+    writer.write_mpint(&self.d);
+    writer.write_mpint(&self.iqmp);
+    writer.write_mpint(&self.p);
+    writer.write_mpint(&self.q);
+  }
+
+  /// Generates a key with a modulus of `bits` bits and public exponent
+  /// 65537.
+  pub fn generate(bits: usize) -> PrivateKey {
+    assert!(bits % 2 == 0);
+
+    let one: BigInt = One::one();
+    let e = 65537.to_bigint().unwrap();
+
+    loop {
+      let p = bignum::random_prime(bits / 2);
+      let q = bignum::random_prime(bits / 2);
+
+      if p == q {
+        continue;
+      }
+
+      let phi = (&p - &one) * (&q - &one);
+
+      if !e.gcd(&phi).is_one() {
+        continue;
+      }
+
+      let (p, q) = if p > q { (p, q) } else { (q, p) };
+
+      return PrivateKey {
+        n: &p * &q,
+        d: bignum::mod_inverse(&e, &phi),
+        iqmp: bignum::mod_inverse(&q, &p),
+        e: e,
+        p: p,
+        q: q
+      };
+    }
+  }
+
+  pub fn public_key(&self) -> PublicKey {
+    return PublicKey { e: self.e.clone(), n: self.n.clone() };
+  }
+
+  /// Signs `data` with PKCS #1 v1.5, using the hash of `algorithm`, one of
+  /// `SIGNATURE_ALGORITHMS`.
+  ///
+  /// Signing is left to libcrypto, which uses the CRT with blinding, so its
+  /// timing doesn't depend on the private key.
+  pub fn sign(&self, algorithm: &str, data: &[u8]) -> Vec<u8> {
+    let (digest_type, digest) = match algorithm {
+      "ssh-rsa" => (NID_SHA1, digest(&mut SHA1::new(), data)),
+      "rsa-sha2-256" => (NID_SHA256, digest(&mut SHA256::new(), data)),
+      "rsa-sha2-512" => (NID_SHA512, digest(&mut SHA512::new(), data)),
+      _ => panic!("Unsupported RSA signature algorithm: {}", algorithm)
+    };
+
+    let one: BigInt = One::one();
+
+    let dmp1 = bignum::modulo(&self.d, &(&self.p - &one));
+    let dmq1 = bignum::modulo(&self.d, &(&self.q - &one));
+
+    let mut signature = vec![0; (self.n.bits() + 7) / 8];
+    let mut length = 0;
+
+    let signed = unsafe {
+      let rsa = RSA_new();
+
+      // The RSA takes over the BIGNUMs it is given.
+      let signed = !rsa.is_null() &&
+        RSA_set0_key(rsa, to_bn(&self.n), to_bn(&self.e), to_bn(&self.d)) == 1 &&
+        RSA_set0_factors(rsa, to_bn(&self.p), to_bn(&self.q)) == 1 &&
+        RSA_set0_crt_params(rsa, to_bn(&dmp1), to_bn(&dmq1), to_bn(&self.iqmp)) == 1 &&
+        RSA_sign(digest_type, digest.as_ptr(), digest.len() as libc::c_uint, signature.as_mut_ptr(), &mut length, rsa) == 1;
+
+      RSA_free(rsa);
+
+      signed
+    };
+
+    if !signed || length as usize != signature.len() {
+      panic!("Failed to sign with RSA key");
+    }
+
+    return signature;
+  }
+}
+
+/// EMSA-PKCS1-v1_5 encoding of `data` into `k` bytes.
+fn encode(algorithm: &str, data: &[u8], k: usize) -> Option<Vec<u8>> {
+  let (prefix, digest) = match algorithm {
+    "ssh-rsa" => (SHA1_PREFIX, digest(&mut SHA1::new(), data)),
+    "rsa-sha2-256" => (SHA256_PREFIX, digest(&mut SHA256::new(), data)),
+    "rsa-sha2-512" => (SHA512_PREFIX, digest(&mut SHA512::new(), data)),
+    _ => return None
+  };
+
+  let t = prefix.len() + digest.len();
+
+  if k < t + 11 {
+    return None;
+  }
+
+  let mut em = vec![0x00, 0x01];
+  em.extend(vec![0xFF; k - t - 3]);
+  em.push(0x00);
+  em.extend(prefix.iter().cloned());
+  em.extend(digest);
+
+  return Some(em);
+}
+
+fn digest(hash: &mut Hash, data: &[u8]) -> Vec<u8> {
+  hash.update(data);
+  return hash.digest();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::PrivateKey;
+
+  #[test]
+  fn generates_keys_that_sign_and_verify() {
+    let key = PrivateKey::generate(1024);
+
+    assert_eq!(key.n.bits(), 1024);
+
+    for &algorithm in super::SIGNATURE_ALGORITHMS.iter() {
+      let signature = key.sign(algorithm, b"data");
+
+      assert!(key.public_key().verify(algorithm, b"data", &signature[..]));
+      assert!(!key.public_key().verify(algorithm, b"other data", &signature[..]));
+    }
+  }
+}
@@ -1,47 +1,152 @@
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
+
+use byteorder::WriteBytesExt;
 
 use sshio::{SSHRead, SSHWrite};
 
 #[cfg(test)]
 use quickcheck::{Arbitrary, Gen};
 
+/// The method of a user authentication request, with its method specific
+/// fields.
+///
+/// See [RFC 4252](https://tools.ietf.org/html/rfc4252) and, for
+/// `keyboard-interactive`, [RFC 4256](https://tools.ietf.org/html/rfc4256).
 #[derive(Clone, Debug, PartialEq)]
-pub enum AuthenticationRequestMethodName {
-  PublicKey,
-  Password,
-  Hostbased,
-  NoneMethod
+pub enum AuthenticationMethod {
+  NoneMethod,
+  /// A password, and a new one if this is a password change
+  Password {
+    password: String,
+    new_password: Option<String>
+  },
+  /// A public key blob, signed if this is not just a query
+  PublicKey {
+    algorithm: String,
+    public_key: Vec<u8>,
+    signature: Option<Vec<u8>>
+  },
+  /// A host key, signed by the client host, vouching for a user there
+  Hostbased {
+    algorithm: String,
+    public_key: Vec<u8>,
+    host_name: String,
+    host_user_name: String,
+    signature: Vec<u8>
+  },
+  KeyboardInteractive {
+    language: String,
+    submethods: String
+  },
+  /// Any other method, with its fields still encoded
+  Other(String, Vec<u8>)
 }
 
-impl AuthenticationRequestMethodName {
-  pub fn from_str(name: &str) -> AuthenticationRequestMethodName {
+impl AuthenticationMethod {
+  /// Reads the fields of the method called `name`.
+  pub fn read(name: &str, reader: &mut Read) -> AuthenticationMethod {
+    let mut reader = reader;
+
     return match name {
-      "publickey" => AuthenticationRequestMethodName::PublicKey,
-      "password" => AuthenticationRequestMethodName::Password,
-      "hostbased" => AuthenticationRequestMethodName::Hostbased,
-      "none" => AuthenticationRequestMethodName::NoneMethod,
-      _ => panic!("Unknown method name.")
+      "none" => AuthenticationMethod::NoneMethod,
+      "password" => {
+        let change = reader.read_bool();
+        let password = reader.read_string();
+
+        AuthenticationMethod::Password {
+          password: password,
+          new_password: if change { Some(reader.read_string()) } else { None }
+        }
+      }
+      "publickey" => {
+        let has_signature = reader.read_bool();
+        let algorithm = reader.read_string();
+        let public_key = reader.read_binary_string();
+
+        AuthenticationMethod::PublicKey {
+          algorithm: algorithm,
+          public_key: public_key,
+          signature: if has_signature { Some(reader.read_binary_string()) } else { None }
+        }
+      }
+      "hostbased" => AuthenticationMethod::Hostbased {
+        algorithm: reader.read_string(),
+        public_key: reader.read_binary_string(),
+        host_name: reader.read_string(),
+        host_user_name: reader.read_string(),
+        signature: reader.read_binary_string()
+      },
+      "keyboard-interactive" => AuthenticationMethod::KeyboardInteractive {
+        language: reader.read_string(),
+        submethods: reader.read_string()
+      },
+      _ => {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+
+        AuthenticationMethod::Other(name.to_string(), data)
+      }
+    };
+  }
+
+  /// Writes the fields of the method, following its name.
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    match self {
+      &AuthenticationMethod::NoneMethod => {}
+      &AuthenticationMethod::Password { ref password, ref new_password } => {
+        writer.write_bool(new_password.is_some());
+        writer.write_string(password.as_str());
+
+        if let &Some(ref new_password) = new_password {
+          writer.write_string(new_password.as_str());
+        }
+      }
+      &AuthenticationMethod::PublicKey { ref algorithm, ref public_key, ref signature } => {
+        writer.write_bool(signature.is_some());
+        writer.write_string(algorithm.as_str());
+        writer.write_binary_string(&public_key[..]);
+
+        if let &Some(ref signature) = signature {
+          writer.write_binary_string(&signature[..]);
+        }
+      }
+      &AuthenticationMethod::Hostbased { ref algorithm, ref public_key, ref host_name, ref host_user_name, ref signature } => {
+        writer.write_string(algorithm.as_str());
+        writer.write_binary_string(&public_key[..]);
+        writer.write_string(host_name.as_str());
+        writer.write_string(host_user_name.as_str());
+        writer.write_binary_string(&signature[..]);
+      }
+      &AuthenticationMethod::KeyboardInteractive { ref language, ref submethods } => {
+        writer.write_string(language.as_str());
+        writer.write_string(submethods.as_str());
+      }
+      &AuthenticationMethod::Other(_, ref data) => {
+        writer.write_all(&data[..]).unwrap();
+      }
     }
   }
 
-  pub fn to_string(&self) -> &'static str {
+  pub fn name(&self) -> &str {
     return match self {
-      &AuthenticationRequestMethodName::PublicKey => "publickey",
-      &AuthenticationRequestMethodName::Password => "password",
-      &AuthenticationRequestMethodName::Hostbased => "hostbased",
-      &AuthenticationRequestMethodName::NoneMethod => "none"
-    }
+      &AuthenticationMethod::NoneMethod => "none",
+      &AuthenticationMethod::Password { .. } => "password",
+      &AuthenticationMethod::PublicKey { .. } => "publickey",
+      &AuthenticationMethod::Hostbased { .. } => "hostbased",
+      &AuthenticationMethod::KeyboardInteractive { .. } => "keyboard-interactive",
+      &AuthenticationMethod::Other(ref name, _) => name.as_str()
+    };
   }
 }
 
-
-
 /// SSH_MSG_USERAUTH_REQUEST, 50
 #[derive(Clone, Debug, PartialEq)]
 pub struct AuthenticationRequest {
   pub user_name: String,
   pub service_name: String,
-  pub method_name: AuthenticationRequestMethodName
+  pub method: AuthenticationMethod
 }
 
 impl AuthenticationRequest {
@@ -55,7 +160,7 @@ impl AuthenticationRequest {
     return AuthenticationRequest {
       user_name: user_name,
       service_name: service_name,
-      method_name: AuthenticationRequestMethodName::from_str(method_name.as_str())
+      method: AuthenticationMethod::read(method_name.as_str(), reader)
     };
   }
 
@@ -64,27 +169,80 @@ impl AuthenticationRequest {
 
     writer.write_string(self.user_name.as_str());
     writer.write_string(self.service_name.as_str());
-    writer.write_string(self.method_name.to_string());
+    writer.write_string(self.method.name());
+    self.method.write(writer);
+  }
+
+  /// The data a `publickey` or `hostbased` request signs: the session
+  /// identifier followed by the request, up to the signature.
+  ///
+  /// See [RFC 4252, section 7](https://tools.ietf.org/html/rfc4252#section-7)
+  /// and [section 9](https://tools.ietf.org/html/rfc4252#section-9).
+  pub fn signed_data(&self, session_identifier: &[u8]) -> Option<Vec<u8>> {
+    let mut writer = io::Cursor::new(Vec::new());
+
+    writer.write_binary_string(session_identifier);
+    writer.write_u8(50).unwrap();
+    writer.write_string(self.user_name.as_str());
+    writer.write_string(self.service_name.as_str());
+    writer.write_string(self.method.name());
+
+    match self.method {
+      AuthenticationMethod::PublicKey { ref algorithm, ref public_key, .. } => {
+        writer.write_bool(true);
+        writer.write_string(algorithm.as_str());
+        writer.write_binary_string(&public_key[..]);
+      }
+      AuthenticationMethod::Hostbased { ref algorithm, ref public_key, ref host_name, ref host_user_name, .. } => {
+        writer.write_string(algorithm.as_str());
+        writer.write_binary_string(&public_key[..]);
+        writer.write_string(host_name.as_str());
+        writer.write_string(host_user_name.as_str());
+      }
+      _ => return None
+    }
+
+    return Some(writer.into_inner());
   }
 }
 
 #[cfg(test)]
-impl Arbitrary for AuthenticationRequestMethodName {
-  fn arbitrary<G: Gen>(g: &mut G) -> AuthenticationRequestMethodName {
-    let i = g.gen::<usize>() % 4;
-    let s = ["publickey", "password", "hostbased", "none"][i];
-    return AuthenticationRequestMethodName::from_str(s);
+impl Arbitrary for AuthenticationMethod {
+  fn arbitrary<G: Gen>(g: &mut G) -> AuthenticationMethod {
+    return match g.gen::<usize>() % 6 {
+      0 => AuthenticationMethod::NoneMethod,
+      1 => AuthenticationMethod::Password {
+        password: Arbitrary::arbitrary(g),
+        new_password: Arbitrary::arbitrary(g)
+      },
+      2 => AuthenticationMethod::PublicKey {
+        algorithm: Arbitrary::arbitrary(g),
+        public_key: Arbitrary::arbitrary(g),
+        signature: Arbitrary::arbitrary(g)
+      },
+      3 => AuthenticationMethod::Hostbased {
+        algorithm: Arbitrary::arbitrary(g),
+        public_key: Arbitrary::arbitrary(g),
+        host_name: Arbitrary::arbitrary(g),
+        host_user_name: Arbitrary::arbitrary(g),
+        signature: Arbitrary::arbitrary(g)
+      },
+      4 => AuthenticationMethod::KeyboardInteractive {
+        language: Arbitrary::arbitrary(g),
+        submethods: Arbitrary::arbitrary(g)
+      },
+      _ => AuthenticationMethod::Other("gssapi-with-mic".to_string(), Arbitrary::arbitrary(g))
+    };
   }
 }
 
-
 #[cfg(test)]
 impl Arbitrary for AuthenticationRequest {
   fn arbitrary<G: Gen>(g: &mut G) -> AuthenticationRequest {
     return AuthenticationRequest {
       user_name: Arbitrary::arbitrary(g),
       service_name: Arbitrary::arbitrary(g),
-      method_name: Arbitrary::arbitrary(g)
+      method: Arbitrary::arbitrary(g)
     };
   }
 }
@@ -93,7 +251,7 @@ impl Arbitrary for AuthenticationRequest {
 mod tests {
   use std::io::Cursor;
 
-  use super::AuthenticationRequest;
+  use super::{AuthenticationMethod, AuthenticationRequest};
 
   #[quickcheck]
   fn roundtrips(packet: AuthenticationRequest) -> bool {
@@ -105,4 +263,25 @@ mod tests {
 
     return packet == AuthenticationRequest::read(&mut reader);
   }
+
+  #[test]
+  fn reads_password_change() {
+    let data = b"\x00\x00\x00\x04user\x00\x00\x00\x0essh-connection\x00\x00\x00\x08password\x01\x00\x00\x00\x03old\x00\x00\x00\x03new";
+
+    let packet = AuthenticationRequest::read(&mut Cursor::new(&data[..]));
+
+    assert_eq!(packet.method, AuthenticationMethod::Password {
+      password: "old".to_string(),
+      new_password: Some("new".to_string())
+    });
+  }
+
+  #[test]
+  fn keeps_unknown_methods() {
+    let data = b"\x00\x00\x00\x04user\x00\x00\x00\x0essh-connection\x00\x00\x00\x0fgssapi-with-mic\x01\x02\x03";
+
+    let packet = AuthenticationRequest::read(&mut Cursor::new(&data[..]));
+
+    assert_eq!(packet.method, AuthenticationMethod::Other("gssapi-with-mic".to_string(), vec![1, 2, 3]));
+  }
 }
@@ -0,0 +1,169 @@
+use std::io::{Read, Write};
+
+use sshio::{SSHRead, SSHWrite};
+
+#[cfg(test)]
+use quickcheck::{Arbitrary, Gen};
+
+/// SSH_MSG_USERAUTH_FAILURE, 51
+///
+/// Lists the methods that may productively continue authentication. With
+/// `partial_success`, the request succeeded, but more methods are required.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthenticationFailure {
+  pub methods: Vec<String>,
+  pub partial_success: bool
+}
+
+impl AuthenticationFailure {
+  pub fn read(reader: &mut Read) -> AuthenticationFailure {
+    let mut reader = reader;
+
+    return AuthenticationFailure {
+      methods: reader.read_name_list(),
+      partial_success: reader.read_bool()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_name_list(&self.methods);
+    writer.write_bool(self.partial_success);
+  }
+}
+
+/// SSH_MSG_USERAUTH_SUCCESS, 52
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthenticationSuccess;
+
+impl AuthenticationSuccess {
+  pub fn read(_: &mut Read) -> AuthenticationSuccess {
+    return AuthenticationSuccess;
+  }
+
+  pub fn write(&self, _: &mut Write) {
+  }
+}
+
+/// SSH_MSG_USERAUTH_BANNER, 53
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthenticationBanner {
+  pub message: String,
+  pub language: String
+}
+
+impl AuthenticationBanner {
+  pub fn read(reader: &mut Read) -> AuthenticationBanner {
+    let mut reader = reader;
+
+    return AuthenticationBanner {
+      message: reader.read_string(),
+      language: reader.read_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.message.as_str());
+    writer.write_string(self.language.as_str());
+  }
+}
+
+/// SSH_MSG_USERAUTH_PK_OK, 60 in answer to a `publickey` query
+///
+/// The server would accept a signature by this key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PublicKeyOk {
+  pub algorithm: String,
+  pub public_key: Vec<u8>
+}
+
+impl PublicKeyOk {
+  pub fn read(reader: &mut Read) -> PublicKeyOk {
+    let mut reader = reader;
+
+    return PublicKeyOk {
+      algorithm: reader.read_string(),
+      public_key: reader.read_binary_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.algorithm.as_str());
+    writer.write_binary_string(&self.public_key[..]);
+  }
+}
+
+/// SSH_MSG_USERAUTH_PASSWD_CHANGEREQ, 60 in answer to a `password` request
+///
+/// The password has expired, and the client should send a new one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PasswordChangeRequest {
+  pub prompt: String,
+  pub language: String
+}
+
+impl PasswordChangeRequest {
+  pub fn read(reader: &mut Read) -> PasswordChangeRequest {
+    let mut reader = reader;
+
+    return PasswordChangeRequest {
+      prompt: reader.read_string(),
+      language: reader.read_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.prompt.as_str());
+    writer.write_string(self.language.as_str());
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for AuthenticationBanner {
+  fn arbitrary<G: Gen>(g: &mut G) -> AuthenticationBanner {
+    return AuthenticationBanner {
+      message: Arbitrary::arbitrary(g),
+      language: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::{AuthenticationBanner, AuthenticationFailure};
+
+  #[test]
+  fn roundtrips_failure() {
+    for methods in [vec![], vec!["publickey".to_string(), "password".to_string()]].iter() {
+      let packet = AuthenticationFailure { methods: methods.clone(), partial_success: true };
+
+      let mut writer = Cursor::new(Vec::new());
+
+      packet.write(&mut writer);
+
+      let mut reader = Cursor::new(writer.into_inner());
+
+      assert_eq!(packet, AuthenticationFailure::read(&mut reader));
+    }
+  }
+
+  #[quickcheck]
+  fn roundtrips_banner(packet: AuthenticationBanner) -> bool {
+    let mut writer = Cursor::new(Vec::new());
+
+    packet.write(&mut writer);
+
+    let mut reader = Cursor::new(writer.into_inner());
+
+    return packet == AuthenticationBanner::read(&mut reader);
+  }
+}
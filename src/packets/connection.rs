@@ -0,0 +1,479 @@
+use std::io::{Read, Write};
+
+use sshio::{SSHRead, SSHWrite};
+
+#[cfg(test)]
+use quickcheck::{Arbitrary, Gen};
+
+/// Reason codes of `ChannelOpenFailure`.
+pub const ADMINISTRATIVELY_PROHIBITED: u32 = 1;
+pub const CONNECT_FAILED: u32 = 2;
+pub const UNKNOWN_CHANNEL_TYPE: u32 = 3;
+pub const RESOURCE_SHORTAGE: u32 = 4;
+
+/// The data type code of `ChannelExtendedData` for standard error.
+pub const EXTENDED_DATA_STDERR: u32 = 1;
+
+fn read_rest(reader: &mut Read) -> Vec<u8> {
+  let mut data = Vec::new();
+
+  reader.read_to_end(&mut data).unwrap();
+
+  return data;
+}
+
+/// SSH_MSG_GLOBAL_REQUEST, 80, with the request specific `data` still
+/// encoded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GlobalRequest {
+  pub request_name: String,
+  pub want_reply: bool,
+  pub data: Vec<u8>
+}
+
+impl GlobalRequest {
+  pub fn read(reader: &mut Read) -> GlobalRequest {
+    let mut reader = reader;
+
+    return GlobalRequest {
+      request_name: reader.read_string(),
+      want_reply: reader.read_bool(),
+      data: read_rest(reader)
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.request_name.as_str());
+    writer.write_bool(self.want_reply);
+    writer.write_all(&self.data[..]).unwrap();
+  }
+}
+
+/// SSH_MSG_REQUEST_SUCCESS, 81, with the request specific `data` still
+/// encoded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestSuccess {
+  pub data: Vec<u8>
+}
+
+impl RequestSuccess {
+  pub fn read(reader: &mut Read) -> RequestSuccess {
+    return RequestSuccess { data: read_rest(reader) };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    writer.write_all(&self.data[..]).unwrap();
+  }
+}
+
+/// SSH_MSG_CHANNEL_OPEN, 90, with the channel type specific `data` still
+/// encoded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelOpen {
+  pub channel_type: String,
+  pub sender_channel: u32,
+  pub initial_window_size: u32,
+  pub maximum_packet_size: u32,
+  pub data: Vec<u8>
+}
+
+impl ChannelOpen {
+  pub fn read(reader: &mut Read) -> ChannelOpen {
+    let mut reader = reader;
+
+    return ChannelOpen {
+      channel_type: reader.read_string(),
+      sender_channel: reader.read_uint32(),
+      initial_window_size: reader.read_uint32(),
+      maximum_packet_size: reader.read_uint32(),
+      data: read_rest(reader)
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.channel_type.as_str());
+    writer.write_uint32(self.sender_channel);
+    writer.write_uint32(self.initial_window_size);
+    writer.write_uint32(self.maximum_packet_size);
+    writer.write_all(&self.data[..]).unwrap();
+  }
+}
+
+/// SSH_MSG_CHANNEL_OPEN_CONFIRMATION, 91, with the channel type specific
+/// `data` still encoded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelOpenConfirmation {
+  pub recipient_channel: u32,
+  pub sender_channel: u32,
+  pub initial_window_size: u32,
+  pub maximum_packet_size: u32,
+  pub data: Vec<u8>
+}
+
+impl ChannelOpenConfirmation {
+  pub fn read(reader: &mut Read) -> ChannelOpenConfirmation {
+    let mut reader = reader;
+
+    return ChannelOpenConfirmation {
+      recipient_channel: reader.read_uint32(),
+      sender_channel: reader.read_uint32(),
+      initial_window_size: reader.read_uint32(),
+      maximum_packet_size: reader.read_uint32(),
+      data: read_rest(reader)
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_uint32(self.sender_channel);
+    writer.write_uint32(self.initial_window_size);
+    writer.write_uint32(self.maximum_packet_size);
+    writer.write_all(&self.data[..]).unwrap();
+  }
+}
+
+/// SSH_MSG_CHANNEL_OPEN_FAILURE, 92
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelOpenFailure {
+  pub recipient_channel: u32,
+  pub reason_code: u32,
+  pub description: String,
+  pub language: String
+}
+
+impl ChannelOpenFailure {
+  pub fn read(reader: &mut Read) -> ChannelOpenFailure {
+    let mut reader = reader;
+
+    return ChannelOpenFailure {
+      recipient_channel: reader.read_uint32(),
+      reason_code: reader.read_uint32(),
+      description: reader.read_string(),
+      language: reader.read_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_uint32(self.reason_code);
+    writer.write_string(self.description.as_str());
+    writer.write_string(self.language.as_str());
+  }
+}
+
+/// SSH_MSG_CHANNEL_WINDOW_ADJUST, 93
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelWindowAdjust {
+  pub recipient_channel: u32,
+  pub bytes_to_add: u32
+}
+
+impl ChannelWindowAdjust {
+  pub fn read(reader: &mut Read) -> ChannelWindowAdjust {
+    let mut reader = reader;
+
+    return ChannelWindowAdjust {
+      recipient_channel: reader.read_uint32(),
+      bytes_to_add: reader.read_uint32()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_uint32(self.bytes_to_add);
+  }
+}
+
+/// SSH_MSG_CHANNEL_DATA, 94
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelData {
+  pub recipient_channel: u32,
+  pub data: Vec<u8>
+}
+
+impl ChannelData {
+  pub fn read(reader: &mut Read) -> ChannelData {
+    let mut reader = reader;
+
+    return ChannelData {
+      recipient_channel: reader.read_uint32(),
+      data: reader.read_binary_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_binary_string(&self.data[..]);
+  }
+}
+
+/// SSH_MSG_CHANNEL_EXTENDED_DATA, 95
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelExtendedData {
+  pub recipient_channel: u32,
+  pub data_type_code: u32,
+  pub data: Vec<u8>
+}
+
+impl ChannelExtendedData {
+  pub fn read(reader: &mut Read) -> ChannelExtendedData {
+    let mut reader = reader;
+
+    return ChannelExtendedData {
+      recipient_channel: reader.read_uint32(),
+      data_type_code: reader.read_uint32(),
+      data: reader.read_binary_string()
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_uint32(self.data_type_code);
+    writer.write_binary_string(&self.data[..]);
+  }
+}
+
+/// SSH_MSG_CHANNEL_EOF, 96
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelEof {
+  pub recipient_channel: u32
+}
+
+impl ChannelEof {
+  pub fn read(reader: &mut Read) -> ChannelEof {
+    let mut reader = reader;
+
+    return ChannelEof { recipient_channel: reader.read_uint32() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+  }
+}
+
+/// SSH_MSG_CHANNEL_CLOSE, 97
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelClose {
+  pub recipient_channel: u32
+}
+
+impl ChannelClose {
+  pub fn read(reader: &mut Read) -> ChannelClose {
+    let mut reader = reader;
+
+    return ChannelClose { recipient_channel: reader.read_uint32() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+  }
+}
+
+/// SSH_MSG_CHANNEL_REQUEST, 98, with the request specific `data` still
+/// encoded
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelRequest {
+  pub recipient_channel: u32,
+  pub request_type: String,
+  pub want_reply: bool,
+  pub data: Vec<u8>
+}
+
+impl ChannelRequest {
+  pub fn read(reader: &mut Read) -> ChannelRequest {
+    let mut reader = reader;
+
+    return ChannelRequest {
+      recipient_channel: reader.read_uint32(),
+      request_type: reader.read_string(),
+      want_reply: reader.read_bool(),
+      data: read_rest(reader)
+    };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+    writer.write_string(self.request_type.as_str());
+    writer.write_bool(self.want_reply);
+    writer.write_all(&self.data[..]).unwrap();
+  }
+}
+
+/// SSH_MSG_CHANNEL_SUCCESS, 99
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelSuccess {
+  pub recipient_channel: u32
+}
+
+impl ChannelSuccess {
+  pub fn read(reader: &mut Read) -> ChannelSuccess {
+    let mut reader = reader;
+
+    return ChannelSuccess { recipient_channel: reader.read_uint32() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+  }
+}
+
+/// SSH_MSG_CHANNEL_FAILURE, 100
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelFailure {
+  pub recipient_channel: u32
+}
+
+impl ChannelFailure {
+  pub fn read(reader: &mut Read) -> ChannelFailure {
+    let mut reader = reader;
+
+    return ChannelFailure { recipient_channel: reader.read_uint32() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.recipient_channel);
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for ChannelOpen {
+  fn arbitrary<G: Gen>(g: &mut G) -> ChannelOpen {
+    return ChannelOpen {
+      channel_type: Arbitrary::arbitrary(g),
+      sender_channel: Arbitrary::arbitrary(g),
+      initial_window_size: Arbitrary::arbitrary(g),
+      maximum_packet_size: Arbitrary::arbitrary(g),
+      data: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for ChannelOpenConfirmation {
+  fn arbitrary<G: Gen>(g: &mut G) -> ChannelOpenConfirmation {
+    return ChannelOpenConfirmation {
+      recipient_channel: Arbitrary::arbitrary(g),
+      sender_channel: Arbitrary::arbitrary(g),
+      initial_window_size: Arbitrary::arbitrary(g),
+      maximum_packet_size: Arbitrary::arbitrary(g),
+      data: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for ChannelExtendedData {
+  fn arbitrary<G: Gen>(g: &mut G) -> ChannelExtendedData {
+    return ChannelExtendedData {
+      recipient_channel: Arbitrary::arbitrary(g),
+      data_type_code: Arbitrary::arbitrary(g),
+      data: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for ChannelRequest {
+  fn arbitrary<G: Gen>(g: &mut G) -> ChannelRequest {
+    return ChannelRequest {
+      recipient_channel: Arbitrary::arbitrary(g),
+      request_type: Arbitrary::arbitrary(g),
+      want_reply: Arbitrary::arbitrary(g),
+      data: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use packets::SSHPacket;
+
+  use super::{ChannelOpen, ChannelOpenConfirmation, ChannelExtendedData, ChannelRequest};
+  use super::{ChannelOpenFailure, ChannelWindowAdjust, ChannelData, ChannelEof, ChannelClose, ChannelSuccess, ChannelFailure};
+  use super::{GlobalRequest, RequestSuccess};
+
+  macro_rules! test_roundtrip {
+    ($a:ident, $b:expr) => {{
+      let mut writer = Cursor::new(Vec::new());
+
+      $b.write(&mut writer);
+
+      let mut reader = Cursor::new(writer.into_inner());
+
+      return $b == $a::read(&mut reader);
+    }};
+  }
+
+  #[quickcheck]
+  fn open_roundtrips(packet: ChannelOpen) -> bool {
+    test_roundtrip!(ChannelOpen, packet);
+  }
+
+  #[quickcheck]
+  fn open_confirmation_roundtrips(packet: ChannelOpenConfirmation) -> bool {
+    test_roundtrip!(ChannelOpenConfirmation, packet);
+  }
+
+  #[quickcheck]
+  fn extended_data_roundtrips(packet: ChannelExtendedData) -> bool {
+    test_roundtrip!(ChannelExtendedData, packet);
+  }
+
+  #[quickcheck]
+  fn request_roundtrips(packet: ChannelRequest) -> bool {
+    test_roundtrip!(ChannelRequest, packet);
+  }
+
+  #[test]
+  fn reads_message_numbers() {
+    let packets = vec![
+      (80, SSHPacket::GlobalRequest(GlobalRequest { request_name: "keepalive@openssh.com".to_string(), want_reply: true, data: vec![] })),
+      (81, SSHPacket::RequestSuccess(RequestSuccess { data: vec![0, 0, 0, 22] })),
+      (82, SSHPacket::RequestFailure),
+      (92, SSHPacket::ChannelOpenFailure(ChannelOpenFailure { recipient_channel: 1, reason_code: 2, description: "no".to_string(), language: String::new() })),
+      (93, SSHPacket::ChannelWindowAdjust(ChannelWindowAdjust { recipient_channel: 1, bytes_to_add: 1024 })),
+      (94, SSHPacket::ChannelData(ChannelData { recipient_channel: 1, data: vec![1, 2, 3] })),
+      (96, SSHPacket::ChannelEof(ChannelEof { recipient_channel: 1 })),
+      (97, SSHPacket::ChannelClose(ChannelClose { recipient_channel: 1 })),
+      (99, SSHPacket::ChannelSuccess(ChannelSuccess { recipient_channel: 1 })),
+      (100, SSHPacket::ChannelFailure(ChannelFailure { recipient_channel: 1 }))
+    ];
+
+    for (number, packet) in packets {
+      let mut writer = Cursor::new(Vec::new());
+
+      packet.write(&mut writer);
+
+      let data = writer.into_inner();
+
+      assert_eq!(data[0], number);
+      assert_eq!(format!("{:?}", SSHPacket::read(&mut Cursor::new(data))), format!("{:?}", packet));
+    }
+  }
+}
@@ -5,6 +5,12 @@ use sshio::{SSHRead, SSHWrite};
 #[cfg(test)]
 use quickcheck::{Arbitrary, Gen};
 
+/// Reason codes, from [RFC 4250, section 4.2.2](https://tools.ietf.org/html/rfc4250#section-4.2.2).
+pub static PROTOCOL_ERROR: u32 = 2;
+pub static SERVICE_NOT_AVAILABLE: u32 = 7;
+pub static NO_MORE_AUTH_METHODS_AVAILABLE: u32 = 14;
+
+/// SSH_MSG_DISCONNECT, 1
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Disconnect {
   pub reason: u32,
@@ -0,0 +1,146 @@
+use std::io::{Read, Write};
+
+use sshio::{SSHRead, SSHWrite};
+
+#[cfg(test)]
+use quickcheck::{Arbitrary, Gen};
+
+/// A question to ask the user, and whether to show what they type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Prompt {
+  pub prompt: String,
+  pub echo: bool
+}
+
+/// SSH_MSG_USERAUTH_INFO_REQUEST, 60 during `keyboard-interactive`
+///
+/// See [RFC 4256, section 3.2](https://tools.ietf.org/html/rfc4256#section-3.2).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfoRequest {
+  pub name: String,
+  pub instruction: String,
+  pub language: String,
+  pub prompts: Vec<Prompt>
+}
+
+impl InfoRequest {
+  pub fn read(reader: &mut Read) -> InfoRequest {
+    let mut reader = reader;
+
+    let name = reader.read_string();
+    let instruction = reader.read_string();
+    let language = reader.read_string();
+
+    let n = reader.read_uint32();
+    let mut prompts = Vec::new();
+
+    for _ in 0 .. n {
+      let prompt = reader.read_string();
+      let echo = reader.read_bool();
+
+      prompts.push(Prompt { prompt: prompt, echo: echo });
+    }
+
+    return InfoRequest { name: name, instruction: instruction, language: language, prompts: prompts };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.name.as_str());
+    writer.write_string(self.instruction.as_str());
+    writer.write_string(self.language.as_str());
+    writer.write_uint32(self.prompts.len() as u32);
+
+    for prompt in self.prompts.iter() {
+      writer.write_string(prompt.prompt.as_str());
+      writer.write_bool(prompt.echo);
+    }
+  }
+}
+
+/// SSH_MSG_USERAUTH_INFO_RESPONSE, 61
+///
+/// See [RFC 4256, section 3.4](https://tools.ietf.org/html/rfc4256#section-3.4).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfoResponse {
+  pub responses: Vec<String>
+}
+
+impl InfoResponse {
+  pub fn read(reader: &mut Read) -> InfoResponse {
+    let mut reader = reader;
+
+    let n = reader.read_uint32();
+
+    return InfoResponse { responses: (0 .. n).map(|_| { reader.read_string() }).collect() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_uint32(self.responses.len() as u32);
+
+    for response in self.responses.iter() {
+      writer.write_string(response.as_str());
+    }
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for Prompt {
+  fn arbitrary<G: Gen>(g: &mut G) -> Prompt {
+    return Prompt {
+      prompt: Arbitrary::arbitrary(g),
+      echo: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for InfoRequest {
+  fn arbitrary<G: Gen>(g: &mut G) -> InfoRequest {
+    return InfoRequest {
+      name: Arbitrary::arbitrary(g),
+      instruction: Arbitrary::arbitrary(g),
+      language: Arbitrary::arbitrary(g),
+      prompts: Arbitrary::arbitrary(g)
+    };
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for InfoResponse {
+  fn arbitrary<G: Gen>(g: &mut G) -> InfoResponse {
+    return InfoResponse { responses: Arbitrary::arbitrary(g) };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::{InfoRequest, InfoResponse};
+
+  #[quickcheck]
+  fn roundtrips_request(packet: InfoRequest) -> bool {
+    let mut writer = Cursor::new(Vec::new());
+
+    packet.write(&mut writer);
+
+    let mut reader = Cursor::new(writer.into_inner());
+
+    return packet == InfoRequest::read(&mut reader);
+  }
+
+  #[quickcheck]
+  fn roundtrips_response(packet: InfoResponse) -> bool {
+    let mut writer = Cursor::new(Vec::new());
+
+    packet.write(&mut writer);
+
+    let mut reader = Cursor::new(writer.into_inner());
+
+    return packet == InfoResponse::read(&mut reader);
+  }
+}
@@ -4,8 +4,16 @@ pub mod group_exchange;
 pub mod disconnect;
 /// Messages for Key Exchange
 pub mod key_exchange;
+/// Messages for service requests
+pub mod service;
 /// Messages for user authentication requests
 pub mod authentication_request;
+/// Messages answering user authentication requests
+pub mod authentication_response;
+/// Messages of the `keyboard-interactive` authentication method
+pub mod keyboard_interactive;
+/// Messages of the connection protocol, global requests and channels
+pub mod connection;
 
 use std::io::{Read, Write};
 
@@ -20,13 +28,35 @@ use byteorder::{ReadBytesExt, WriteBytesExt};
 #[derive(Debug)]
 pub enum SSHPacket {
   Disconnect(disconnect::Disconnect),
+  ServiceRequest(service::ServiceRequest),
+  ServiceAccept(service::ServiceAccept),
   KeyExchange(key_exchange::KeyExchangeInit),
   NewKeys(key_exchange::NewKeys),
   GroupExchangeRequest(group_exchange::Request),
   GroupExchangeGroup(group_exchange::Group),
   GroupExchangeInit(group_exchange::Init),
   GroupExchangeReply(group_exchange::Reply),
-  AuthenticationRequest(authentication_request::AuthenticationRequest)
+  AuthenticationRequest(authentication_request::AuthenticationRequest),
+  AuthenticationFailure(authentication_response::AuthenticationFailure),
+  AuthenticationSuccess(authentication_response::AuthenticationSuccess),
+  AuthenticationBanner(authentication_response::AuthenticationBanner),
+  /// Messages 60 to 79, whose meaning depends on the authentication method
+  /// in use, with their still encoded fields
+  AuthenticationMethodSpecific(u8, Vec<u8>),
+  GlobalRequest(connection::GlobalRequest),
+  RequestSuccess(connection::RequestSuccess),
+  RequestFailure,
+  ChannelOpen(connection::ChannelOpen),
+  ChannelOpenConfirmation(connection::ChannelOpenConfirmation),
+  ChannelOpenFailure(connection::ChannelOpenFailure),
+  ChannelWindowAdjust(connection::ChannelWindowAdjust),
+  ChannelData(connection::ChannelData),
+  ChannelExtendedData(connection::ChannelExtendedData),
+  ChannelEof(connection::ChannelEof),
+  ChannelClose(connection::ChannelClose),
+  ChannelRequest(connection::ChannelRequest),
+  ChannelSuccess(connection::ChannelSuccess),
+  ChannelFailure(connection::ChannelFailure)
 }
 
 impl SSHPacket {
@@ -35,6 +65,8 @@ impl SSHPacket {
 
     return match t {
       1 => SSHPacket::Disconnect(disconnect::Disconnect::read(reader)),
+      5 => SSHPacket::ServiceRequest(service::ServiceRequest::read(reader)),
+      6 => SSHPacket::ServiceAccept(service::ServiceAccept::read(reader)),
       20 => SSHPacket::KeyExchange(key_exchange::KeyExchangeInit::read(reader)),
       21 => SSHPacket::NewKeys(key_exchange::NewKeys::read(reader)),
       31 => SSHPacket::GroupExchangeGroup(group_exchange::Group::read(reader)),
@@ -42,6 +74,29 @@ impl SSHPacket {
       33 => SSHPacket::GroupExchangeReply(group_exchange::Reply::read(reader)),
       34 => SSHPacket::GroupExchangeRequest(group_exchange::Request::read(reader)),
       50 => SSHPacket::AuthenticationRequest(authentication_request::AuthenticationRequest::read(reader)),
+      51 => SSHPacket::AuthenticationFailure(authentication_response::AuthenticationFailure::read(reader)),
+      52 => SSHPacket::AuthenticationSuccess(authentication_response::AuthenticationSuccess::read(reader)),
+      53 => SSHPacket::AuthenticationBanner(authentication_response::AuthenticationBanner::read(reader)),
+      60 ... 79 => {
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload).unwrap();
+
+        SSHPacket::AuthenticationMethodSpecific(t, payload)
+      }
+      80 => SSHPacket::GlobalRequest(connection::GlobalRequest::read(reader)),
+      81 => SSHPacket::RequestSuccess(connection::RequestSuccess::read(reader)),
+      82 => SSHPacket::RequestFailure,
+      90 => SSHPacket::ChannelOpen(connection::ChannelOpen::read(reader)),
+      91 => SSHPacket::ChannelOpenConfirmation(connection::ChannelOpenConfirmation::read(reader)),
+      92 => SSHPacket::ChannelOpenFailure(connection::ChannelOpenFailure::read(reader)),
+      93 => SSHPacket::ChannelWindowAdjust(connection::ChannelWindowAdjust::read(reader)),
+      94 => SSHPacket::ChannelData(connection::ChannelData::read(reader)),
+      95 => SSHPacket::ChannelExtendedData(connection::ChannelExtendedData::read(reader)),
+      96 => SSHPacket::ChannelEof(connection::ChannelEof::read(reader)),
+      97 => SSHPacket::ChannelClose(connection::ChannelClose::read(reader)),
+      98 => SSHPacket::ChannelRequest(connection::ChannelRequest::read(reader)),
+      99 => SSHPacket::ChannelSuccess(connection::ChannelSuccess::read(reader)),
+      100 => SSHPacket::ChannelFailure(connection::ChannelFailure::read(reader)),
       _ => {
         panic!(format!("Oh noes, unknown packet type {:?}", t));
       }
@@ -54,6 +109,14 @@ impl SSHPacket {
         writer.write_u8(1).unwrap();
         p.write(writer);
       }
+      &SSHPacket::ServiceRequest(ref p) => {
+        writer.write_u8(5).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ServiceAccept(ref p) => {
+        writer.write_u8(6).unwrap();
+        p.write(writer);
+      }
       &SSHPacket::KeyExchange(ref p) => {
         writer.write_u8(20).unwrap();
         p.write(writer);
@@ -82,6 +145,75 @@ impl SSHPacket {
         writer.write_u8(50).unwrap();
         p.write(writer);
       }
+      &SSHPacket::AuthenticationFailure(ref p) => {
+        writer.write_u8(51).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::AuthenticationSuccess(ref p) => {
+        writer.write_u8(52).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::AuthenticationBanner(ref p) => {
+        writer.write_u8(53).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::AuthenticationMethodSpecific(t, ref payload) => {
+        writer.write_u8(t).unwrap();
+        writer.write_all(&payload[..]).unwrap();
+      }
+      &SSHPacket::GlobalRequest(ref p) => {
+        writer.write_u8(80).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::RequestSuccess(ref p) => {
+        writer.write_u8(81).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::RequestFailure => writer.write_u8(82).unwrap(),
+      &SSHPacket::ChannelOpen(ref p) => {
+        writer.write_u8(90).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelOpenConfirmation(ref p) => {
+        writer.write_u8(91).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelOpenFailure(ref p) => {
+        writer.write_u8(92).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelWindowAdjust(ref p) => {
+        writer.write_u8(93).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelData(ref p) => {
+        writer.write_u8(94).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelExtendedData(ref p) => {
+        writer.write_u8(95).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelEof(ref p) => {
+        writer.write_u8(96).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelClose(ref p) => {
+        writer.write_u8(97).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelRequest(ref p) => {
+        writer.write_u8(98).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelSuccess(ref p) => {
+        writer.write_u8(99).unwrap();
+        p.write(writer);
+      }
+      &SSHPacket::ChannelFailure(ref p) => {
+        writer.write_u8(100).unwrap();
+        p.write(writer);
+      }
     }
   }
 }
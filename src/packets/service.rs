@@ -0,0 +1,71 @@
+use std::io::{Read, Write};
+
+use sshio::{SSHRead, SSHWrite};
+
+#[cfg(test)]
+use quickcheck::{Arbitrary, Gen};
+
+/// SSH_MSG_SERVICE_REQUEST, 5
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceRequest {
+  pub service_name: String
+}
+
+impl ServiceRequest {
+  pub fn read(reader: &mut Read) -> ServiceRequest {
+    let mut reader = reader;
+
+    return ServiceRequest { service_name: reader.read_string() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.service_name.as_str());
+  }
+}
+
+/// SSH_MSG_SERVICE_ACCEPT, 6
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceAccept {
+  pub service_name: String
+}
+
+impl ServiceAccept {
+  pub fn read(reader: &mut Read) -> ServiceAccept {
+    let mut reader = reader;
+
+    return ServiceAccept { service_name: reader.read_string() };
+  }
+
+  pub fn write(&self, writer: &mut Write) {
+    let mut writer = writer;
+
+    writer.write_string(self.service_name.as_str());
+  }
+}
+
+#[cfg(test)]
+impl Arbitrary for ServiceRequest {
+  fn arbitrary<G: Gen>(g: &mut G) -> ServiceRequest {
+    return ServiceRequest { service_name: Arbitrary::arbitrary(g) };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use super::ServiceRequest;
+
+  #[quickcheck]
+  fn roundtrips(packet: ServiceRequest) -> bool {
+    let mut writer = Cursor::new(Vec::new());
+
+    packet.write(&mut writer);
+
+    let mut reader = Cursor::new(writer.into_inner());
+
+    return packet == ServiceRequest::read(&mut reader);
+  }
+}
@@ -0,0 +1,59 @@
+/// Matches `text` against a `pattern` where `*` matches any number of
+/// characters and `?` matches exactly one.
+pub fn matches(text: &str, pattern: &str) -> bool {
+  let text: Vec<char> = text.chars().collect();
+  let pattern: Vec<char> = pattern.chars().collect();
+
+  return matches_chars(&text[..], &pattern[..]);
+}
+
+fn matches_chars(text: &[char], pattern: &[char]) -> bool {
+  return match pattern.first() {
+    None => text.is_empty(),
+    Some(&'*') => (0 .. text.len() + 1).any(|i| { matches_chars(&text[i ..], &pattern[1 ..]) }),
+    Some(&'?') => !text.is_empty() && matches_chars(&text[1 ..], &pattern[1 ..]),
+    Some(c) => text.first() == Some(c) && matches_chars(&text[1 ..], &pattern[1 ..])
+  };
+}
+
+/// Matches `text` against a comma separated list of patterns, some of which
+/// may be negated with `!`.
+///
+/// Returns `Some(true)` if a pattern matches and no negated one does,
+/// `Some(false)` if a negated pattern matches and `None` if nothing does.
+pub fn matches_list(text: &str, patterns: &str) -> Option<bool> {
+  let mut matched = None;
+
+  for pattern in patterns.split(',').map(|p| { p.trim() }) {
+    if pattern.starts_with('!') {
+      if matches(text, &pattern[1 ..]) {
+        return Some(false);
+      }
+    } else if matches(text, pattern) {
+      matched = Some(true);
+    }
+  }
+
+  return matched;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{matches, matches_list};
+
+  #[test]
+  fn matches_wildcards() {
+    assert!(matches("host.example.com", "*.example.com"));
+    assert!(matches("10.0.0.1", "10.0.0.?"));
+    assert!(matches("", "*"));
+    assert!(!matches("example.com", "*.example.com"));
+    assert!(!matches("10.0.0.10", "10.0.0.?"));
+  }
+
+  #[test]
+  fn negations_win() {
+    assert_eq!(matches_list("a.example.com", "*.example.com,!a.example.com"), Some(false));
+    assert_eq!(matches_list("b.example.com", "*.example.com,!a.example.com"), Some(true));
+    assert_eq!(matches_list("example.org", "*.example.com"), None);
+  }
+}
@@ -27,12 +27,20 @@ extern crate byteorder;
 #[cfg(test)]
 extern crate quickcheck;
 
-use std::io::{Read, Write};
-
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
 use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
 
 /// The `hash` module defines ways of hashing and digesting
 pub mod hash;
+/// The `cipher` module defines block ciphers and their modes of operation
+pub mod cipher;
 
 /// SSH socket and transport details
 pub mod transport {
@@ -42,21 +50,476 @@ pub mod transport {
   ///
   /// The individual conversation bits go into this module.
   pub mod ssh_transport;
+  /// Host keys, as used by servers
+  pub mod host_key;
+  /// Diffie-Hellman groups from moduli files
+  pub mod moduli;
+  /// OpenSSH known_hosts files
+  pub mod known_hosts;
+}
+
+/// User authentication, on top of the transport
+pub mod authentication {
+  /// Authenticating to a server
+  pub mod client;
+  /// Authenticating clients, as a server
+  pub mod server;
+  /// OpenSSH authorized_keys files
+  pub mod authorized_keys;
+}
+
+/// The ssh-agent protocol
+pub mod agent {
+  /// Messages of the agent protocol
+  pub mod messages;
+  /// Talking to an agent
+  pub mod client;
+  /// Being an agent
+  pub mod server;
+  /// Forwarding agents through sessions
+  pub mod forwarding;
+}
+
+/// The connection protocol, on top of user authentication
+pub mod connection {
+  /// Channels multiplexed over the transport
+  pub mod multiplexer;
+  /// Sessions, running remote commands
+  pub mod session;
+  /// Terminal modes, and the local terminal
+  pub mod terminal;
 }
 
 /// SSH I/O
 mod sshio;
+/// Arithmetic on big integers
+mod bignum;
+/// Base64, as used in key files
+mod base64;
+/// Wildcard patterns, as in OpenSSH configuration files
+mod pattern;
+/// Private and public keys
+pub mod keys;
 /// SSH-related messages, defined as types
 pub mod packets;
 
+use agent::forwarding::AgentChannels;
+use authentication::client::{Method, Prompter};
+use connection::multiplexer::Multiplexer;
+use connection::session::{Exit, Output, Session};
+use connection::terminal::Terminal;
+use packets::keyboard_interactive::Prompt;
+use transport::known_hosts::{self, KnownHosts, Status};
+
+static USAGE: &'static str = "usage: ssh [-A] [-i identity_file] [-l login_name] [-p port] [user@]hostname [command]";
+
+struct Options {
+  forward_agent: bool,
+  identity_files: Vec<PathBuf>,
+  user_name: Option<String>,
+  host: Option<String>,
+  port: u16,
+  command: Option<String>
+}
+
+fn fail(message: &str) -> ! {
+  writeln!(io::stderr(), "{}", message).unwrap();
+  process::exit(255);
+}
+
+fn parse_options() -> Options {
+  let mut options = Options {
+    forward_agent: false,
+    identity_files: Vec::new(),
+    user_name: None,
+    host: None,
+    port: 22,
+    command: None
+  };
+
+  let mut args = env::args().skip(1);
+
+  while let Some(arg) = args.next() {
+    if options.host.is_some() {
+      options.command = Some(match options.command {
+        Some(ref command) => format!("{} {}", command, arg),
+        None => arg
+      });
+
+      continue;
+    }
+
+    let mut value = || {
+      match args.next() {
+        Some(value) => value,
+        None => fail(&format!("option requires an argument -- {}\n{}", &arg[1 ..], USAGE))
+      }
+    };
+
+    match arg.as_str() {
+      "-A" => options.forward_agent = true,
+      "-i" => options.identity_files.push(PathBuf::from(value())),
+      "-l" => options.user_name = Some(value()),
+      "-p" => options.port = match value().parse() {
+        Ok(port) => port,
+        Err(_) => fail("Bad port")
+      },
+      _ if !arg.starts_with('-') => {
+        let mut parts = arg.rsplitn(2, '@');
+        let host = parts.next().unwrap().to_string();
+
+        if let Some(user_name) = parts.next() {
+          options.user_name = Some(user_name.to_string());
+        }
+
+        options.host = Some(host);
+      }
+      _ => fail(USAGE)
+    }
+  }
+
+  return options;
+}
+
+fn ssh_directory() -> PathBuf {
+  return Path::new(&env::var("HOME").unwrap_or(".".to_string())).join(".ssh");
+}
+
+/// Reads a line from standard input, without echo unless `echo`.
+fn read_line(prompt: &str, echo: bool) -> String {
+  write!(io::stderr(), "{}", prompt).unwrap();
+
+  let terminal = if echo { None } else { Terminal::stdin() };
+
+  if let Some(ref terminal) = terminal {
+    terminal.hide_input();
+  }
+
+  let mut line = String::new();
+  io::stdin().read_line(&mut line).unwrap();
+
+  if terminal.is_some() {
+    writeln!(io::stderr(), "").unwrap();
+  }
+
+  return line.trim_right_matches(|c| { c == '\r' || c == '\n' }).to_string();
+}
+
+/// Asks the questions of `keyboard-interactive` on the terminal.
+struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+  fn respond(&mut self, name: &str, instruction: &str, prompts: &[Prompt]) -> Vec<String> {
+    for line in [name, instruction].iter().filter(|l| { !l.is_empty() }) {
+      writeln!(io::stderr(), "{}", line).unwrap();
+    }
+
+    return prompts.iter().map(|p| { read_line(&p.prompt, p.echo) }).collect();
+  }
+
+  fn change_password(&mut self, prompt: &str, _: &str) -> Option<String> {
+    return Some(read_line(&format!("{}\nNew password: ", prompt), false));
+  }
+}
+
+/// Checks the server's host key against `~/.ssh/known_hosts`, asking
+/// whether to trust keys that aren't known yet.
+fn check_host_key(host: &str, port: u16, key: &keys::PublicKey) {
+  let path = ssh_directory().join("known_hosts");
+
+  let known_hosts = match KnownHosts::read_file(&path) {
+    Ok(known_hosts) => known_hosts,
+    Err(ref e) if e.kind() == io::ErrorKind::NotFound => KnownHosts::default(),
+    Err(e) => fail(&format!("{}: {}", path.display(), e))
+  };
+
+  match known_hosts.check(host, port, key) {
+    Status::Trusted => {}
+    Status::Unknown => {
+      writeln!(io::stderr(), "The authenticity of host '{}' can't be established.", known_hosts::host_name(host, port)).unwrap();
+      writeln!(io::stderr(), "{} key fingerprint is {}.", key.algorithm(), key.fingerprint()).unwrap();
+
+      if read_line("Are you sure you want to continue connecting (yes/no)? ", true) != "yes" {
+        fail("Host key verification failed.");
+      }
+
+      let mut key = key.clone();
+      key.comment = String::new();
+
+      let line = format!("{} {}\n", known_hosts::host_name(host, port), key.to_line().trim());
+
+      if OpenOptions::new().create(true).append(true).open(&path).and_then(|mut f| { f.write_all(line.as_bytes()) }).is_err() {
+        writeln!(io::stderr(), "Failed to add the host to {}", path.display()).unwrap();
+      }
+    }
+    Status::Changed => fail(&format!("WARNING: REMOTE HOST IDENTIFICATION HAS CHANGED!\nThe {} key sent by the remote host is {}.\nHost key verification failed.", key.algorithm(), key.fingerprint())),
+    Status::Revoked => fail(&format!("Host key {} {} revoked by file {}", key.algorithm(), key.fingerprint(), path.display())),
+    Status::InvalidCertificate(_) => fail("Host certificate is not valid.\nHost key verification failed.")
+  }
+}
+
+/// Adds the ways to authenticate: the agent's keys, the identity files,
+/// and the server's questions.
+fn add_methods(client: &mut authentication::client::Client, options: &Options) {
+  client.add_method(Method::None);
+
+  if let Some(mut agent) = agent::client::Client::from_env() {
+    for key in agent.identities().unwrap_or(Vec::new()) {
+      client.add_method(Method::Agent(key));
+    }
+
+    client.set_agent(agent);
+  }
+
+  let identity_files = if options.identity_files.is_empty() {
+    ["id_ed25519", "id_ecdsa", "id_rsa"].iter().map(|f| { ssh_directory().join(f) }).filter(|p| { fs::metadata(p).is_ok() }).collect()
+  } else {
+    options.identity_files.clone()
+  };
+
+  for path in identity_files.iter() {
+    let loaded = match keys::read_private_key_file(path, None) {
+      Err(keys::Error::PassphraseRequired) => {
+        keys::read_private_key_file(path, Some(&read_line(&format!("Enter passphrase for key '{}': ", path.display()), false)))
+      }
+      loaded => loaded
+    };
+
+    match loaded {
+      Ok(keys) => for (key, _) in keys {
+        client.add_method(Method::PublicKey(key));
+      },
+      Err(e) => writeln!(io::stderr(), "Can't load key {}: {:?}", path.display(), e).unwrap()
+    }
+  }
+
+  client.set_prompter(Box::new(TerminalPrompter));
+  client.add_method(Method::KeyboardInteractive);
+}
+
+/// Bytes from the server, read on a thread of their own so the terminal can
+/// be read at the same time. Whether the connection ended is kept with them.
+struct Incoming {
+  shared: Arc<(Mutex<(Vec<u8>, bool)>, Condvar)>
+}
+
+impl Incoming {
+  /// Whether bytes have arrived, that the transport has not read yet.
+  fn available(&self) -> bool {
+    return !(self.shared.0).lock().unwrap().0.is_empty();
+  }
+
+  /// Whether the connection was closed, and everything that arrived before
+  /// was read.
+  fn closed(&self) -> bool {
+    let state = (self.shared.0).lock().unwrap();
+
+    return state.1 && state.0.is_empty();
+  }
+}
+
+impl Read for Incoming {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let &(ref lock, ref arrived) = &*self.shared;
+    let mut state = lock.lock().unwrap();
+
+    while state.0.is_empty() && !state.1 {
+      state = arrived.wait(state).unwrap();
+    }
+
+    let n = std::cmp::min(buf.len(), state.0.len());
+
+    buf[.. n].copy_from_slice(&state.0[.. n]);
+    state.0.drain(.. n);
+
+    return Ok(n);
+  }
+}
+
+/// What the relay between the session and the terminal waits for.
+enum Input {
+  Network,
+  Terminal(Vec<u8>),
+  TerminalEof
+}
+
+/// Reads `reader` on a thread, sending what it reads as `Input`s.
+fn spawn_reader<R: Read + Send + 'static, F: Fn(Option<Vec<u8>>) -> bool + Send + 'static>(mut reader: R, send: F) {
+  thread::spawn(move || {
+    let mut buffer = [0; 16384];
+
+    loop {
+      match reader.read(&mut buffer) {
+        Ok(0) | Err(_) => { send(None); return; }
+        Ok(n) => if !send(Some(buffer[.. n].to_vec())) { return; }
+      }
+    }
+  });
+}
+
+/// Relays standard input to the session, and its output to standard output
+/// and error, until it closes. A terminal's size changes are passed on, and
+/// forwarded agent connections served.
+fn relay(session: &mut Session, incoming: &Incoming, inputs: mpsc::Receiver<Input>, terminal: Option<&Terminal>, agent: Option<&mut AgentChannels>) -> Result<(), connection::multiplexer::Error> {
+  let mut agent = agent;
+  let mut size = terminal.map(|t| { t.size() });
+
+  while !session.is_closed() {
+    match inputs.recv_timeout(Duration::from_millis(250)) {
+      Ok(Input::Network) if incoming.closed() => return Err(connection::multiplexer::Error::Closed),
+      Ok(Input::Network) => while incoming.available() && !session.is_closed() {
+        try!(session.process());
+
+        if let Some(ref mut agent) = agent {
+          try!(agent.process(session.multiplexer()));
+        }
+
+        while let Some(output) = try!(session.take_output()) {
+          match output {
+            Output::Stdout(data) => { io::stdout().write_all(&data[..]).unwrap(); io::stdout().flush().unwrap(); }
+            Output::Stderr(data) => io::stderr().write_all(&data[..]).unwrap()
+          }
+        }
+      },
+      Ok(Input::Terminal(data)) => try!(session.write(&data[..])),
+      Ok(Input::TerminalEof) => try!(session.send_eof()),
+      Err(mpsc::RecvTimeoutError::Timeout) => {
+        let new_size = terminal.map(|t| { t.size() });
+
+        if new_size != size {
+          size = new_size;
+          try!(session.window_change(size.unwrap()));
+        }
+      }
+      Err(mpsc::RecvTimeoutError::Disconnected) => break
+    }
+  }
+
+  return Ok(());
+}
+
 fn main() {
-  let mut tcp_socket = TcpStream::connect("127.0.0.1:9001").unwrap();
+  let options = parse_options();
+
+  let host = match options.host {
+    Some(ref host) => host.clone(),
+    None => fail(USAGE)
+  };
+
+  let user_name = options.user_name.clone().or(env::var("USER").ok()).unwrap_or("root".to_string());
+
+  let mut tcp_socket = match TcpStream::connect((host.as_str(), options.port)) {
+    Ok(socket) => socket,
+    Err(e) => fail(&format!("ssh: connect to host {} port {}: {}", host, options.port, e))
+  };
+
+  let shared = Arc::new((Mutex::new((Vec::new(), false)), Condvar::new()));
+  let (sender, inputs) = mpsc::channel();
 
-  let reader = &mut tcp_socket.try_clone().unwrap() as &mut Read;
+  {
+    let shared = shared.clone();
+    let sender = sender.clone();
+
+    spawn_reader(tcp_socket.try_clone().unwrap(), move |data| {
+      let &(ref lock, ref arrived) = &*shared;
+
+      {
+        let mut state = lock.lock().unwrap();
+
+        match data {
+          Some(ref data) => state.0.extend(data.iter().cloned()),
+          None => state.1 = true
+        }
+      }
+
+      arrived.notify_one();
+
+      return sender.send(Input::Network).is_ok();
+    });
+  }
+
+  let mut incoming = Incoming { shared: shared.clone() };
+  let arrived = Incoming { shared: shared };
+
+  let reader = &mut incoming as &mut Read;
   let writer = &mut tcp_socket as &mut Write;
 
   let mut socket = transport::ssh_socket::Socket::new(reader, writer);
   let mut transport = transport::ssh_transport::Transport::new(&mut socket);
 
-  println!("Packet!: {:?}", transport.read());
+  check_host_key(&host, options.port, transport.server_host_key().unwrap());
+
+  let mut client = authentication::client::Client::new(&user_name);
+  add_methods(&mut client, &options);
+
+  let result = client.authenticate(&mut transport);
+
+  for banner in client.banners() {
+    write!(io::stderr(), "{}", banner).unwrap();
+  }
+
+  if result.is_err() {
+    fail(&format!("{}@{}: Permission denied.", user_name, host));
+  }
+
+  let mut multiplexer = Multiplexer::new(&mut transport);
+
+  let mut session = match Session::open(&mut multiplexer) {
+    Ok(session) => session,
+    Err(e) => fail(&format!("Could not open a session: {:?}", e))
+  };
+
+  let mut agent = if options.forward_agent { AgentChannels::from_env() } else { None };
+
+  if agent.is_some() && session.request_agent_forwarding() != Ok(true) {
+    writeln!(io::stderr(), "Agent forwarding request failed").unwrap();
+    agent = None;
+  }
+
+  let terminal = if options.command.is_none() { Terminal::stdin() } else { None };
+
+  let started = match (&options.command, &terminal) {
+    (&Some(ref command), _) => session.exec(command),
+    (&None, &Some(ref terminal)) => {
+      let term = env::var("TERM").unwrap_or("xterm".to_string());
+
+      match session.request_pty(&term, terminal.size(), &terminal.modes()) {
+        Ok(false) => writeln!(io::stderr(), "PTY allocation request failed").unwrap(),
+        Err(e) => fail(&format!("{:?}", e)),
+        Ok(true) => {}
+      }
+
+      session.shell()
+    }
+    (&None, &None) => session.shell()
+  };
+
+  if started != Ok(true) {
+    fail("Could not start the command");
+  }
+
+  if let Some(ref terminal) = terminal {
+    terminal.make_raw();
+  }
+
+  spawn_reader(io::stdin(), move |data| {
+    return sender.send(match data {
+      Some(data) => Input::Terminal(data),
+      None => Input::TerminalEof
+    }).is_ok();
+  });
+
+  let result = relay(&mut session, &arrived, inputs, terminal.as_ref(), agent.as_mut());
+
+  drop(terminal);
+
+  if let Err(e) = result {
+    fail(&format!("Connection to {} closed: {:?}", host, e));
+  }
+
+  match session.exit() {
+    Some(&Exit::Status(status)) => process::exit(status as i32),
+    Some(&Exit::Signal { ref signal_name, ref message, .. }) => fail(&format!("Remote command killed by signal {}{}", signal_name, if message.is_empty() { String::new() } else { format!(": {}", message) })),
+    None => process::exit(255)
+  }
 }
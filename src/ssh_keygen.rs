@@ -0,0 +1,369 @@
+#![feature(libc)]
+#![feature(convert)]
+
+#![cfg_attr(test, feature(plugin))]
+#![cfg_attr(test, plugin(quickcheck_macros))]
+
+//! # ssh-keygen.rs: key generation for ssh.rs
+//!
+//! Generates, inspects and converts keys, with a subset of the options of
+//! OpenSSH's `ssh-keygen`.
+
+extern crate num;
+extern crate rand;
+extern crate byteorder;
+
+#[cfg(test)]
+extern crate quickcheck;
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `hash` module defines ways of hashing and digesting
+pub mod hash;
+/// The `cipher` module defines block ciphers and their modes of operation
+pub mod cipher;
+
+/// SSH I/O
+mod sshio;
+/// Arithmetic on big integers
+mod bignum;
+/// Base64, as used in key files
+mod base64;
+/// Private and public keys
+pub mod keys;
+
+use keys::{PrivateKey, PublicKey};
+use keys::certificate::{self, Certificate, CertificateType};
+
+static USAGE: &'static str = "usage: ssh-keygen [-t rsa | ecdsa | ed25519] [-b bits] [-N new_passphrase] [-C comment] [-f output_keyfile]
+       ssh-keygen -l [-P passphrase] -f input_keyfile
+       ssh-keygen -y [-P passphrase] -f input_keyfile
+       ssh-keygen -s ca_key -I certificate_identity [-h] [-n principals] [-O option] [-V validity_interval] [-z serial_number] file ...";
+
+enum Mode {
+  Generate,
+  Fingerprint,
+  PublicKey,
+  /// Sign public keys with the CA key in the path
+  Sign(PathBuf)
+}
+
+struct Options {
+  mode: Mode,
+  key_type: String,
+  bits: Option<usize>,
+  passphrase: Option<String>,
+  new_passphrase: Option<String>,
+  comment: Option<String>,
+  file: Option<PathBuf>,
+  /// For certificates, the key ID, whether to make host certificates,
+  /// principals, options, the validity interval and the serial number
+  identity: Option<String>,
+  host: bool,
+  principals: Vec<String>,
+  certificate_options: Vec<String>,
+  validity: Option<String>,
+  serial: u64,
+  /// Public keys to sign
+  files: Vec<PathBuf>
+}
+
+fn fail(message: &str) -> ! {
+  writeln!(io::stderr(), "{}", message).unwrap();
+  process::exit(1);
+}
+
+fn parse_options() -> Options {
+  let mut options = Options {
+    mode: Mode::Generate,
+    key_type: "ed25519".to_string(),
+    bits: None,
+    passphrase: None,
+    new_passphrase: None,
+    comment: None,
+    file: None,
+    identity: None,
+    host: false,
+    principals: Vec::new(),
+    certificate_options: Vec::new(),
+    validity: None,
+    serial: 0,
+    files: Vec::new()
+  };
+
+  let mut args = env::args().skip(1);
+
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      match args.next() {
+        Some(value) => value,
+        None => fail(&format!("option requires an argument -- {}\n{}", &arg[1 ..], USAGE))
+      }
+    };
+
+    match arg.as_str() {
+      "-t" => options.key_type = value(),
+      "-b" => options.bits = match value().parse() {
+        Ok(bits) => Some(bits),
+        Err(_) => fail("Bits has bad value")
+      },
+      "-N" => options.new_passphrase = Some(value()),
+      "-P" => options.passphrase = Some(value()),
+      "-C" => options.comment = Some(value()),
+      "-f" => options.file = Some(PathBuf::from(value())),
+      "-l" => options.mode = Mode::Fingerprint,
+      "-y" => options.mode = Mode::PublicKey,
+      "-s" => options.mode = Mode::Sign(PathBuf::from(value())),
+      "-I" => options.identity = Some(value()),
+      "-h" => options.host = true,
+      "-n" => options.principals = value().split(',').map(|p| { p.to_string() }).collect(),
+      "-O" => options.certificate_options.push(value()),
+      "-V" => options.validity = Some(value()),
+      "-z" => options.serial = match value().parse() {
+        Ok(serial) => serial,
+        Err(_) => fail("Invalid serial number")
+      },
+      _ if !arg.starts_with('-') => options.files.push(PathBuf::from(arg.clone())),
+      _ => fail(USAGE)
+    }
+  }
+
+  return options;
+}
+
+/// The default comment, `user@host`.
+fn default_comment() -> String {
+  let user = env::var("USER").unwrap_or("root".to_string());
+
+  let mut host = String::new();
+
+  if File::open("/etc/hostname").and_then(|mut f| { f.read_to_string(&mut host) }).is_err() {
+    host = "localhost".to_string();
+  }
+
+  return format!("{}@{}", user, host.trim());
+}
+
+/// Reads a private key file, with the passphrase given by `-P` if any.
+fn read_private_key(path: &Path, passphrase: Option<&str>) -> (PrivateKey, String) {
+  return match keys::read_private_key_file(path, passphrase) {
+    Ok(mut keys) => keys.remove(0),
+    Err(keys::Error::PassphraseRequired) => fail(&format!("{} is encrypted, give its passphrase with -P", path.display())),
+    Err(keys::Error::WrongPassphrase) => fail("Incorrect passphrase supplied to decrypt private key"),
+    Err(keys::Error::Io(e)) => fail(&format!("{}: {}", path.display(), e)),
+    Err(e) => fail(&format!("Can't load key {}: {:?}", path.display(), e))
+  };
+}
+
+/// Writes a file created with `mode`, replacing an existing one only if
+/// `replace` is set so that private keys are never written through a file
+/// someone else created.
+fn write_file(path: &Path, contents: &str, mode: u32, replace: bool) {
+  let mut open_options = OpenOptions::new();
+
+  open_options.write(true).mode(mode);
+
+  if replace {
+    open_options.create(true).truncate(true);
+  } else {
+    open_options.create_new(true);
+  }
+
+  let result = open_options.open(path).and_then(|mut f| {
+    try!(f.write_all(contents.as_bytes()));
+
+    return if replace { fs::set_permissions(path, fs::Permissions::from_mode(mode)) } else { Ok(()) };
+  });
+
+  match result {
+    Ok(()) => (),
+    Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => fail(&format!("{} already exists.", path.display())),
+    Err(e) => fail(&format!("Saving key \"{}\" failed: {}", path.display(), e))
+  }
+}
+
+fn generate(options: &Options) {
+  let key = match PrivateKey::generate(&options.key_type, options.bits) {
+    Ok(key) => key,
+    Err(keys::Error::InvalidKeySize(bits)) => fail(&format!("Invalid key length {} for {} keys", bits, options.key_type)),
+    Err(_) => fail(&format!("unknown key type {}", options.key_type))
+  };
+
+  let path = match options.file {
+    Some(ref file) => file.clone(),
+    None => {
+      let home = env::var("HOME").unwrap_or(".".to_string());
+
+      Path::new(&home).join(".ssh").join(format!("id_{}", options.key_type))
+    }
+  };
+
+  let public_path = PathBuf::from(format!("{}.pub", path.display()));
+
+  let comment = options.comment.clone().unwrap_or_else(default_comment);
+
+  let passphrase = match options.new_passphrase {
+    Some(ref passphrase) if !passphrase.is_empty() => Some(passphrase.as_str()),
+    _ => None
+  };
+
+  let mut public_key = key.public_key();
+  public_key.comment = comment.clone();
+
+  write_file(&path, &keys::openssh::encode(&key, &comment, passphrase), 0o600, false);
+  write_file(&public_path, &format!("{}\n", public_key.to_line()), 0o644, true);
+
+  println!("Your identification has been saved in {}", path.display());
+  println!("Your public key has been saved in {}", public_path.display());
+  println!("The key fingerprint is:");
+  println!("{} {}", public_key.fingerprint(), comment);
+}
+
+fn fingerprint(options: &Options, path: &Path) {
+  let key = match PublicKey::read_file(path) {
+    Ok(key) => key,
+    Err(keys::Error::Io(e)) => fail(&format!("{}: {}", path.display(), e)),
+    Err(_) => {
+      let (key, comment) = read_private_key(path, options.passphrase.as_ref().map(|p| { p.as_str() }));
+
+      let mut public_key = key.public_key();
+      public_key.comment = comment;
+      public_key
+    }
+  };
+
+  let name = type_name(&key.key);
+
+  let comment = if key.comment.is_empty() { "no comment" } else { key.comment() };
+
+  println!("{} {} {} ({})", key.bits(), key.fingerprint(), comment, name);
+}
+
+/// Applies a `-O` option to a certificate, returning `false` if it is not
+/// one we know.
+fn apply_certificate_option(certificate: &mut Certificate, option: &str) -> bool {
+  let (name, value) = match option.find('=') {
+    Some(i) => (&option[.. i], &option[i + 1 ..]),
+    None => (option, "")
+  };
+
+  match name {
+    "clear" => certificate.extensions.clear(),
+    "force-command" | "source-address" | "verify-required" => {
+      certificate.critical_options.retain(|o| { o.0 != name });
+      certificate.critical_options.push((name.to_string(), value.to_string()));
+    }
+    _ if name.starts_with("no-") => {
+      let extension = format!("permit-{}", &name[3 ..]).replace("permit-x11", "permit-X11");
+
+      certificate.extensions.retain(|e| { e.0 != extension });
+    }
+    _ if name.starts_with("permit-") => {
+      let extension = name.replace("permit-x11", "permit-X11");
+
+      certificate.extensions.retain(|e| { e.0 != extension });
+      certificate.extensions.push((extension, String::new()));
+    }
+    _ if name.starts_with("extension:") => certificate.extensions.push((name[10 ..].to_string(), value.to_string())),
+    _ if name.starts_with("critical:") => certificate.critical_options.push((name[9 ..].to_string(), value.to_string())),
+    _ => return false
+  }
+
+  return true;
+}
+
+/// Signs each public key in `files` with the CA key at `ca_path`, writing
+/// the certificates next to them, `id_ed25519-cert.pub` for
+/// `id_ed25519.pub`.
+fn sign(options: &Options, ca_path: &Path) {
+  let identity = match options.identity {
+    Some(ref identity) => identity.clone(),
+    None => fail("Must specify key id (-I) when certifying")
+  };
+
+  if options.files.is_empty() {
+    fail(USAGE);
+  }
+
+  let (ca, _) = read_private_key(ca_path, options.passphrase.as_ref().map(|p| { p.as_str() }));
+
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+  let (valid_after, valid_before) = match options.validity {
+    Some(ref validity) => match certificate::parse_validity(validity, now) {
+      Some(interval) => interval,
+      None => fail(&format!("Invalid certificate life {}", validity))
+    },
+    None => (0, std::u64::MAX)
+  };
+
+  let certificate_type = if options.host { CertificateType::Host } else { CertificateType::User };
+
+  for path in options.files.iter() {
+    let key = match PublicKey::read_file(path) {
+      Ok(key) => key,
+      Err(e) => fail(&format!("Can't load key {}: {:?}", path.display(), e))
+    };
+
+    let mut certificate = Certificate::new(&key, certificate_type);
+    certificate.key_id = identity.clone();
+    certificate.serial = options.serial;
+    certificate.valid_principals = options.principals.clone();
+    certificate.valid_after = valid_after;
+    certificate.valid_before = valid_before;
+
+    for option in options.certificate_options.iter() {
+      if !apply_certificate_option(&mut certificate, option) {
+        fail(&format!("Unsupported certificate option \"{}\"", option));
+      }
+    }
+
+    certificate.sign(&ca);
+
+    let name = path.display().to_string();
+    let certificate_path = PathBuf::from(format!("{}-cert.pub", name.trim_right_matches(".pub")));
+
+    write_file(&certificate_path, &format!("{}\n", certificate.to_line(&key.comment)), 0o644, true);
+
+    println!("Signed {} key {}: id \"{}\" serial {}", if options.host { "host" } else { "user" }, certificate_path.display(), identity, options.serial);
+  }
+}
+
+/// The key type as `ssh-keygen -l` shows it, like `ED25519` or
+/// `RSA-CERT`.
+fn type_name(key: &keys::Key) -> String {
+  return match key {
+    &keys::Key::Rsa(_) => "RSA".to_string(),
+    &keys::Key::Ecdsa(_) => "ECDSA".to_string(),
+    &keys::Key::Ed25519(_) => "ED25519".to_string(),
+    &keys::Key::Certificate(ref c) => format!("{}-CERT", type_name(&c.key))
+  };
+}
+
+fn main() {
+  let options = parse_options();
+
+  let file = options.file.clone();
+
+  match options.mode {
+    Mode::Generate => generate(&options),
+    Mode::Sign(ref ca_path) => sign(&options, ca_path),
+    Mode::Fingerprint | Mode::PublicKey if file.is_none() => fail(USAGE),
+    Mode::Fingerprint => fingerprint(&options, file.as_ref().unwrap()),
+    Mode::PublicKey => {
+      let path = file.unwrap();
+      let (key, comment) = read_private_key(&path, options.passphrase.as_ref().map(|p| { p.as_str() }));
+
+      let mut public_key = key.public_key();
+      public_key.comment = comment;
+
+      println!("{}", public_key.to_line());
+    }
+  }
+}
@@ -9,7 +9,23 @@ use num::traits::Signed;
 use num::BigInt;
 use num::bigint::Sign;
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+
+/// Data that ends too early, or holds a string that is not UTF-8, found by
+/// the `try_read_` readers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Malformed;
+
+/// Decodes an mpint from its two's complement bytes.
+fn mpint_from_bytes(value: &[u8]) -> BigInt {
+  if value.first().map_or(false, |b| { b & 0x80 == 0x80 }) {
+    let value: Vec<u8> = value.iter().map(|b| { !b }).collect();
+
+    return BigInt::from_bytes_be(Sign::Minus, &value[..]) - &One::one();
+  }
+
+  return BigInt::from_bytes_be(Sign::Plus, value);
+}
 
 pub trait SSHRead : Read {
   fn read_n_into_buffer(&mut self, buffer: &mut [u8]) {
@@ -41,6 +57,10 @@ pub trait SSHRead : Read {
     return self.read_u32::<BigEndian>().unwrap();
   }
 
+  fn read_uint64(&mut self) -> u64 {
+    return self.read_u64::<BigEndian>().unwrap();
+  }
+
   fn read_string(&mut self) -> String {
     let n = self.read_u32::<BigEndian>().unwrap();
 
@@ -55,24 +75,56 @@ pub trait SSHRead : Read {
   }
 
   fn read_mpint(&mut self) -> BigInt {
-    let mut value = self.read_binary_string();
+    return mpint_from_bytes(&self.read_binary_string()[..]);
+  }
 
-    if value.first().unwrap() & 0x80 == 0x80 {
-      for i in 0 .. value.len() {
-        value[i] = !value[i];
-      }
+  fn read_name_list(&mut self) -> Vec<String> {
+    let string = self.read_string();
 
-      let result: BigInt = BigInt::from_bytes_be(Sign::Minus, &value[..]) - &One::one();
+    if string.is_empty() {
+      return Vec::new();
+    }
 
-      return result;
-    } else {
-      return BigInt::from_bytes_be(Sign::Plus, &value[..]);
+    return Vec::from_iter(string.split(',').map(|x| { String::from(x) }));
+  }
+
+  // Readers for data from the other side, which may be anything. They fail
+  // instead of panicking or returning less than asked for.
+
+  fn try_read_n(&mut self, n: u32) -> Result<Vec<u8>, Malformed> {
+    let buffer = self.read_n(n);
+
+    if buffer.len() != n as usize {
+      return Err(Malformed);
     }
+
+    return Ok(buffer);
   }
 
-  fn read_name_list(&mut self) -> Vec<String> {
-    let string = self.read_string();
-    return Vec::from_iter(string.split(',').map(|x| { String::from(x) }));
+  fn try_read_bool(&mut self) -> Result<bool, Malformed> {
+    return Ok(try!(self.try_read_n(1))[0] != 0);
+  }
+
+  fn try_read_uint32(&mut self) -> Result<u32, Malformed> {
+    return Ok(BigEndian::read_u32(&try!(self.try_read_n(4))[..]));
+  }
+
+  fn try_read_uint64(&mut self) -> Result<u64, Malformed> {
+    return Ok(BigEndian::read_u64(&try!(self.try_read_n(8))[..]));
+  }
+
+  fn try_read_binary_string(&mut self) -> Result<Vec<u8>, Malformed> {
+    let n = try!(self.try_read_uint32());
+
+    return self.try_read_n(n);
+  }
+
+  fn try_read_string(&mut self) -> Result<String, Malformed> {
+    return String::from_utf8(try!(self.try_read_binary_string())).map_err(|_| { Malformed });
+  }
+
+  fn try_read_mpint(&mut self) -> Result<BigInt, Malformed> {
+    return Ok(mpint_from_bytes(&try!(self.try_read_binary_string())[..]));
   }
 }
 
@@ -87,6 +139,10 @@ pub trait SSHWrite : Write {
     self.write_u32::<BigEndian>(v).unwrap();
   }
 
+  fn write_uint64(&mut self, v: u64) {
+    self.write_u64::<BigEndian>(v).unwrap();
+  }
+
   fn write_string(&mut self, str: &str) {
     self.write_binary_string(str.as_bytes());
   }
@@ -148,7 +204,7 @@ mod tests {
 
   use num::bigint::ToBigInt;
 
-  use super::{SSHWrite, SSHRead};
+  use super::{Malformed, SSHWrite, SSHRead};
 
   macro_rules! test_roundtrip {
     ($a:expr, $b:expr) => {{
@@ -206,4 +262,15 @@ mod tests {
     test_roundtrip!(149, vec![0x00, 0x00, 0x00, 0x02, 0x00, 0x95]);
     test_roundtrip!(36412, vec![0x00, 0x00, 0x00, 0x03, 0x00, 0x8E, 0x3C]);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn reads_untrusted_data() {
+    assert_eq!(Cursor::new(vec![0, 0, 0, 2, b'h', b'i']).try_read_string(), Ok("hi".to_string()));
+    assert_eq!(Cursor::new(vec![0, 0, 0, 3, b'h', b'i']).try_read_string(), Err(Malformed));
+    assert_eq!(Cursor::new(vec![0, 0, 0, 1, 0xff]).try_read_string(), Err(Malformed));
+    assert_eq!(Cursor::new(vec![0, 0, 1]).try_read_uint32(), Err(Malformed));
+    assert_eq!(Cursor::new(vec![0xff, 0xff, 0xff, 0xff]).try_read_binary_string(), Err(Malformed));
+    assert_eq!(Cursor::new(vec![0, 0, 0, 0]).try_read_mpint(), Ok(0.to_bigint().unwrap()));
+    assert_eq!(Cursor::new(vec![0, 0, 0, 0]).read_mpint(), 0.to_bigint().unwrap());
+  }
+}
@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use keys;
+use keys::PrivateKey;
+
+/// A private host key, used by servers to prove their identity by signing
+/// the exchange hash.
+pub trait HostKey {
+  /// The public key algorithms this key can sign with, in order of
+  /// preference, like `ssh-rsa`.
+  fn algorithms(&self) -> Vec<String>;
+
+  /// The public key blob sent in `group_exchange::Reply`.
+  fn public_key(&self) -> Vec<u8>;
+
+  /// Signs `data` using `algorithm`, returning the encoded signature, a
+  /// string with the algorithm name followed by the signature blob.
+  fn sign(&self, algorithm: &str, data: &[u8]) -> Vec<u8>;
+}
+
+impl HostKey for PrivateKey {
+  fn algorithms(&self) -> Vec<String> {
+    return self.signature_algorithms().iter().map(|a| { a.to_string() }).collect();
+  }
+
+  fn public_key(&self) -> Vec<u8> {
+    return self.public_key_blob();
+  }
+
+  fn sign(&self, algorithm: &str, data: &[u8]) -> Vec<u8> {
+    return PrivateKey::sign(self, algorithm, data);
+  }
+}
+
+/// The host keys of a server, usually one of each type.
+///
+/// Which key is used for a connection depends on the
+/// `server_host_key_algorithms` negotiated with the client.
+pub struct HostKeys<'a> {
+  keys: Vec<Box<HostKey + 'a>>
+}
+
+impl<'a> HostKeys<'a> {
+  pub fn new() -> HostKeys<'a> {
+    return HostKeys { keys: Vec::new() };
+  }
+
+  pub fn add(&mut self, key: Box<HostKey + 'a>) {
+    self.keys.push(key);
+  }
+
+  /// Adds the keys in the OpenSSH or PEM private key file at `path`, like
+  /// `/etc/ssh/ssh_host_ed25519_key`.
+  pub fn load(&mut self, path: &Path) -> Result<(), keys::Error> {
+    for (key, _) in try!(keys::read_private_key_file(path, None)) {
+      self.add(Box::new(key));
+    }
+
+    return Ok(());
+  }
+
+  pub fn is_empty(&self) -> bool {
+    return self.keys.is_empty();
+  }
+
+  /// All algorithms the keys can sign with, for `server_host_key_algorithms`.
+  pub fn algorithms(&self) -> Vec<String> {
+    let mut algorithms: Vec<String> = Vec::new();
+
+    for key in self.keys.iter() {
+      for algorithm in key.algorithms() {
+        if !algorithms.contains(&algorithm) {
+          algorithms.push(algorithm);
+        }
+      }
+    }
+
+    return algorithms;
+  }
+
+  /// The first key that can sign with `algorithm`.
+  pub fn find(&self, algorithm: &str) -> Option<&(HostKey + 'a)> {
+    return self.keys.iter().find(|key| { key.algorithms().iter().any(|a| { a == algorithm }) }).map(|key| { &**key });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use keys::{ecdsa, ed25519, PrivateKey};
+
+  use num::bigint::ToBigInt;
+
+  use super::HostKeys;
+
+  #[test]
+  fn selects_key_by_algorithm() {
+    let mut host_keys = HostKeys::new();
+
+    host_keys.add(Box::new(PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[7u8; 32]))));
+    host_keys.add(Box::new(PrivateKey::Ecdsa(ecdsa::PrivateKey::from_scalar(ecdsa::Curve::NistP256, 42.to_bigint().unwrap()))));
+
+    assert_eq!(host_keys.algorithms(), vec!["ssh-ed25519".to_string(), "ecdsa-sha2-nistp256".to_string()]);
+
+    assert_eq!(host_keys.find("ecdsa-sha2-nistp256").unwrap().algorithms(), vec!["ecdsa-sha2-nistp256".to_string()]);
+    assert_eq!(host_keys.find("ssh-ed25519").unwrap().algorithms(), vec!["ssh-ed25519".to_string()]);
+    assert!(host_keys.find("ssh-rsa").is_none());
+  }
+}
@@ -0,0 +1,304 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64;
+
+use hash::hmac_sha1;
+
+use keys::{Key, PublicKey};
+use keys::certificate::{CertificateType, Invalid};
+use keys::krl::RevocationList;
+
+use pattern;
+
+/// What an entry with a marker is about, instead of a host key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Marker {
+  /// `@cert-authority`, a CA trusted to sign host certificates
+  CertAuthority,
+  /// `@revoked`, a key that must never be accepted
+  Revoked
+}
+
+/// A line of a `known_hosts` file, `[@marker] hosts key [comment]`.
+///
+/// See the SSH_KNOWN_HOSTS FILE FORMAT section of OpenSSH's `sshd(8)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+  pub marker: Option<Marker>,
+  /// Comma separated host patterns, or a hashed host name,
+  /// `|1|salt|hash`
+  pub hosts: String,
+  pub key: PublicKey
+}
+
+impl Entry {
+  pub fn parse(line: &str) -> Option<Entry> {
+    let line = line.trim();
+
+    let (marker, line) = if line.starts_with("@cert-authority ") {
+      (Some(Marker::CertAuthority), &line["@cert-authority ".len() ..])
+    } else if line.starts_with("@revoked ") {
+      (Some(Marker::Revoked), &line["@revoked ".len() ..])
+    } else {
+      (None, line)
+    };
+
+    let line = line.trim_left();
+
+    let i = match line.find(|c| { c == ' ' || c == '\t' }) {
+      Some(i) => i,
+      None => return None
+    };
+
+    return match PublicKey::parse(line[i ..].trim()) {
+      Ok(key) => Some(Entry { marker: marker, hosts: line[.. i].to_string(), key: key }),
+      Err(_) => None
+    };
+  }
+
+  pub fn to_line(&self) -> String {
+    let marker = match self.marker {
+      Some(Marker::CertAuthority) => "@cert-authority ",
+      Some(Marker::Revoked) => "@revoked ",
+      None => ""
+    };
+
+    return format!("{}{} {}", marker, self.hosts, self.key.to_line());
+  }
+
+  /// Whether the entry is for `name`, a host name as returned by
+  /// `host_name`.
+  pub fn matches(&self, name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    if self.hosts.starts_with("|1|") {
+      let mut fields = self.hosts[3 ..].splitn(2, '|');
+
+      return match (fields.next().and_then(base64::decode), fields.next().and_then(base64::decode)) {
+        (Some(salt), Some(hash)) => hmac_sha1(&salt[..], name.as_bytes()) == hash,
+        _ => false
+      };
+    }
+
+    return pattern::matches_list(&name, &self.hosts) == Some(true);
+  }
+}
+
+/// How a host key compares to the known ones.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+  /// The key is known for the host, or is a valid certificate from one of
+  /// its CAs
+  Trusted,
+  /// No key of the type is known for the host
+  Unknown,
+  /// Another key of the type is known for the host, so this one may be an
+  /// impostor's
+  Changed,
+  /// The key, or the CA that certified it, is revoked
+  Revoked,
+  /// The key is a certificate from one of the host's CAs, but not valid
+  InvalidCertificate(Invalid)
+}
+
+/// The host keys in a `known_hosts` file, which clients check servers
+/// against.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KnownHosts {
+  pub entries: Vec<Entry>,
+  /// Keys and certificates to reject even if they would be trusted, like
+  /// OpenSSH's `RevokedHostKeys`
+  pub revocation_list: Option<RevocationList>
+}
+
+impl KnownHosts {
+  /// Parses the lines of a `known_hosts` file, skipping comments and lines
+  /// that can't be parsed.
+  pub fn parse(text: &str) -> KnownHosts {
+    let entries = text.lines().filter(|l| {
+      let l = l.trim();
+
+      !l.is_empty() && !l.starts_with('#')
+    }).filter_map(Entry::parse).collect();
+
+    return KnownHosts { entries: entries, revocation_list: None };
+  }
+
+  pub fn read_file(path: &Path) -> io::Result<KnownHosts> {
+    let mut text = String::new();
+
+    try!(try!(File::open(path)).read_to_string(&mut text));
+
+    return Ok(KnownHosts::parse(&text));
+  }
+
+  /// Checks `key`, offered by `host` on `port`.
+  ///
+  /// A certificate is checked against the host's CAs. If it has none, the
+  /// certified key is checked like a plain key.
+  pub fn check(&self, host: &str, port: u16, key: &PublicKey) -> Status {
+    let name = host_name(host, port);
+
+    let entries: Vec<&Entry> = self.entries.iter().filter(|e| { e.matches(&name) }).collect();
+
+    let mut revoked = vec![key.blob()];
+
+    if let Key::Certificate(ref c) = key.key {
+      revoked.push(c.public_key().blob());
+      revoked.push(c.signature_key.blob());
+    }
+
+    if entries.iter().any(|e| { e.marker == Some(Marker::Revoked) && revoked.contains(&e.key.blob()) }) {
+      return Status::Revoked;
+    }
+
+    if self.revocation_list.as_ref().map_or(false, |l| { l.is_revoked(key) }) {
+      return Status::Revoked;
+    }
+
+    let key = match key.key {
+      Key::Certificate(ref c) => {
+        let ca = c.signature_key.blob();
+
+        if entries.iter().any(|e| { e.marker == Some(Marker::CertAuthority) && e.key.blob() == ca }) {
+          let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+          return match c.validate(CertificateType::Host, Some(&host.to_lowercase()), now) {
+            Ok(()) => Status::Trusted,
+            Err(invalid) => Status::InvalidCertificate(invalid)
+          };
+        }
+
+        c.public_key()
+      }
+      _ => key.clone()
+    };
+
+    let blob = key.blob();
+    let mut status = Status::Unknown;
+
+    for entry in entries.iter().filter(|e| { e.marker.is_none() }) {
+      if entry.key.blob() == blob {
+        return Status::Trusted;
+      }
+
+      if entry.key.algorithm() == key.algorithm() {
+        status = Status::Changed;
+      }
+    }
+
+    return status;
+  }
+
+  /// Adds `key` for `host` on `port`, as a client does when it first
+  /// connects to a host.
+  pub fn add(&mut self, host: &str, port: u16, key: &PublicKey) {
+    self.entries.push(Entry {
+      marker: None,
+      hosts: host_name(host, port),
+      key: PublicKey { key: key.key.clone(), comment: String::new() }
+    });
+  }
+}
+
+/// The name `known_hosts` lists a host under: the host itself on port 22,
+/// otherwise `[host]:port`.
+pub fn host_name(host: &str, port: u16) -> String {
+  let host = host.to_lowercase();
+
+  if port == 22 {
+    return host;
+  }
+
+  return format!("[{}]:{}", host, port);
+}
+
+#[cfg(test)]
+mod tests {
+  use keys::PublicKey;
+  use keys::certificate;
+  use keys::certificate::Invalid;
+  use keys::krl::RevocationList;
+
+  use super::{Entry, KnownHosts, Status};
+
+  static HOST_KEY: &'static str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBPA3k16BQwxGjSEcZHDeY9NQF0tbueDhMbUB/oceQwVu9ZnPVokPXSxVcJiCmMs4w6nEhDS18CuQO98eFOPBe5U=";
+  static OTHER_KEY: &'static str = "ecdsa-sha2-nistp256 AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBCcmyZpBor3Vv/NAy8DdI3c4D/eYnipj4KJjn1Ydpdd/GeKTlYpUR/QwIfQZVvuEAXbSDz3w8oDMZPbwvQfF3bc=";
+
+  fn key(line: &str) -> PublicKey {
+    return PublicKey::parse(line).unwrap();
+  }
+
+  #[test]
+  fn checks_plain_keys() {
+    let known_hosts = KnownHosts::parse(&format!("# comment\nserver.example.com,10.0.0.1 {}\n[other.example.com]:2222 {}\n", HOST_KEY, HOST_KEY));
+
+    assert_eq!(known_hosts.check("Server.example.com", 22, &key(HOST_KEY)), Status::Trusted);
+    assert_eq!(known_hosts.check("10.0.0.1", 22, &key(HOST_KEY)), Status::Trusted);
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(OTHER_KEY)), Status::Changed);
+    assert_eq!(known_hosts.check("other.example.com", 22, &key(HOST_KEY)), Status::Unknown);
+    assert_eq!(known_hosts.check("other.example.com", 2222, &key(HOST_KEY)), Status::Trusted);
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(certificate::tests::CA)), Status::Unknown);
+  }
+
+  #[test]
+  fn matches_hashed_host_names() {
+    let entry = Entry::parse(&format!("|1|MIfG8+62LMYSElXjvf41drkyMqY=|/RkePzuafdmE6g+o55ziSXbiB+M= {}", HOST_KEY)).unwrap();
+
+    assert!(entry.matches("server.example.com"));
+    assert!(!entry.matches("other.example.com"));
+  }
+
+  #[test]
+  fn trusts_certificates_from_host_cas() {
+    let certificate = key(certificate::tests::HOST_CERTIFICATE);
+    let known_hosts = KnownHosts::parse(&format!("@cert-authority *.example.com {}\n", certificate::tests::CA));
+
+    assert_eq!(known_hosts.check("server.example.com", 22, &certificate), Status::Trusted);
+    assert_eq!(known_hosts.check("other.example.com", 22, &certificate), Status::InvalidCertificate(Invalid::WrongPrincipal));
+    assert_eq!(known_hosts.check("server.example.org", 22, &certificate), Status::Unknown);
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(HOST_KEY)), Status::Unknown);
+  }
+
+  #[test]
+  fn checks_certified_keys_without_a_ca() {
+    let certificate = key(certificate::tests::HOST_CERTIFICATE);
+    let known_hosts = KnownHosts::parse(&format!("server.example.com {}\n", HOST_KEY));
+
+    assert_eq!(known_hosts.check("server.example.com", 22, &certificate), Status::Trusted);
+  }
+
+  #[test]
+  fn rejects_revoked_keys() {
+    let certificate = key(certificate::tests::HOST_CERTIFICATE);
+    let known_hosts = KnownHosts::parse(&format!("@cert-authority * {}\n@revoked * {}\n", certificate::tests::CA, HOST_KEY));
+
+    assert_eq!(known_hosts.check("server.example.com", 22, &certificate), Status::Revoked);
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(HOST_KEY)), Status::Revoked);
+  }
+
+  #[test]
+  fn rejects_keys_in_the_revocation_list() {
+    let mut known_hosts = KnownHosts::parse(&format!("server.example.com {}\n", HOST_KEY));
+
+    let mut revocation_list = RevocationList::default();
+    revocation_list.keys.push(key(HOST_KEY).blob());
+
+    known_hosts.revocation_list = Some(revocation_list);
+
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(HOST_KEY)), Status::Revoked);
+    assert_eq!(known_hosts.check("server.example.com", 22, &key(certificate::tests::HOST_CERTIFICATE)), Status::Revoked);
+  }
+
+  #[test]
+  fn adds_keys() {
+    let mut known_hosts = KnownHosts::default();
+    known_hosts.add("Server.example.com", 2222, &key(HOST_KEY));
+
+    assert_eq!(known_hosts.entries[0].to_line(), format!("[server.example.com]:2222 {}", HOST_KEY));
+    assert_eq!(known_hosts.check("server.example.com", 2222, &key(HOST_KEY)), Status::Trusted);
+  }
+}
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use num::Num;
+use num::bigint::BigInt;
+
+use rand;
+use rand::Rng;
+
+/// Safe primes, where `(p - 1) / 2` is prime as well
+const MODULI_TYPE_SAFE: u32 = 2;
+
+/// Set in the tests field if the modulus turned out to be composite
+const MODULI_TESTS_COMPOSITE: u32 = 0x01;
+
+/// A Diffie-Hellman group from a moduli file.
+///
+/// The format is the one of OpenSSH's `/etc/ssh/moduli`, one group per line
+/// with the fields `timestamp type tests trials size generator modulus`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Modulus {
+  pub timestamp: String,
+  pub kind: u32,
+  pub tests: u32,
+  pub trials: u32,
+  /// Size of the modulus in bits
+  ///
+  /// The file stores one less than this, which is the size of the Sophie
+  /// Germain prime `(p - 1) / 2`.
+  pub size: u32,
+  pub generator: BigInt,
+  pub modulus: BigInt
+}
+
+impl Modulus {
+  /// Parses a single line of a moduli file, returning `None` unless it is a
+  /// usable group.
+  pub fn parse(line: &str) -> Option<Modulus> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    if fields.len() != 7 {
+      return None;
+    }
+
+    let kind = match fields[1].parse::<u32>() { Ok(v) => v, Err(_) => return None };
+    let tests = match fields[2].parse::<u32>() { Ok(v) => v, Err(_) => return None };
+    let trials = match fields[3].parse::<u32>() { Ok(v) => v, Err(_) => return None };
+    let size = match fields[4].parse::<u32>() { Ok(v) => v + 1, Err(_) => return None };
+
+    let generator = match BigInt::from_str_radix(fields[5], 16) { Ok(v) => v, Err(_) => return None };
+    let modulus = match BigInt::from_str_radix(fields[6], 16) { Ok(v) => v, Err(_) => return None };
+
+    if kind != MODULI_TYPE_SAFE {
+      return None;
+    }
+
+    if tests & MODULI_TESTS_COMPOSITE != 0 || tests & !MODULI_TESTS_COMPOSITE == 0 {
+      return None;
+    }
+
+    if trials == 0 || modulus.bits() != size as usize {
+      return None;
+    }
+
+    return Some(Modulus {
+      timestamp: fields[0].to_owned(),
+      kind: kind,
+      tests: tests,
+      trials: trials,
+      size: size,
+      generator: generator,
+      modulus: modulus
+    });
+  }
+}
+
+/// Reads groups from a moduli file.
+///
+/// Comments, blank lines and lines that do not describe a usable safe prime
+/// are skipped, like OpenSSH does.
+pub fn read(reader: &mut Read) -> Vec<Modulus> {
+  let reader = BufReader::new(reader);
+
+  let mut moduli = Vec::new();
+
+  for line in reader.lines() {
+    let line = line.unwrap();
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with("#") {
+      continue;
+    }
+
+    if let Some(modulus) = Modulus::parse(line) {
+      moduli.push(modulus);
+    }
+  }
+
+  return moduli;
+}
+
+/// Reads groups from the moduli file at `path`, usually `/etc/ssh/moduli`.
+pub fn read_file(path: &Path) -> Vec<Modulus> {
+  let mut file = File::open(path).unwrap();
+
+  return read(&mut file);
+}
+
+/// Chooses a group for a `group_exchange::Request`.
+///
+/// Only groups between `min` and `max` bits are considered. Of those, the
+/// smallest groups of at least `n` bits are preferred, otherwise the largest.
+/// Ties are broken at random.
+pub fn choose(moduli: &[Modulus], min: u32, n: u32, max: u32) -> Option<&Modulus> {
+  let candidates: Vec<&Modulus> = moduli.iter().filter(|m| { m.size >= min && m.size <= max }).collect();
+
+  let best = candidates.iter().map(|m| { m.size }).filter(|&s| { s >= n }).min()
+    .or(candidates.iter().map(|m| { m.size }).max());
+
+  let best = match best {
+    Some(size) => size,
+    None => return None
+  };
+
+  let candidates: Vec<&Modulus> = candidates.into_iter().filter(|m| { m.size == best }).collect();
+
+  let i = rand::thread_rng().gen_range(0, candidates.len());
+
+  return Some(candidates[i]);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use num::bigint::ToBigInt;
+
+  use super::{read, choose};
+
+  static MODULI: &'static str = "# Time Type Tests Tries Size Generator Modulus
+20150101000000 2 6 100 4 2 17
+20150101000000 2 6 100 4 5 17
+
+20150101000000 2 6 100 5 2 2F
+20150101000000 2 6 100 6 2 53
+20150101000000 2 6 100 7 2 83
+20150101000000 4 6 100 6 2 47
+20150101000000 2 7 100 6 2 47
+20150101000000 2 6 0 6 2 47
+20150101000000 2 6 100 6 2 17
+20150101000000 2 6 100 6 2
+";
+
+  #[test]
+  fn reads_usable_groups() {
+    let moduli = read(&mut Cursor::new(MODULI.as_bytes()));
+
+    assert_eq!(moduli.len(), 5);
+
+    assert_eq!(moduli[0].timestamp, "20150101000000");
+    assert_eq!(moduli[0].size, 5);
+    assert_eq!(moduli[0].generator, 2.to_bigint().unwrap());
+    assert_eq!(moduli[0].modulus, 23.to_bigint().unwrap());
+    assert_eq!(moduli[1].generator, 5.to_bigint().unwrap());
+  }
+
+  #[test]
+  fn chooses_smallest_group_of_preferred_size() {
+    let moduli = read(&mut Cursor::new(MODULI.as_bytes()));
+
+    assert_eq!(choose(&moduli, 5, 6, 8).unwrap().size, 6);
+    assert_eq!(choose(&moduli, 5, 7, 8).unwrap().size, 7);
+    assert_eq!(choose(&moduli, 5, 8, 8).unwrap().size, 8);
+  }
+
+  #[test]
+  fn chooses_largest_group_below_preferred_size() {
+    let moduli = read(&mut Cursor::new(MODULI.as_bytes()));
+
+    assert_eq!(choose(&moduli, 1, 16, 16).unwrap().size, 8);
+    assert_eq!(choose(&moduli, 1, 16, 6).unwrap().size, 6);
+  }
+
+  #[test]
+  fn chooses_nothing_outside_bounds() {
+    let moduli = read(&mut Cursor::new(MODULI.as_bytes()));
+
+    assert!(choose(&moduli, 9, 16, 16).is_none());
+    assert!(choose(&[], 1, 16, 16).is_none());
+  }
+}
@@ -2,11 +2,84 @@
 
 use std::io;
 
+/// The longest identification string allowed, including CR LF.
+///
+/// See [RFC 4253, section 4.2](https://tools.ietf.org/html/rfc4253#section-4.2).
+const MAX_IDENTIFICATION_LENGTH: usize = 255;
+
+/// The longest line a server may send before its identification string.
+const MAX_PREAMBLE_LINE_LENGTH: usize = 8192;
+
+/// How many lines a server may send before its identification string.
+const MAX_PREAMBLE_LINES: usize = 1024;
+
+/// An identification string, `SSH-protoversion-softwareversion SP comments`,
+/// split into its parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identification {
+  pub protocol_version: String,
+  pub software_version: String,
+  pub comments: Option<String>
+}
+
+impl Identification {
+  /// Parses an identification string without the trailing CR LF.
+  pub fn parse(line: &str) -> Identification {
+    if !line.starts_with("SSH-") {
+      panic!("Not an identification string: {:?}", line);
+    }
+
+    let line = &line[4 ..];
+
+    let (versions, comments) = match line.find(' ') {
+      Some(i) => (&line[.. i], Some(line[i + 1 ..].to_owned())),
+      None => (line, None)
+    };
+
+    let (protocol_version, software_version) = match versions.find('-') {
+      Some(i) => (&versions[.. i], &versions[i + 1 ..]),
+      None => panic!("Identification string lacks a software version: {:?}", line)
+    };
+
+    if software_version.is_empty() {
+      panic!("Identification string lacks a software version: {:?}", line);
+    }
+
+    return Identification {
+      protocol_version: protocol_version.to_owned(),
+      software_version: software_version.to_owned(),
+      comments: comments
+    };
+  }
+
+  /// Whether the protocol version is one we can speak, `2.0` or the
+  /// backwards-compatible `1.99`.
+  pub fn is_supported(&self) -> bool {
+    return self.protocol_version == "2.0" || self.protocol_version == "1.99";
+  }
+}
+
+/// Which side of the connection a `Socket` is on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+  Client,
+  Server
+}
+
 /// A Socket can make Version exchange, and this struct holds the result of
 /// that.
+///
+/// `server` and `client` are the identification strings as they go into the
+/// exchange hash, regardless of which side we are.
 pub struct VersionExchange {
   pub server: String,
-  pub client: String
+  pub client: String,
+  /// The server identification string, split into its parts
+  pub server_identification: Identification,
+  /// The client identification string, split into its parts
+  pub client_identification: Identification,
+  /// Lines the server sent before its identification string
+  pub preamble: Vec<String>
 }
 
 /// SSH Socket with a reader and writer.
@@ -14,37 +87,121 @@ pub struct Socket<'a> {
   /// Reader
   reader: &'a mut io::Read,
   /// Writer
-  writer: &'a mut io::Write
+  writer: &'a mut io::Write,
+  /// Which side of the connection we are
+  role: Role,
+  /// Identification string sent to the other side
+  identification: String
 }
 
-static SSH_IDENTIFIER_CLIENT: &'static str = "SSH-2.0-ssh.rs_0.0.1";
+static SSH_IDENTIFIER: &'static str = "SSH-2.0-ssh.rs_0.0.1";
 
 /// Implementation of SSH Socket.
 impl<'a> Socket<'a> {
-  /// Constructs a new `Socket<'a>`, connecting given `reader` and `writer`
-  /// to it.
+  /// Constructs a new client `Socket<'a>`, connecting given `reader` and
+  /// `writer` to it.
   pub fn new(reader: &'a mut io::Read, writer: &'a mut io::Write) -> Socket<'a> {
-    return Socket { reader: reader, writer: writer };
+    return Socket::with_role(reader, writer, Role::Client);
+  }
+
+  /// Constructs a new server `Socket<'a>`, connecting given `reader` and
+  /// `writer` to it.
+  pub fn new_server(reader: &'a mut io::Read, writer: &'a mut io::Write) -> Socket<'a> {
+    return Socket::with_role(reader, writer, Role::Server);
+  }
+
+  fn with_role(reader: &'a mut io::Read, writer: &'a mut io::Write, role: Role) -> Socket<'a> {
+    return Socket {
+      reader: reader,
+      writer: writer,
+      role: role,
+      identification: SSH_IDENTIFIER.to_owned()
+    };
+  }
+
+  /// Which side of the connection this socket is on.
+  pub fn role(&self) -> Role {
+    return self.role;
+  }
+
+  /// Replaces the identification string sent during version exchange, which
+  /// is `SSH-2.0-ssh.rs_0.0.1` unless set.
+  ///
+  /// The string is given without CR LF and must announce protocol version
+  /// `2.0`.
+  pub fn set_identification(&mut self, identification: &str) {
+    let parsed = Identification::parse(identification);
+
+    if parsed.protocol_version != "2.0" {
+      panic!("Identification string must announce version 2.0: {:?}", identification);
+    }
+
+    if identification.len() + 2 > MAX_IDENTIFICATION_LENGTH {
+      panic!("Identification string is too long: {:?}", identification);
+    }
+
+    self.identification = identification.to_owned();
   }
 
-  /// Performs Version exchange with the client, returning the result as a
-  /// struct.
+  /// Performs Version exchange with the other side, returning the result as
+  /// a struct.
+  ///
+  /// Both sides send their identification string right away. A server may
+  /// send other lines before its identification string, those are collected
+  /// into `VersionExchange::preamble`. A client may not.
   pub fn version_exchange(&mut self) -> VersionExchange {
-    let mut client_identifier = SSH_IDENTIFIER_CLIENT.to_owned();
+    let mut local_identifier = self.identification.clone();
+
+    local_identifier.push('\r');
+    local_identifier.push('\n');
 
-    client_identifier.push('\r');
-    client_identifier.push('\n');
+    self.writer.write_all(local_identifier.as_bytes()).unwrap();
+    self.writer.flush().unwrap();
 
-    assert_eq!(self.writer.write(client_identifier.as_bytes()).unwrap(), client_identifier.len());
+    let mut preamble = Vec::new();
 
-    let mut name = read_until(self.reader, '\n');
-    assert_eq!(name.pop(), Some('\r'));
+    let mut name = read_line(self.reader, MAX_PREAMBLE_LINE_LENGTH);
 
-    assert_eq!(&name[0 .. 8], "SSH-2.0-");
+    while !name.starts_with("SSH-") {
+      if self.role == Role::Server {
+        panic!("Client sent a line before its identification string: {:?}", name);
+      }
 
-    return VersionExchange {
-      client: SSH_IDENTIFIER_CLIENT.to_owned(),
-      server: name
+      if preamble.len() == MAX_PREAMBLE_LINES {
+        panic!("Too many lines before the identification string");
+      }
+
+      preamble.push(name);
+      name = read_line(self.reader, MAX_PREAMBLE_LINE_LENGTH);
+    }
+
+    if name.len() + 2 > MAX_IDENTIFICATION_LENGTH {
+      panic!("Identification string is too long: {:?}", name);
+    }
+
+    let remote_identification = Identification::parse(&name);
+
+    if !remote_identification.is_supported() {
+      panic!("Unsupported protocol version: {:?}", remote_identification.protocol_version);
+    }
+
+    let local_identification = Identification::parse(&self.identification);
+
+    return match self.role {
+      Role::Client => VersionExchange {
+        client: self.identification.clone(),
+        server: name,
+        client_identification: local_identification,
+        server_identification: remote_identification,
+        preamble: preamble
+      },
+      Role::Server => VersionExchange {
+        client: name,
+        server: self.identification.clone(),
+        client_identification: remote_identification,
+        server_identification: local_identification,
+        preamble: preamble
+      }
     };
   }
 }
@@ -65,38 +222,65 @@ impl<'a> io::Write for Socket<'a> {
   }
 }
 
-fn read_until(stream: &mut io::Read, stop: char) -> String {
+/// Reads a line terminated by LF, or CR LF, and returns it without the
+/// terminator.
+///
+/// Panics if the stream ends, or if the line, terminator included, is longer
+/// than `limit` bytes.
+fn read_line(stream: &mut io::Read, limit: usize) -> String {
   let mut buffer = [0u8];
-  let mut string = String::new();
+  let mut line = Vec::new();
+
+  loop {
+    match stream.read(&mut buffer) {
+      Ok(0) => panic!("Connection closed during version exchange"),
+      Ok(_) => {},
+      Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => panic!("Couldn't read during version exchange: {:?}", e)
+    }
 
-  let mut c = None;
-  while c != Some(stop) {
-    if let Some(character) = c {
-      string.push(character)
+    if buffer[0] == '\n' as u8 {
+      break;
     }
 
-    let r = stream.read(&mut buffer);
+    line.push(buffer[0]);
 
-    c = match r {
-      Ok(1) => Some(buffer[0] as char),
-      _ => None
-    };
+    if line.len() + 1 > limit {
+      panic!("Line is too long during version exchange");
+    }
   }
 
-  return string;
+  if line.last() == Some(&('\r' as u8)) {
+    line.pop();
+  }
+
+  return String::from_utf8_lossy(&line[..]).into_owned();
 }
 
 #[cfg(test)]
 mod tests {
   use std::io::Cursor;
 
-  use super::Socket;
+  use super::{Socket, VersionExchange};
 
-  use super::SSH_IDENTIFIER_CLIENT;
+  use super::SSH_IDENTIFIER;
 
   static SSH_IDENTIFIER_SERVER: &'static str = "SSH-2.0-OpenSSH_6.2";
   static OLD_IDENTIFIER_SERVER: &'static str = "SSH-1.0-OpenSSH_6.2";
 
+  fn exchange_with(input: &[u8]) -> (VersionExchange, Vec<u8>) {
+    let mut reader = Cursor::new(input.to_owned());
+    let mut writer = Cursor::new(Vec::new());
+
+    let version_exchange = {
+      let mut socket = Socket::new(&mut reader, &mut writer);
+
+      socket.version_exchange()
+    };
+
+    return (version_exchange, writer.into_inner());
+  }
+
   #[test]
   fn does_version_exchange() {
     let mut server_identifier = SSH_IDENTIFIER_SERVER.as_bytes().to_owned();
@@ -113,13 +297,13 @@ mod tests {
       socket.version_exchange()
     };
 
-    let mut client_identifier = SSH_IDENTIFIER_CLIENT.as_bytes().to_owned();
+    let mut client_identifier = SSH_IDENTIFIER.as_bytes().to_owned();
 
     client_identifier.push('\r' as u8);
     client_identifier.push('\n' as u8);
 
     assert_eq!(writer.into_inner(), client_identifier);
-    assert_eq!(version_exchange.client, SSH_IDENTIFIER_CLIENT);
+    assert_eq!(version_exchange.client, SSH_IDENTIFIER);
     assert_eq!(version_exchange.server, SSH_IDENTIFIER_SERVER);
   }
 
@@ -138,4 +322,105 @@ mod tests {
 
     let _ = socket.version_exchange();
   }
+
+  #[test]
+  fn collects_lines_before_identification() {
+    let (version_exchange, _) = exchange_with(b"Welcome!\r\nGo away.\r\nSSH-2.0-OpenSSH_6.2\r\n");
+
+    assert_eq!(version_exchange.preamble, vec!["Welcome!".to_owned(), "Go away.".to_owned()]);
+    assert_eq!(version_exchange.server, SSH_IDENTIFIER_SERVER);
+  }
+
+  #[test]
+  fn accepts_version_1_99() {
+    let (version_exchange, _) = exchange_with(b"SSH-1.99-OpenSSH_3.9\r\n");
+
+    assert_eq!(version_exchange.server, "SSH-1.99-OpenSSH_3.9");
+    assert_eq!(version_exchange.server_identification.protocol_version, "1.99");
+  }
+
+  #[test]
+  fn accepts_bare_line_feed() {
+    let (version_exchange, _) = exchange_with(b"SSH-2.0-OpenSSH_6.2\n");
+
+    assert_eq!(version_exchange.server, SSH_IDENTIFIER_SERVER);
+  }
+
+  #[test]
+  fn splits_software_version_and_comments() {
+    let (version_exchange, _) = exchange_with(b"SSH-2.0-OpenSSH_6.6.1p1 Ubuntu-2ubuntu2\r\n");
+
+    let identification = version_exchange.server_identification;
+
+    assert_eq!(identification.protocol_version, "2.0");
+    assert_eq!(identification.software_version, "OpenSSH_6.6.1p1");
+    assert_eq!(identification.comments, Some("Ubuntu-2ubuntu2".to_owned()));
+  }
+
+  #[test]
+  #[should_panic]
+  fn fails_on_overlong_identification() {
+    let mut server_identifier = b"SSH-2.0-".to_vec();
+
+    server_identifier.extend(vec!['x' as u8; 250]);
+    server_identifier.extend(b"\r\n".iter().cloned());
+
+    let _ = exchange_with(&server_identifier[..]);
+  }
+
+  #[test]
+  #[should_panic]
+  fn fails_if_connection_closes() {
+    let _ = exchange_with(b"SSH-2.0-OpenSSH_6.2");
+  }
+
+  #[test]
+  fn sends_configured_identification() {
+    let mut reader = Cursor::new(b"SSH-2.0-OpenSSH_6.2\r\n".to_vec());
+    let mut writer = Cursor::new(Vec::new());
+
+    let version_exchange = {
+      let mut socket = Socket::new(&mut reader, &mut writer);
+
+      socket.set_identification("SSH-2.0-Provisioner_1.0 build-42");
+      socket.version_exchange()
+    };
+
+    assert_eq!(writer.into_inner(), b"SSH-2.0-Provisioner_1.0 build-42\r\n".to_vec());
+    assert_eq!(version_exchange.client, "SSH-2.0-Provisioner_1.0 build-42");
+  }
+
+  #[test]
+  fn does_server_version_exchange() {
+    let mut reader = Cursor::new(b"SSH-2.0-OpenSSH_6.2\r\n".to_vec());
+    let mut writer = Cursor::new(Vec::new());
+
+    let version_exchange = {
+      let mut socket = Socket::new_server(&mut reader, &mut writer);
+
+      socket.version_exchange()
+    };
+
+    let mut server_identifier = SSH_IDENTIFIER.as_bytes().to_owned();
+
+    server_identifier.push('\r' as u8);
+    server_identifier.push('\n' as u8);
+
+    assert_eq!(writer.into_inner(), server_identifier);
+    assert_eq!(version_exchange.server, SSH_IDENTIFIER);
+    assert_eq!(version_exchange.client, SSH_IDENTIFIER_SERVER);
+    assert_eq!(version_exchange.client_identification.software_version, "OpenSSH_6.2");
+    assert_eq!(version_exchange.server_identification.software_version, "ssh.rs_0.0.1");
+  }
+
+  #[test]
+  #[should_panic]
+  fn server_fails_on_lines_before_client_identification() {
+    let mut reader = Cursor::new(b"Hello!\r\nSSH-2.0-OpenSSH_6.2\r\n".to_vec());
+    let mut writer = Cursor::new(Vec::new());
+
+    let mut socket = Socket::new_server(&mut reader, &mut writer);
+
+    let _ = socket.version_exchange();
+  }
 }
@@ -1,12 +1,14 @@
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
 
 use transport::ssh_socket;
+use transport::ssh_socket::Role;
+use transport::host_key::HostKeys;
+use transport::moduli;
 
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 
-use num::{Zero, One};
-use num::bigint::{BigInt, BigUint, ToBigInt, RandBigInt};
+use num::bigint::{BigInt, ToBigInt, RandBigInt};
 
 use rand;
 use rand::Rng;
@@ -14,27 +16,121 @@ use rand::Rng;
 use packets::SSHPacket;
 use packets::group_exchange;
 use packets::key_exchange;
-use packets::authentication_request;
 
-use hash::{Hash, SHA256};
+use hash::{constant_time_eq, hmac_sha1, Hash, SHA256};
+
+use cipher::{AES, CtrStream};
+
+use keys::PublicKey;
+use keys::certificate;
 
 use sshio::{SSHRead, SSHWrite};
 
+use bignum::mod_exp;
+
+/// Something that SSH packets are read from and written to, usually a
+/// `Transport`.
+///
+/// The layers above the transport, like user authentication, only need
+/// this.
+pub trait PacketStream {
+  fn read(&mut self) -> SSHPacket;
+  fn write(&mut self, packet: &SSHPacket);
+  fn session_identifier(&self) -> Option<&[u8]>;
+}
+
+/// The host key algorithms clients offer, in order of preference.
+static CLIENT_HOST_KEY_ALGORITHMS: &'static [&'static str] = &[
+  "ssh-ed25519-cert-v01@openssh.com",
+  "ecdsa-sha2-nistp256-cert-v01@openssh.com",
+  "ecdsa-sha2-nistp384-cert-v01@openssh.com",
+  "ecdsa-sha2-nistp521-cert-v01@openssh.com",
+  "rsa-sha2-512-cert-v01@openssh.com",
+  "rsa-sha2-256-cert-v01@openssh.com",
+  "ssh-ed25519",
+  "ecdsa-sha2-nistp256",
+  "ecdsa-sha2-nistp384",
+  "ecdsa-sha2-nistp521",
+  "rsa-sha2-512",
+  "rsa-sha2-256",
+  "ssh-rsa"
+];
+
+/// The cipher, the only one offered.
+static CIPHER: &'static str = "aes128-ctr";
+/// The MAC, the only one offered.
+static MAC: &'static str = "hmac-sha1";
+
+const BLOCK_SIZE: usize = 16;
+const KEY_LENGTH: usize = 16;
+const MAC_LENGTH: usize = 20;
+
+/// The largest packet read, with its length but without the MAC.
+const MAXIMUM_PACKET_LENGTH: usize = 256 * 1024;
+
+/// The keys protecting one direction of the connection, after NEWKEYS.
+struct Keys {
+  cipher: CtrStream<AES>,
+  mac_key: Vec<u8>
+}
+
+impl Keys {
+  fn new(iv: &[u8], key: &[u8], mac_key: &[u8]) -> Keys {
+    return Keys {
+      cipher: CtrStream::new(AES::new(&key[.. KEY_LENGTH]), &iv[.. BLOCK_SIZE]),
+      mac_key: mac_key[.. MAC_LENGTH].to_vec()
+    };
+  }
+
+  /// The MAC of the unencrypted `packet` with the `sequence_number`.
+  fn mac(&self, sequence_number: u32, packet: &[u8]) -> Vec<u8> {
+    let mut data = vec![0; 4];
+    BigEndian::write_u32(&mut data[..], sequence_number);
+    data.extend(packet.iter().cloned());
+
+    return hmac_sha1(&self.mac_key[..], &data[..]);
+  }
+}
+
 /// Holds socket, session identifier and version-exchange information.
 pub struct Transport<'a> {
   socket: &'a mut ssh_socket::Socket<'a>,
   session_identifier: Option<Vec<u8>>,
-  version_exchange: ssh_socket::VersionExchange
+  version_exchange: ssh_socket::VersionExchange,
+  /// Keys to prove our identity with, only used by servers
+  host_keys: HostKeys<'a>,
+  /// Groups to offer in group exchange, only used by servers
+  moduli: Vec<moduli::Modulus>,
+  /// The key the server proved its identity with, only used by clients
+  server_host_key: Option<PublicKey>,
+  /// Keys for the packets read and written, once keys were exchanged
+  incoming_keys: Option<Keys>,
+  outgoing_keys: Option<Keys>,
+  /// Sequence numbers of the next packets, counting every packet since the
+  /// connection started
+  incoming_sequence_number: u32,
+  outgoing_sequence_number: u32
 }
 
 impl<'a> Transport<'a> {
+  /// Connects to a server over a client `socket`, performing version and key
+  /// exchange.
   pub fn new(socket: &'a mut ssh_socket::Socket<'a>) -> Transport<'a> {
+    assert_eq!(socket.role(), Role::Client);
+
     let vex = socket.version_exchange();
 
     let mut transport = Transport {
       socket: socket,
       session_identifier: None,
-      version_exchange: vex
+      version_exchange: vex,
+      host_keys: HostKeys::new(),
+      moduli: Vec::new(),
+      server_host_key: None,
+      incoming_keys: None,
+      outgoing_keys: None,
+      incoming_sequence_number: 0,
+      outgoing_sequence_number: 0
     };
 
     let s_kex = match transport.read() {
@@ -48,28 +144,68 @@ impl<'a> Transport<'a> {
     return transport;
   }
 
+  /// Accepts a client over a server `socket`, performing version and key
+  /// exchange.
+  ///
+  /// The server proves its identity with one of `host_keys`, and offers the
+  /// client a group from `moduli` for Diffie-Hellman group exchange.
+  pub fn new_server(socket: &'a mut ssh_socket::Socket<'a>, host_keys: HostKeys<'a>, moduli: Vec<moduli::Modulus>) -> Transport<'a> {
+    assert_eq!(socket.role(), Role::Server);
+    assert!(!host_keys.is_empty(), "A server needs at least one host key");
+
+    let vex = socket.version_exchange();
+
+    let mut transport = Transport {
+      socket: socket,
+      session_identifier: None,
+      version_exchange: vex,
+      host_keys: host_keys,
+      moduli: moduli,
+      server_host_key: None,
+      incoming_keys: None,
+      outgoing_keys: None,
+      incoming_sequence_number: 0,
+      outgoing_sequence_number: 0
+    };
+
+    let s_kex = transport.start_rekey();
+    let c_kex = match transport.read() {
+      SSHPacket::KeyExchange(k) => k,
+      _ => panic!("FIXME: Unhandled message during key exchange")
+    };
+
+    transport.rekey(&c_kex, &s_kex);
+
+    return transport;
+  }
+
   pub fn start_rekey(&mut self) -> key_exchange::KeyExchangeInit {
     let mut rng = rand::thread_rng();
     let mut cookie = [0u8; 16];
     for x in cookie.iter_mut() { *x = rng.gen::<u8>() }
 
     let enc = vec![
-      "aes128-ctr".to_string(),
+      CIPHER.to_string()
     ];
 
     let mac = vec![
-      "hmac-sha1".to_string()
+      MAC.to_string()
     ];
 
     let comp = vec![
       "none".to_string()
     ];
 
+    let host_key_algorithms = match self.socket.role() {
+      Role::Client => CLIENT_HOST_KEY_ALGORITHMS.iter().map(|a| { a.to_string() }).collect(),
+      Role::Server => self.host_keys.algorithms()
+    };
+
     let kex = key_exchange::KeyExchangeInit {
       cookie: cookie,
-      first_kex_packet_follows: true,
+      first_kex_packet_follows: false,
       kex_algorithms: vec!["diffie-hellman-group-exchange-sha256".to_string()],
-      server_host_key_algorithms: vec!["ssh-rsa".to_string()],
+      server_host_key_algorithms: host_key_algorithms,
       encryption_algorithms_client_to_server: enc.clone(),
       encryption_algorithms_server_to_client: enc.clone(),
       mac_algorithms_client_to_server: mac.clone(),
@@ -86,29 +222,59 @@ impl<'a> Transport<'a> {
 
   pub fn rekey(&mut self, kex_c: &key_exchange::KeyExchangeInit, kex_s: &key_exchange::KeyExchangeInit) {
     // TODO: Support other methods than Group Exchange Diffie-Hellman
-    // TODO: Check if Group Exchange is supported by the other side
 
+    match negotiate(&kex_c.kex_algorithms, &kex_s.kex_algorithms) {
+      Some(ref algorithm) if algorithm == "diffie-hellman-group-exchange-sha256" => {},
+      _ => panic!("No common key exchange algorithm")
+    }
+
+    let ciphers = [
+      (&kex_c.encryption_algorithms_client_to_server, &kex_s.encryption_algorithms_client_to_server),
+      (&kex_c.encryption_algorithms_server_to_client, &kex_s.encryption_algorithms_server_to_client)
+    ];
+
+    for &(client, server) in ciphers.iter() {
+      if negotiate(client, server).as_ref().map(|a| { a.as_str() }) != Some(CIPHER) {
+        panic!("No common cipher");
+      }
+    }
+
+    let macs = [
+      (&kex_c.mac_algorithms_client_to_server, &kex_s.mac_algorithms_client_to_server),
+      (&kex_c.mac_algorithms_server_to_client, &kex_s.mac_algorithms_server_to_client)
+    ];
+
+    for &(client, server) in macs.iter() {
+      if negotiate(client, server).as_ref().map(|a| { a.as_str() }) != Some(MAC) {
+        panic!("No common MAC");
+      }
+    }
+
+    match self.socket.role() {
+      Role::Client => self.client_group_exchange(kex_c, kex_s),
+      Role::Server => self.server_group_exchange(kex_c, kex_s)
+    }
+  }
+
+  fn client_group_exchange(&mut self, kex_c: &key_exchange::KeyExchangeInit, kex_s: &key_exchange::KeyExchangeInit) {
     let gex = group_exchange::Request { min: 1024, n: 1024, max: 8192 };
 
     self.write(&SSHPacket::GroupExchangeRequest(gex.clone()));
 
     let geg = match self.read() {
       SSHPacket::GroupExchangeGroup(g) => g,
-      p => {
-        println!("{:?}", p);
-        panic!("Unexpected packet!")
-      }
+      p => panic!("Unexpected packet during group exchange: {:?}", p)
     };
 
     let mut rng = rand::thread_rng();
 
-    let p = geg.p;
+    let p = geg.p.clone();
     let x = rng.gen_bigint_range(&2.to_bigint().unwrap(), &((&p - &1.to_bigint().unwrap()) / &2.to_bigint().unwrap()));
     let e = mod_exp(&geg.g, &x, &p);
 
     let gei = group_exchange::Init { e: e.clone() };
 
-    self.write(&SSHPacket::GroupExchangeInit(gei));
+    self.write(&SSHPacket::GroupExchangeInit(gei.clone()));
 
     let ger = match self.read() {
       SSHPacket::GroupExchangeReply(g) => g,
@@ -117,8 +283,93 @@ impl<'a> Transport<'a> {
 
     let k = mod_exp(&ger.f, &x, &p);
 
-    println!("Shared key: {:?}", k);
+    let h = self.exchange_hash(kex_c, kex_s, &gex, &geg, &gei, &ger, &k);
+
+    let algorithm = match negotiate(&kex_c.server_host_key_algorithms, &kex_s.server_host_key_algorithms) {
+      Some(algorithm) => algorithm,
+      None => panic!("No common host key algorithm")
+    };
+
+    let host_key = match PublicKey::from_blob(&ger.host_key_and_certificates[..]) {
+      Ok(ref key) if key.signature_algorithms().contains(&algorithm.as_str()) => key.clone(),
+      _ => panic!("Server sent a host key that is not {}", algorithm)
+    };
+
+    let signature_algorithm = io::Cursor::new(&ger.signature[..]).read_string();
+
+    if signature_algorithm != certificate::base_algorithm(&algorithm) || !host_key.verify(&h[..], &ger.signature[..]) {
+      panic!("Server's signature of the exchange hash does not verify");
+    }
+
+    self.server_host_key = Some(host_key);
+
+    self.finish_key_exchange(&k, h);
+  }
+
+  fn server_group_exchange(&mut self, kex_c: &key_exchange::KeyExchangeInit, kex_s: &key_exchange::KeyExchangeInit) {
+    let algorithm = match negotiate(&kex_c.server_host_key_algorithms, &kex_s.server_host_key_algorithms) {
+      Some(algorithm) => algorithm,
+      None => panic!("No common host key algorithm")
+    };
+
+    let gex = match self.read() {
+      SSHPacket::GroupExchangeRequest(g) => g,
+      p => panic!("Unexpected packet during group exchange: {:?}", p)
+    };
+
+    if gex.min > gex.n || gex.n > gex.max {
+      panic!("Invalid group exchange request: {:?}", gex);
+    }
+
+    let geg = match moduli::choose(&self.moduli, gex.min, gex.n, gex.max) {
+      Some(m) => group_exchange::Group { p: m.modulus.clone(), g: m.generator.clone() },
+      None => panic!("No group between {} and {} bits", gex.min, gex.max)
+    };
+
+    self.write(&SSHPacket::GroupExchangeGroup(geg.clone()));
+
+    let gei = match self.read() {
+      SSHPacket::GroupExchangeInit(g) => g,
+      _ => panic!("Unexpected packet!")
+    };
+
+    let one = 1.to_bigint().unwrap();
+    let p = geg.p.clone();
+
+    if gei.e <= one || gei.e >= &p - &one {
+      panic!("Client sent e out of range");
+    }
+
+    let mut rng = rand::thread_rng();
+
+    let y = rng.gen_bigint_range(&2.to_bigint().unwrap(), &((&p - &one) / &2.to_bigint().unwrap()));
+    let f = mod_exp(&geg.g, &y, &p);
+    let k = mod_exp(&gei.e, &y, &p);
+
+    let mut ger = group_exchange::Reply {
+      host_key_and_certificates: match self.host_keys.find(&algorithm) {
+        Some(key) => key.public_key(),
+        None => panic!("No host key for {}", algorithm)
+      },
+      f: f,
+      signature: Vec::new()
+    };
+
+    let h = self.exchange_hash(kex_c, kex_s, &gex, &geg, &gei, &ger, &k);
+
+    ger.signature = self.host_keys.find(&algorithm).unwrap().sign(&algorithm, &h[..]);
+
+    self.write(&SSHPacket::GroupExchangeReply(ger));
+
+    self.finish_key_exchange(&k, h);
+  }
 
+  /// Computes the exchange hash, `H`, for Diffie-Hellman group exchange.
+  ///
+  /// See [RFC 4419, section 3](https://tools.ietf.org/html/rfc4419#section-3).
+  fn exchange_hash(&self, kex_c: &key_exchange::KeyExchangeInit, kex_s: &key_exchange::KeyExchangeInit,
+                   gex: &group_exchange::Request, geg: &group_exchange::Group,
+                   gei: &group_exchange::Init, ger: &group_exchange::Reply, k: &BigInt) -> Vec<u8> {
     let mut writer = io::Cursor::new(Vec::new());
 
     writer.write_string(&self.version_exchange.client);
@@ -136,22 +387,26 @@ impl<'a> Transport<'a> {
     writer.write_uint32(gex.min);
     writer.write_uint32(gex.n);
     writer.write_uint32(gex.max);
-    writer.write_mpint(&p);
+    writer.write_mpint(&geg.p);
     writer.write_mpint(&geg.g);
-    writer.write_mpint(&e);
+    writer.write_mpint(&gei.e);
     writer.write_mpint(&ger.f);
-    writer.write_mpint(&k);
+    writer.write_mpint(k);
 
     let buffer = writer.into_inner();
 
-    println!("Buffer: {:?}", buffer);
-
     let mut hash = SHA256::new();
 
     hash.update(&buffer[..]);
 
-    let h = hash.digest();
+    return hash.digest();
+  }
 
+  /// Derives the keys from the shared secret `k` and exchange hash `h`, and
+  /// starts using them once NEWKEYS was sent and received.
+  ///
+  /// See [RFC 4253, section 7.2](https://tools.ietf.org/html/rfc4253#section-7.2).
+  fn finish_key_exchange(&mut self, k: &BigInt, h: Vec<u8>) {
     let session_identifier = match &self.session_identifier {
       &None => h.clone(),
       &Some(ref s) => s.clone()
@@ -159,47 +414,93 @@ impl<'a> Transport<'a> {
 
     self.session_identifier = Some(session_identifier.clone());
 
-    let iv_c2s = generate_key(&mut SHA256::new(), &k, &h[..], b"A", &session_identifier[..]);
-    let iv_s2c = generate_key(&mut SHA256::new(), &k, &h[..], b"B", &session_identifier[..]);
-
-    let enc_key_c2s = generate_key(&mut SHA256::new(), &k, &h[..], b"C", &session_identifier[..]);
-    let enc_key_s2c = generate_key(&mut SHA256::new(), &k, &h[..], b"D", &session_identifier[..]);
+    let derive = |c: &[u8]| { generate_key(&mut SHA256::new(), k, &h[..], c, &session_identifier[..]) };
 
-    let mac_key_c2s = generate_key(&mut SHA256::new(), &k, &h[..], b"E", &session_identifier[..]);
-    let mac_key_s2c = generate_key(&mut SHA256::new(), &k, &h[..], b"F", &session_identifier[..]);
+    let client_to_server = Keys::new(&derive(b"A")[..], &derive(b"C")[..], &derive(b"E")[..]);
+    let server_to_client = Keys::new(&derive(b"B")[..], &derive(b"D")[..], &derive(b"F")[..]);
 
-    println!("Session ID: {:?}", h);
-    println!("IV (c2s): {:?}", iv_c2s);
-    println!("IV (s2c): {:?}", iv_s2c);
-    println!("Key (c2s): {:?}", enc_key_c2s);
-    println!("Key (s2c): {:?}", enc_key_s2c);
-    println!("MAC (c2s): {:?}", mac_key_c2s);
-    println!("MAC (s2c): {:?}", mac_key_s2c);
+    let (outgoing, incoming) = match self.socket.role() {
+      Role::Client => (client_to_server, server_to_client),
+      Role::Server => (server_to_client, client_to_server)
+    };
 
     self.write(&SSHPacket::NewKeys(key_exchange::NewKeys));
+    self.outgoing_keys = Some(outgoing);
+
+    match self.read() {
+      SSHPacket::NewKeys(_) => {},
+      p => panic!("Unexpected packet instead of NEWKEYS: {:?}", p)
+    }
 
-    panic!("Oh noes, sowwy, not implemented :C")
+    self.incoming_keys = Some(incoming);
   }
 
-  /// Reads bytes from the transport socket and returns an `SSHPacket`.
+  /// The exchange hash of the first key exchange, which identifies the
+  /// session, or `None` before the first key exchange has finished.
+  pub fn session_identifier(&self) -> Option<&[u8]> {
+    return self.session_identifier.as_ref().map(|s| { &s[..] });
+  }
+
+  /// The key the server proved its identity with during key exchange, which
+  /// a client should check, say against `known_hosts`, before sending
+  /// anything secret.
+  pub fn server_host_key(&self) -> Option<&PublicKey> {
+    return self.server_host_key.as_ref();
+  }
+
+  /// Reads bytes from the transport socket and returns an `SSHPacket`,
+  /// decrypting it and checking its MAC once keys were exchanged.
   pub fn read(&mut self) -> SSHPacket {
-    let packet_length = self.socket.read_u32::<BigEndian>().unwrap();
-    let padding_length = self.socket.read_u8().unwrap() as u32;
+    let block_size = if self.incoming_keys.is_some() { BLOCK_SIZE } else { 8 };
+
+    let mut packet = vec![0; block_size];
+    self.socket.read_exact(&mut packet[..]).unwrap();
+
+    if let Some(ref mut keys) = self.incoming_keys {
+      packet = keys.cipher.apply(&packet[..]);
+    }
+
+    let packet_length = BigEndian::read_u32(&packet[.. 4]) as usize;
+
+    if packet_length + 4 > MAXIMUM_PACKET_LENGTH || packet_length + 4 < block_size || (packet_length + 4) % block_size != 0 {
+      panic!("Invalid packet length {}", packet_length);
+    }
+
+    let mut rest = vec![0; packet_length + 4 - block_size];
+    self.socket.read_exact(&mut rest[..]).unwrap();
+
+    if let Some(ref mut keys) = self.incoming_keys {
+      rest = keys.cipher.apply(&rest[..]);
+    }
+
+    packet.extend(rest);
+
+    if let Some(ref keys) = self.incoming_keys {
+      let mut mac = vec![0; MAC_LENGTH];
+      self.socket.read_exact(&mut mac[..]).unwrap();
+
+      if !constant_time_eq(&mac[..], &keys.mac(self.incoming_sequence_number, &packet[..])[..]) {
+        panic!("Corrupted MAC on input");
+      }
+    }
+
+    self.incoming_sequence_number = self.incoming_sequence_number.wrapping_add(1);
 
-    let payload = self.socket.read_n(packet_length - padding_length - 1);
-    let padding = self.socket.read_n(padding_length);
-    // let mac = self.read_n(mac_length);
+    let padding_length = packet[4] as usize;
 
-    // TODO: Check padding, mac
+    if padding_length < 4 || padding_length + 1 > packet_length {
+      panic!("Invalid padding length {}", padding_length);
+    }
 
-    let mut reader = io::Cursor::new(payload);
+    let mut reader = io::Cursor::new(&packet[5 .. packet_length + 4 - padding_length]);
 
     return SSHPacket::read(&mut reader);
   }
 
   /// Understands and writes `SSHPacket` onto the transport socket.
   ///
-  /// Messages are padded, and that's taken into account.
+  /// Messages are padded to the cipher's block size, and encrypted and
+  /// followed by a MAC once keys were exchanged.
   pub fn write(&mut self, packet: &SSHPacket) {
     let mut writer = io::Cursor::new(Vec::new());
 
@@ -207,20 +508,58 @@ impl<'a> Transport<'a> {
 
     let payload = writer.into_inner();
 
-    let padding_length = 8 - (5 + payload.len()) % 8;
-    let padding_length = if padding_length < 4 { padding_length + 8 } else { padding_length };
+    let block_size = if self.outgoing_keys.is_some() { BLOCK_SIZE } else { 8 };
+
+    let padding_length = block_size - (5 + payload.len()) % block_size;
+    let padding_length = if padding_length < 4 { padding_length + block_size } else { padding_length };
+
+    let mut packet = io::Cursor::new(Vec::new());
+
+    packet.write_u32::<BigEndian>((payload.len() + padding_length + 1) as u32).unwrap();
+    packet.write_u8(padding_length as u8).unwrap();
+    packet.write_all(&payload[..]).unwrap();
+    packet.write_all(&rand::thread_rng().gen_iter::<u8>().take(padding_length).collect::<Vec<u8>>()[..]).unwrap();
+
+    let packet = packet.into_inner();
 
-    self.socket.write_u32::<BigEndian>((payload.len() + padding_length + 1) as u32).unwrap();
-    self.socket.write_u8(padding_length as u8).unwrap();
+    let data = match self.outgoing_keys {
+      Some(ref mut keys) => {
+        let mac = keys.mac(self.outgoing_sequence_number, &packet[..]);
 
-    let padding = vec![0u8; padding_length];
+        let mut data = keys.cipher.apply(&packet[..]);
+        data.extend(mac);
+        data
+      }
+      None => packet
+    };
+
+    self.outgoing_sequence_number = self.outgoing_sequence_number.wrapping_add(1);
 
-    self.socket.write_all(&payload[..]).unwrap();
-    self.socket.write_all(&padding[..]).unwrap();
-    // TODO: Calculate the MAC
+    self.socket.write_all(&data[..]).unwrap();
   }
 }
 
+impl<'a> PacketStream for Transport<'a> {
+  fn read(&mut self) -> SSHPacket {
+    return Transport::read(self);
+  }
+
+  fn write(&mut self, packet: &SSHPacket) {
+    Transport::write(self, packet);
+  }
+
+  fn session_identifier(&self) -> Option<&[u8]> {
+    return Transport::session_identifier(self);
+  }
+}
+
+/// Picks the first algorithm on the client's list that the server supports.
+///
+/// See [RFC 4253, section 7.1](https://tools.ietf.org/html/rfc4253#section-7.1).
+fn negotiate(client: &Vec<String>, server: &Vec<String>) -> Option<String> {
+  return client.iter().find(|a| { server.contains(a) }).cloned();
+}
+
 fn generate_key(hsh: &mut Hash, k: &BigInt, h: &[u8], c: &[u8], sid: &[u8]) -> Vec<u8> {
   let mut w = io::Cursor::new(Vec::new());
   w.write_mpint(k);
@@ -233,26 +572,91 @@ fn generate_key(hsh: &mut Hash, k: &BigInt, h: &[u8], c: &[u8], sid: &[u8]) -> V
   return hsh.digest();
 }
 
-fn mod_exp(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
-  let mut result: BigUint = One::one();
-  let mut base = base.to_biguint().unwrap();
-  let mut exponent = exponent.to_biguint().unwrap();
-  let modulus = modulus.to_biguint().unwrap();
-
-  while exponent > Zero::zero() {
-    let one: BigUint = One::one();
-    // Accumulate current base if current exponent bit is 1
-    if (&exponent & one) == One::one() {
-      result = result * &base;
-      result = result % &modulus;
-    }
-    // Get next base by squaring
-    base = &base * &base;
-    base = &base % &modulus;
+#[cfg(test)]
+mod tests {
+  use std::os::unix::net::UnixStream;
+  use std::thread;
+
+  use num::Num;
+  use num::bigint::{BigInt, ToBigInt};
 
-    // Get next bit of exponent
-    exponent = &exponent >> 1;
+  use keys::{ed25519, PrivateKey};
+
+  use packets::SSHPacket;
+  use packets::disconnect::Disconnect;
+
+  use transport::ssh_socket::Socket;
+  use transport::host_key::HostKeys;
+  use transport::moduli::Modulus;
+
+  use super::Transport;
+
+  /// The 1024 bit group of RFC 2409, section 6.2.
+  static MODULUS: &'static str = "FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE65381FFFFFFFFFFFFFFFF";
+
+  fn host_key() -> PrivateKey {
+    return PrivateKey::Ed25519(ed25519::PrivateKey::from_seed(&[5; 32]));
   }
 
-  return result.to_bigint().unwrap();
+  fn disconnect(message: &str) -> SSHPacket {
+    return SSHPacket::Disconnect(Disconnect { reason: 11, message: message.to_string(), language: String::new() });
+  }
+
+  #[test]
+  fn exchanges_keys_and_encrypts_packets() {
+    let (client_stream, server_stream) = UnixStream::pair().unwrap();
+
+    let server = thread::spawn(move || {
+      let mut reader = server_stream.try_clone().unwrap();
+      let mut writer = server_stream;
+
+      let mut host_keys = HostKeys::new();
+      host_keys.add(Box::new(host_key()));
+
+      let moduli = vec![Modulus {
+        timestamp: "20150101000000".to_string(),
+        kind: 2,
+        tests: 6,
+        trials: 100,
+        size: 1024,
+        generator: 2.to_bigint().unwrap(),
+        modulus: BigInt::from_str_radix(MODULUS, 16).unwrap()
+      }];
+
+      let mut socket = Socket::new_server(&mut reader, &mut writer);
+      let mut transport = Transport::new_server(&mut socket, host_keys, moduli);
+
+      let mut messages = Vec::new();
+
+      for _ in 0 .. 3 {
+        match transport.read() {
+          SSHPacket::Disconnect(d) => messages.push(d.message),
+          p => panic!("Unexpected packet {:?}", p)
+        }
+      }
+
+      transport.write(&disconnect("bye"));
+
+      return messages;
+    });
+
+    let mut reader = client_stream.try_clone().unwrap();
+    let mut writer = client_stream;
+
+    let mut socket = Socket::new(&mut reader, &mut writer);
+    let mut transport = Transport::new(&mut socket);
+
+    assert_eq!(transport.server_host_key().unwrap().blob(), host_key().public_key_blob());
+
+    transport.write(&disconnect("a"));
+    transport.write(&disconnect(&"long".repeat(100)));
+    transport.write(&disconnect("done"));
+
+    match transport.read() {
+      SSHPacket::Disconnect(d) => assert_eq!(d.message, "bye"),
+      p => panic!("Unexpected packet {:?}", p)
+    }
+
+    assert_eq!(server.join().unwrap(), vec!["a".to_string(), "long".repeat(100), "done".to_string()]);
+  }
 }